@@ -4,7 +4,9 @@ use std::time::Instant;
 
 use t10::data::Value;
 use t10::func::RustFunction;
-use t10::turbofan::rd93::{CompiledFuncInfo, CompiledProgram, Insc, RD93};
+use t10::intake::oxtank::OxTank;
+use t10::turbofan::f110::{self, Operand, F110};
+use t10::turbofan::rd93::{verify, CompiledFuncInfo, FlatProgram, CompiledProgram, Insc, RD93};
 
 #[cfg(not(debug_assertions))]
 const BENCH_RUNS: i32 = 10;
@@ -12,13 +14,35 @@ const BENCH_RUNS: i32 = 10;
 const BENCH_RUNS: i32 = 1;
 
 fn bench(program: &CompiledProgram, args: &[Value], outputs: &mut [MaybeUninit<Value>]) {
+    let program = verify(program).unwrap();
     for _ in 0..BENCH_RUNS {
         let start_time = Instant::now();
         unsafe {
-            RD93::run_func(&program, 0, args, outputs);
+            RD93::run_func(&program, 0, args, outputs, &mut OxTank::new_unlimited()).unwrap();
         };
         let end_time = Instant::now();
-        eprintln!("{} millis elapsed", (end_time - start_time).as_millis());
+        eprintln!("match: {} millis elapsed", (end_time - start_time).as_millis());
+    }
+
+    let program = FlatProgram::new(&program).unwrap();
+    for _ in 0..BENCH_RUNS {
+        let start_time = Instant::now();
+        unsafe {
+            RD93::run_flat_func(&program, 0, args, outputs, &mut OxTank::new_unlimited()).unwrap();
+        };
+        let end_time = Instant::now();
+        eprintln!("flat: {} millis elapsed", (end_time - start_time).as_millis());
+    }
+}
+
+fn bench_f110(program: &f110::CompiledProgram, args: &[Value], outputs: &mut [MaybeUninit<Value>]) {
+    for _ in 0..BENCH_RUNS {
+        let start_time = Instant::now();
+        unsafe {
+            F110::run_func(program, 0, args, outputs, &mut OxTank::new_unlimited()).unwrap();
+        };
+        let end_time = Instant::now();
+        eprintln!("f110: {} millis elapsed", (end_time - start_time).as_millis());
     }
 }
 
@@ -43,6 +67,29 @@ fn bench_fib35() {
         CompiledFuncInfo::new(0, 1, 1, 4),
     ], vec![]);
     bench(&program, &[Value::from(35i64)], &mut [MaybeUninit::uninit()]);
+
+    let program = f110::CompiledProgram::new(vec![
+        // fibonacci(n int @0) -> int
+        /*00*/ f110::Insc::IntLe {
+            lhs: Operand::stack_value(0), rhs: Operand::constant(1), dest_value: 1
+        },
+        /*01*/ f110::Insc::JumpIfTrue { cond: Operand::stack_value(1), jump_dest: 7 },
+        /*02*/ f110::Insc::IntSub {
+            lhs: Operand::stack_value(0), rhs: Operand::constant(1), dest_value: 1
+        },
+        /*03*/ f110::Insc::IntSub {
+            lhs: Operand::stack_value(0), rhs: Operand::constant(2), dest_value: 2
+        },
+        /*04*/ f110::Insc::FuncCall { func_id: 0, arg_values: vec![1], ret_value_locs: vec![1] },
+        /*05*/ f110::Insc::FuncCall { func_id: 0, arg_values: vec![2], ret_value_locs: vec![2] },
+        /*06*/ f110::Insc::IntAdd {
+            lhs: Operand::stack_value(1), rhs: Operand::stack_value(2), dest_value: 0
+        },
+        /*07*/ f110::Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 3),
+    ], vec![]);
+    bench_f110(&program, &[Value::from(35i64)], &mut [MaybeUninit::uninit()]);
 }
 
 fn bench_loop100m() {
@@ -65,6 +112,31 @@ fn bench_loop100m() {
         CompiledFuncInfo::new(0, 0, 0, 4)
     ], vec![]);
     bench(&program, &[], &mut []);
+
+    let program = f110::CompiledProgram::new(vec![
+        // application_start() -> void
+        /*00*/ f110::Insc::MakeIntConst { c: 1, dest_value: 0 },
+        /*01*/ f110::Insc::IntGt {
+            lhs: Operand::stack_value(0), rhs: Operand::constant(10000), dest_value: 2
+        },
+        /*02*/ f110::Insc::JumpIfTrue { cond: Operand::stack_value(2), jump_dest: 11 },
+        /*03*/ f110::Insc::MakeIntConst { c: 1, dest_value: 1 },
+        /*04*/ f110::Insc::IntGt {
+            lhs: Operand::stack_value(1), rhs: Operand::constant(10000), dest_value: 2
+        },
+        /*05*/ f110::Insc::JumpIfTrue { cond: Operand::stack_value(2), jump_dest: 9 },
+        /*06*/ f110::Insc::IntAdd {
+            lhs: Operand::stack_value(0), rhs: Operand::stack_value(1), dest_value: 3
+        },
+        /*07*/ f110::Insc::Incr { value: 1 },
+        /*08*/ f110::Insc::Jump { jump_dest: 4 },
+        /*09*/ f110::Insc::Incr { value: 0 },
+        /*10*/ f110::Insc::Jump { jump_dest: 1 },
+        /*11*/ f110::Insc::ReturnNothing
+    ], vec![
+        CompiledFuncInfo::new(0, 0, 0, 4)
+    ], vec![]);
+    bench_f110(&program, &[], &mut []);
 }
 
 fn baz(x: i64, y: i64) -> i64 {
@@ -93,6 +165,33 @@ fn bench_ffi100m() {
         Box::new(RustFunction { f: baz, _phantom: PhantomData::default() })
     ]);
     bench(&program, &[], &mut []);
+
+    let program = f110::CompiledProgram::new(vec![
+        // application_start() -> void
+        /*00*/ f110::Insc::MakeIntConst { c: 1, dest_value: 0 },
+        /*01*/ f110::Insc::IntGt {
+            lhs: Operand::stack_value(0), rhs: Operand::constant(10000), dest_value: 2
+        },
+        /*02*/ f110::Insc::JumpIfTrue { cond: Operand::stack_value(2), jump_dest: 11 },
+        /*03*/ f110::Insc::MakeIntConst { c: 1, dest_value: 1 },
+        /*04*/ f110::Insc::IntGt {
+            lhs: Operand::stack_value(1), rhs: Operand::constant(10000), dest_value: 2
+        },
+        /*05*/ f110::Insc::JumpIfTrue { cond: Operand::stack_value(2), jump_dest: 9 },
+        /*06*/ f110::Insc::FFICall {
+            func_id: 0, arg_values: vec![0, 1], ret_value_locs: vec![3]
+        },
+        /*07*/ f110::Insc::Incr { value: 1 },
+        /*08*/ f110::Insc::Jump { jump_dest: 4 },
+        /*09*/ f110::Insc::Incr { value: 0 },
+        /*10*/ f110::Insc::Jump { jump_dest: 1 },
+        /*11*/ f110::Insc::ReturnNothing
+    ], vec![
+        CompiledFuncInfo::new(0, 0, 0, 4)
+    ], vec![
+        Box::new(RustFunction { f: baz, _phantom: PhantomData })
+    ]);
+    bench_f110(&program, &[], &mut []);
 }
 
 fn main() {
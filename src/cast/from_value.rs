@@ -116,7 +116,11 @@ impl<T> FromValue<Option<T>> for Void where Void: FromValueL1<T> {
     }
 
     unsafe fn from_value(value: &Value) -> Option<T> {
-        Some(<Void as FromValueL1<T>>::from_value_l1(value))
+        if value.is_null() {
+            None
+        } else {
+            Some(<Void as FromValueL1<T>>::from_value_l1(value))
+        }
     }
 }
 
@@ -212,44 +216,54 @@ const VALUE_TYPE_LIFETIMES: [GcInfo; 4] = [
     GcInfo::SharedToHost,
     GcInfo::TempObject
 ];
-impl FromValueL2<i64> for Void {
-    #[inline] unsafe fn lifetime_check_l2(value: &Value) -> Result<GcInfoGuard, TError> {
-        let actual = value.gc_info();
-        if actual as u8 & GCINFO_READ_MASK != 0 {
-            Ok(GcInfoGuard::no_action(value))
-        } else {
-            Err(LifetimeError::new(&VALUE_TYPE_LIFETIMES, FFIAction::Copy, actual).into())
-        }
-    }
+/// 为 `i64` 一类的值类型生成 `FromValueL2` 实现，这些类型的值直接存储在 `Value` 中
+macro_rules! impl_value_type_from_value {
+    ($ty:ty, $field:ident) => {
+        impl FromValueL2<$ty> for Void {
+            #[inline] unsafe fn lifetime_check_l2(value: &Value) -> Result<GcInfoGuard, TError> {
+                let actual = value.gc_info();
+                if actual as u8 & GCINFO_READ_MASK != 0 {
+                    Ok(GcInfoGuard::no_action(value))
+                } else {
+                    Err(LifetimeError::new(&VALUE_TYPE_LIFETIMES, FFIAction::Copy, actual).into())
+                }
+            }
 
-    #[cfg(not(debug_assertions))]
-    #[inline] unsafe fn from_value_l2(value: &Value) -> i64 {
-        if value.is_value() {
-            value.value_typed_data.inner.int
-        } else {
-            let mut ret: MaybeUninit<i64> = MaybeUninit::uninit();
-            value.ptr.as_mut().unwrap_unchecked().move_out(
-                &mut ret as *mut MaybeUninit<_> as *mut ()
-            );
-            ret.assume_init()
-        }
-    }
+            #[cfg(not(debug_assertions))]
+            #[inline] unsafe fn from_value_l2(value: &Value) -> $ty {
+                if value.is_value() {
+                    value.value_typed_data.inner.$field
+                } else {
+                    let mut ret: MaybeUninit<$ty> = MaybeUninit::uninit();
+                    value.move_out(
+                        &mut ret as *mut MaybeUninit<_> as *mut ()
+                    );
+                    ret.assume_init()
+                }
+            }
 
-    #[cfg(debug_assertions)]
-    #[inline] unsafe fn from_value_l2(value: &Value) -> i64 {
-        if value.is_value() {
-            value.value_typed_data.inner.int
-        } else {
-            let mut ret: MaybeUninit<i64> = MaybeUninit::uninit();
-            value.ptr.as_mut().unwrap_unchecked().move_out_ck(
-                &mut ret as *mut MaybeUninit<_> as *mut (),
-                std::any::TypeId::of::<MaybeUninit<i64>>()
-            );
-            ret.assume_init()
+            #[cfg(debug_assertions)]
+            #[inline] unsafe fn from_value_l2(value: &Value) -> $ty {
+                if value.is_value() {
+                    value.value_typed_data.inner.$field
+                } else {
+                    let mut ret: MaybeUninit<$ty> = MaybeUninit::uninit();
+                    value.move_out_ck(
+                        &mut ret as *mut MaybeUninit<_> as *mut (),
+                        std::any::TypeId::of::<$ty>()
+                    );
+                    ret.assume_init()
+                }
+            }
         }
-    }
+    };
 }
 
+impl_value_type_from_value!(i64, int);
+impl_value_type_from_value!(f64, float);
+impl_value_type_from_value!(char, ch);
+impl_value_type_from_value!(bool, boolean);
+
 const MOVE_TYPE_LIFETIMES: [GcInfo; 1] = [ GcInfo::Owned ];
 impl<T> FromValueL3<T> for Void where Void: StaticBase<T> {
     #[inline] default unsafe fn lifetime_check_l3(value: &Value) -> Result<GcInfoGuard, TError> {
@@ -264,14 +278,14 @@ impl<T> FromValueL3<T> for Void where Void: StaticBase<T> {
 
     #[cfg(not(debug_assertions))]
     #[inline] default unsafe fn from_value_l3(value: &Value, out: &mut MaybeUninit<T>) {
-        value.ptr.as_mut().unwrap_unchecked().move_out(
+        value.move_out(
             out as *mut MaybeUninit<_> as *mut ()
         );
     }
 
     #[cfg(debug_assertions)]
     #[inline] default unsafe fn from_value_l3(value: &Value, out: &mut MaybeUninit<T>) {
-        value.ptr.as_mut().unwrap_unchecked().move_out_ck(
+        value.move_out_ck(
             out as *mut MaybeUninit<_> as *mut (),
             <Void as StaticBase<T>>::base_type_id()
         );
@@ -1,11 +1,22 @@
 //! 从 Rust 对象到 `Value` 的转换
 
 use std::error::Error;
+use std::mem::MaybeUninit;
 
 use crate::data::{Value, StaticWrapper, DynBase};
 use crate::error::TError;
 use crate::void::Void;
 
+/// 在这一层 specialization 中特殊处理元组 `(A, B, ...)` 和 `Result<(A, B, ...), E>`
+///
+/// 元组中的每个元素会被分别写入 `dest` 中对应的返回值位置，`()` 不写入任何返回值。其他类型都视为
+/// 单个返回值，写入 `dest[0]`。
+pub trait IntoValueMulti<T> {
+    /// # Safety
+    /// `dest` 的长度必须与 `T` 所对应的返回值个数相同
+    unsafe fn into_value_multi(t: T, dest: &mut [&mut MaybeUninit<Value>]) -> Result<(), TError>;
+}
+
 /// 在这一层 specialization 中特殊处理 `Result<T, E>`
 ///
 /// 对 `Result<T, E>` 的处理只在从 Rust 函数向 T10 运行时返回一个值的时候进行。
@@ -35,10 +46,99 @@ pub trait IntoValueL2<T> {
 }
 
 /// 在这一层的 specialization 中特殊处理 `i64` 之类的值类型
+///
+/// 其他类型的值会被包装成新分配的 `StaticWrapper`，所得到的堆对象归调用者所有：调用者应当将其交给
+/// `Adopt::adopt` 管理，或者在不再使用时通过 `Value::drop_object` 释放。VM 执行 FFI 调用时会
+/// 将宿主函数的返回值交给运行时传入的 `intake`。
 pub trait IntoValueL3<T> {
      fn into_value_l3(t: T) -> Result<Value, TError>;
 }
 
+impl<T> IntoValueMulti<T> for Void where Void: IntoValue<T> {
+    #[inline] default unsafe fn into_value_multi(
+        t: T,
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        debug_assert_eq!(dest.len(), 1);
+        let value = <Void as IntoValue<T>>::into_value(t)?;
+        dest.get_unchecked_mut(0).write(value);
+        Ok(())
+    }
+}
+
+impl IntoValueMulti<()> for Void {
+    #[inline] unsafe fn into_value_multi(
+        _t: (),
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        debug_assert_eq!(dest.len(), 0);
+        Ok(())
+    }
+}
+
+impl<E> IntoValueMulti<Result<(), E>> for Void where E: 'static + Error {
+    #[inline] unsafe fn into_value_multi(
+        t: Result<(), E>,
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        debug_assert_eq!(dest.len(), 0);
+        t.map_err(TError::user_exception)
+    }
+}
+
+/// 为 `(A, B, ...)` 和 `Result<(A, B, ...), E>` 生成 `IntoValueMulti` 实现
+macro_rules! impl_into_value_multi_tuple {
+    ($count:expr $(, ($elem_ty:ident, $elem:ident, $idx:expr))*) => {
+        impl<$($elem_ty: 'static,)*> IntoValueMulti<($($elem_ty,)*)> for Void
+            where $(Void: IntoValueNoexcept<$elem_ty>,)*
+        {
+            #[inline] unsafe fn into_value_multi(
+                t: ($($elem_ty,)*),
+                dest: &mut [&mut MaybeUninit<Value>]
+            ) -> Result<(), TError> {
+                debug_assert_eq!(dest.len(), $count);
+                let ($($elem,)*) = t;
+                $(
+                    match <Void as IntoValueNoexcept<$elem_ty>>::into_value_noexcept($elem) {
+                        Ok(value) => {
+                            dest.get_unchecked_mut($idx).write(value);
+                        },
+                        Err(e) => {
+                            // 释放已经转换的元素，尚未转换的元素随元组一起丢弃
+                            for converted in dest.get_unchecked_mut(..$idx) {
+                                converted.assume_init_read().drop_object();
+                            }
+                            return Err(e);
+                        }
+                    }
+                )*
+                Ok(())
+            }
+        }
+
+        impl<$($elem_ty: 'static,)* EXC> IntoValueMulti<Result<($($elem_ty,)*), EXC>> for Void
+            where $(Void: IntoValueNoexcept<$elem_ty>,)*
+                  EXC: 'static + Error
+        {
+            #[inline] unsafe fn into_value_multi(
+                t: Result<($($elem_ty,)*), EXC>,
+                dest: &mut [&mut MaybeUninit<Value>]
+            ) -> Result<(), TError> {
+                match t {
+                    Ok(data) => <Void as IntoValueMulti<($($elem_ty,)*)>>::into_value_multi(data, dest),
+                    Err(e) => Err(TError::user_exception(e))
+                }
+            }
+        }
+    };
+}
+
+impl_into_value_multi_tuple!(2, (A, a, 0), (B, b, 1));
+impl_into_value_multi_tuple!(3, (A, a, 0), (B, b, 1), (C, c, 2));
+impl_into_value_multi_tuple!(4, (A, a, 0), (B, b, 1), (C, c, 2), (D, d, 3));
+impl_into_value_multi_tuple!(5, (A, a, 0), (B, b, 1), (C, c, 2), (D, d, 3), (E, e, 4));
+impl_into_value_multi_tuple!(6, (A, a, 0), (B, b, 1), (C, c, 2), (D, d, 3), (E, e, 4), (F, f, 5));
+
 impl<T> IntoValue<T> for Void where Void: IntoValueNoexcept<T> {
     default fn into_value(t: T) -> Result<Value, TError> {
         <Void as IntoValueNoexcept<T>>::into_value_noexcept(t)
@@ -107,33 +207,88 @@ impl<T> IntoValueL2<T> for Void where T: 'static {
 
 impl<T> IntoValueL2<&'static T> for Void where T: 'static {
     #[inline] fn into_value_l2(t: &'static T) -> Result<Value, TError> {
-        let wrapper = Box::leak(Box::new(StaticWrapper::shared(t)));
-        Ok(Value::from(wrapper as &mut dyn DynBase as *mut dyn DynBase))
+        let wrapper = Box::into_raw(Box::new(StaticWrapper::shared(t)));
+        Ok(Value::from(wrapper as *mut dyn DynBase))
     }
 }
 
 impl<T> IntoValueL2<&'static mut T> for Void where T: 'static {
     #[inline] fn into_value_l2(t: &'static mut T) -> Result<Value, TError> {
-        let wrapper = Box::leak(Box::new(StaticWrapper::mut_shared(t)));
-        Ok(Value::from(wrapper as &mut dyn DynBase as *mut dyn DynBase))
+        let wrapper = Box::into_raw(Box::new(StaticWrapper::mut_shared(t)));
+        Ok(Value::from(wrapper as *mut dyn DynBase))
     }
 }
 
-impl IntoValueL2<i64> for Void {
-    #[inline] fn into_value_l2(t: i64) -> Result<Value, TError> {
-        Ok(Value::from(t))
-    }
+/// 为 `i64` 一类的值类型生成 `IntoValueL2` 和 `IntoValueL3` 实现，这些类型的值直接存储在
+/// `Value` 中，不需要包装成 `StaticWrapper`
+macro_rules! impl_value_type_into_value {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoValueL2<$ty> for Void {
+                #[inline] fn into_value_l2(t: $ty) -> Result<Value, TError> {
+                    Ok(Value::from(t))
+                }
+            }
+
+            impl IntoValueL3<$ty> for Void {
+                #[inline] fn into_value_l3(t: $ty) -> Result<Value, TError> {
+                    Ok(Value::from(t))
+                }
+            }
+        )*
+    };
 }
 
+impl_value_type_into_value!(i64, f64, char, bool);
+
 impl<T> IntoValueL3<T> for Void where T: 'static {
     #[inline] default fn into_value_l3(t: T) -> Result<Value, TError> {
-        let wrapper = Box::leak(Box::new(StaticWrapper::owned(t)));
-        Ok(Value::from(wrapper as &mut dyn DynBase as *mut dyn DynBase))
+        let wrapper = Box::into_raw(Box::new(StaticWrapper::owned(t)));
+        Ok(Value::from(wrapper as *mut dyn DynBase))
     }
 }
 
-impl IntoValueL3<i64> for Void {
-    #[inline] fn into_value_l3(t: i64) -> Result<Value, TError> {
-        Ok(Value::from(t))
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::mem::MaybeUninit;
+
+    use crate::cast::into_value::{IntoValueMulti, IntoValueNoexcept};
+    use crate::data::Value;
+    use crate::error::{NullError, TError};
+    use crate::void::Void;
+
+    thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Tracked ();
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+
+    struct Failing ();
+
+    impl IntoValueNoexcept<Failing> for Void {
+        fn into_value_noexcept(_t: Failing) -> Result<Value, TError> {
+            Err(TError::from(NullError()))
+        }
+    }
+
+    #[test]
+    fn test_into_value_multi_failure_drops_converted() {
+        let mut slots = [MaybeUninit::uninit(); 3];
+        let [a, b, c] = &mut slots;
+        let mut dest = [a, b, c];
+        let result = unsafe {
+            <Void as IntoValueMulti<(Tracked, Failing, Tracked)>>::into_value_multi(
+                (Tracked(), Failing(), Tracked()), &mut dest
+            )
+        };
+        assert!(matches!(result, Err(TError::NullError(_))));
+        assert_eq!(DROPS.with(Cell::get), 2);
     }
 }
@@ -6,11 +6,12 @@
 
 use std::any::{TypeId, type_name};
 use std::marker::PhantomData;
-use std::mem::{ManuallyDrop, MaybeUninit, transmute};
+use std::mem::{ManuallyDrop, MaybeUninit, size_of, transmute};
 use std::ptr::NonNull;
 
 use crate::tyck::TypeCheckInfo;
 use crate::tyck::base::StaticBase;
+use crate::tyck::names;
 use crate::util::FatPointer;
 use crate::void::Void;
 
@@ -63,6 +64,8 @@ union WrapperData<T> {
 pub struct Wrapper<'a, Ta: 'a, Ts: 'static> {
     gc_info: u8,
     data_offset: u8,
+    /// 数据是否借用自 Rust 一侧，借用的数据在任何状态下都不会被丢弃
+    borrowed: bool,
     data: WrapperData<Ta>,
     _phantom: PhantomData<&'a Ts>
 }
@@ -75,6 +78,7 @@ impl<'a, Ta: 'a, Ts: 'static> Wrapper<'a, Ta, Ts> {
             },
             data_offset: 0,
             gc_info: GcInfo::Owned as u8,
+            borrowed: false,
             _phantom: PhantomData::default()
         };
         r.data_offset = (&r.data as *const _ as *const () as usize - &r as *const _ as *const () as usize) as u8;
@@ -88,6 +92,7 @@ impl<'a, Ta: 'a, Ts: 'static> Wrapper<'a, Ta, Ts> {
             },
             data_offset: 0,
             gc_info: GcInfo::SharedFromHost as u8,
+            borrowed: true,
             _phantom: PhantomData::default()
         };
         r.data_offset = (&r.data as *const _ as *const () as usize - &r as *const _ as *const () as usize) as u8;
@@ -101,6 +106,7 @@ impl<'a, Ta: 'a, Ts: 'static> Wrapper<'a, Ta, Ts> {
             },
             data_offset: 0,
             gc_info: GcInfo::MutSharedFromHost as u8,
+            borrowed: true,
             _phantom: PhantomData::default()
         };
         r.data_offset = (&r.data as *const _ as *const () as usize - &r as *const _ as *const () as usize) as u8;
@@ -120,9 +126,15 @@ impl<'a, Ta: 'a, Ts: 'static> Wrapper<'a, Ta, Ts> {
     }
 }
 
+/// 包装对象被释放时，根据 `GcInfo` 决定是否丢弃其中的数据
+///
+/// `Owned` 和 `Dropped` 状态的对象拥有自己的数据，需要一并丢弃；`MovedToHost` 状态的对象中的数据
+/// 已经被移动到了 Rust 一侧；`SharedFromHost` 和 `MutSharedFromHost` 状态的对象只持有指向
+/// Rust 一侧数据的指针，数据归 Rust 所有。借用结束之后，这类对象会被标记为 `Dropped`，
+/// 但其中的数据仍然不会被丢弃。
 impl<'a, Ta: 'a, Ts: 'static> Drop for Wrapper<'a, Ta, Ts> {
     fn drop(&mut self) {
-        if false /* TODO use the real condition here */ {
+        if !self.borrowed && matches!(GcInfo::from(self.gc_info), GcInfo::Owned | GcInfo::Dropped) {
             unsafe {
                 ManuallyDrop::take(&mut self.data.value).assume_init_drop();
             }
@@ -145,6 +157,10 @@ pub trait DynBase {
     fn dyn_tyck(&self, tyck_info: &TypeCheckInfo) -> bool;
     /// 运行时获取类型检查信息
     fn dyn_tyck_info(&self) -> TypeCheckInfo;
+    /// 垃圾回收时访问对象中存储的 `Value`
+    fn dyn_trace(&self, visit: &mut dyn FnMut(&Value));
+    /// 对象所拥有的、在包装对象之外的堆内存字节数，参见 `HeapSize`
+    fn dyn_heap_size(&self) -> usize;
 
     /// 将数据移动到 dest 中。dest 应为一个 `MaybeUninit`
     #[cfg(not(debug_assertions))]
@@ -152,17 +168,19 @@ pub trait DynBase {
 
     /// 将数据移动到 dest中。dest 应为一个 `MaybeUninit`
     ///
-    /// 这是带有运行时类型检查的版本，在 debug 模式下使用
+    /// 这是带有运行时类型检查的版本，在 debug 模式下使用。`dest_ty` 是 dest 中所存数据类型的
+    /// `TypeId`
     #[cfg(debug_assertions)]
     unsafe fn move_out_ck(&mut self, dest: *mut (), dest_ty: TypeId);
 }
 
 impl<'a, Ta: 'a, Ts: 'static> DynBase for Wrapper<'a, Ta, Ts> {
     fn dyn_type_id(&self) -> TypeId {
-        TypeId::of::<Ts>()
+        <Void as StaticBase<Ts>>::base_type_id()
     }
 
     fn dyn_type_name(&self) -> String {
+        names::register_name(TypeId::of::<Ts>(), type_name::<Ta>());
         type_name::<Ta>().to_string()
     }
 
@@ -174,6 +192,25 @@ impl<'a, Ta: 'a, Ts: 'static> DynBase for Wrapper<'a, Ta, Ts> {
         <Void as StaticBase<Ts>>::tyck_info()
     }
 
+    fn dyn_trace(&self, visit: &mut dyn FnMut(&Value)) {
+        unsafe {
+            match GcInfo::from(self.gc_info) {
+                GcInfo::MovedToHost | GcInfo::Dropped => {},
+                _ if self.borrowed => self.data.ptr.as_ref().trace(visit),
+                _ => self.data.value.assume_init_ref().trace(visit)
+            }
+        }
+    }
+
+    fn dyn_heap_size(&self) -> usize {
+        match GcInfo::from(self.gc_info) {
+            GcInfo::MovedToHost | GcInfo::Dropped => 0,
+            // 从 Rust 共享过来的数据归 Rust 一侧所有
+            _ if self.borrowed => 0,
+            _ => unsafe { self.data.value.assume_init_ref().heap_size() }
+        }
+    }
+
     #[cfg(not(debug_assertions))]
     unsafe fn move_out(&mut self, dest: *mut ()) {
         let dest = (dest as *mut MaybeUninit<Ta>).as_mut().unwrap();
@@ -182,15 +219,52 @@ impl<'a, Ta: 'a, Ts: 'static> DynBase for Wrapper<'a, Ta, Ts> {
 
     #[cfg(debug_assertions)]
     unsafe fn move_out_ck(&mut self, dest: *mut (), dest_ty: TypeId) {
-        debug_assert_eq!(GcInfo::from(self.gc_info), GcInfo::Owned);
-        debug_assert_eq!(dest_ty, TypeId::of::<MaybeUninit<Ts>>());
+        debug_assert!(matches!(GcInfo::from(self.gc_info), GcInfo::Owned | GcInfo::MovedToHost));
+        debug_assert_eq!(dest_ty, <Void as StaticBase<Ts>>::base_type_id());
         let dest = (dest as *mut MaybeUninit<Ta>).as_mut().unwrap();
         dest.write(self.take_value());
     }
 }
 
+/// 负责在垃圾回收时给出对象中存储的 `Value`
+///
+/// 存储了 `Value` 的类型（例如 `VMVec`）需要实现这个 trait，否则其中引用的对象可能被错误地回收
+pub trait Trace {
+    fn trace(&self, visit: &mut dyn FnMut(&Value));
+}
+
+impl<T> Trace for T {
+    #[inline] default fn trace(&self, _visit: &mut dyn FnMut(&Value)) {}
+}
+
+/// 给出对象在自身之外拥有的堆内存字节数，分配器据此统计内存用量
+///
+/// 默认实现返回 0。拥有堆内存的类型（例如 `String` 和 `Vec<T>`）应当实现这个 trait，否则
+/// `OxTank` 的内存上限只能限制包装对象本身的大小。
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl<T> HeapSize for T {
+    #[inline] default fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for String {
+    #[inline] fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
 /// “值类型对象”的类型标记
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub enum ValueType {
     Int     = 0b00000100,
@@ -217,6 +291,34 @@ impl From<u8> for ValueType {
     }
 }
 
+impl ValueType {
+    /// 值类型对应的 Rust 类型的 `TypeId`，动态类型的值以 `Value` 本身作为其类型
+    pub fn type_id(self) -> TypeId {
+        match self {
+            ValueType::Int => TypeId::of::<i64>(),
+            ValueType::Float => TypeId::of::<f64>(),
+            ValueType::Char => TypeId::of::<char>(),
+            ValueType::Bool => TypeId::of::<bool>(),
+            ValueType::AnyType => TypeId::of::<Value>()
+        }
+    }
+
+    /// 如果 `type_id` 是某个值类型对应的 Rust 类型，返回这个值类型
+    pub fn from_type_id(type_id: TypeId) -> Option<ValueType> {
+        if type_id == TypeId::of::<i64>() {
+            Some(ValueType::Int)
+        } else if type_id == TypeId::of::<f64>() {
+            Some(ValueType::Float)
+        } else if type_id == TypeId::of::<char>() {
+            Some(ValueType::Char)
+        } else if type_id == TypeId::of::<bool>() {
+            Some(ValueType::Bool)
+        } else {
+            None
+        }
+    }
+}
+
 pub(crate) const VALUE_MASK      : u8 = 0b00000001;
 pub(crate) const CONTAINER_MASK  : u8 = 0b00000010;
 pub(crate) const VALUE_TYPE_MASK : u8 = 0b00011100;
@@ -258,6 +360,7 @@ pub struct CustomVTable {
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct CustomFatPtr {
+    /// 指向容器对象的指针，其中 `CONTAINER_MASK` 位被置为 1
     data: *mut (),
     vtable: *const CustomVTable
 }
@@ -284,6 +387,25 @@ impl Value {
         }
     }
 
+    /// 创建一个指向容器对象的 `Value`，容器对象的类型信息由 `vtable` 给出
+    ///
+    /// `data` 至少需要按 4 字节对齐，以便存放 `CONTAINER_MASK` 标记
+    #[inline] pub fn from_container(data: *mut (), vtable: *const CustomVTable) -> Self {
+        debug_assert_eq!(data as usize & (VALUE_MASK | CONTAINER_MASK) as usize, 0);
+        Self {
+            custom_fat_ptr: CustomFatPtr {
+                data: (data as usize | CONTAINER_MASK as usize) as *mut (),
+                vtable
+            }
+        }
+    }
+
+    #[inline] pub fn is_container(&self) -> bool {
+        unsafe {
+            self.ptr_inner.part1 as u8 & (VALUE_MASK | CONTAINER_MASK) == CONTAINER_MASK
+        }
+    }
+
     #[inline] pub fn is_null(&self) -> bool {
         unsafe {
             self.ptr_inner.part1 == 0
@@ -311,7 +433,8 @@ impl Value {
                 ValueType::Float => TypeId::of::<f64>(),
                 ValueType::Char => TypeId::of::<char>(),
                 ValueType::Bool => TypeId::of::<bool>(),
-                ValueType::AnyType => todo!("What data type should we use for this?")
+                // 动态类型的值没有对应的 Rust 类型，以 `Value` 本身作为其类型
+                ValueType::AnyType => TypeId::of::<Value>()
             }
         } else if self.ptr_inner.part1 as u8 & CONTAINER_MASK != 0 {
             let f = (*self.custom_fat_ptr.vtable).dyn_type_id;
@@ -321,35 +444,158 @@ impl Value {
         }
     }
 
+    /// # Safety
+    /// requires the data to be not null
+    pub unsafe fn type_name(&self) -> String {
+        if self.ptr_inner.part1 as u8 & VALUE_MASK != 0 {
+            match ValueType::from(self.ptr_inner.part1 as u8 & VALUE_TYPE_MASK) {
+                ValueType::Int => type_name::<i64>().to_string(),
+                ValueType::Float => type_name::<f64>().to_string(),
+                ValueType::Char => type_name::<char>().to_string(),
+                ValueType::Bool => type_name::<bool>().to_string(),
+                ValueType::AnyType => "any".to_string()
+            }
+        } else if self.ptr_inner.part1 as u8 & CONTAINER_MASK != 0 {
+            let f = (*self.custom_fat_ptr.vtable).dyn_type_name;
+            f(self.custom_fat_ptr.vtable)
+        } else {
+            self.ptr.as_ref().unwrap_unchecked().dyn_type_name()
+        }
+    }
+
+    /// 根据类型检查信息 `tyck_info` 判断 `Value` 的类型是否兼容
+    ///
+    /// # Safety
+    /// requires the data to be not null
+    pub unsafe fn type_check(&self, tyck_info: &TypeCheckInfo) -> bool {
+        match tyck_info {
+            TypeCheckInfo::Bypass => true,
+            TypeCheckInfo::SimpleType(tid) => self.type_id() == *tid,
+            TypeCheckInfo::Container(..) => if self.is_container() {
+                let vtable = self.custom_fat_ptr.vtable;
+                ((*vtable).dyn_tyck)(vtable, tyck_info)
+            } else if self.is_ptr() {
+                self.ptr.as_ref().unwrap_unchecked().dyn_tyck(tyck_info)
+            } else {
+                false
+            }
+        }
+    }
+
+    /// 若 `Value` 是一个值类型数据，返回其类型标记
+    #[inline] pub fn value_type(&self) -> Option<ValueType> {
+        if self.is_value() {
+            unsafe { Some(ValueType::from(self.ptr_inner.part1 as u8 & VALUE_TYPE_MASK)) }
+        } else {
+            None
+        }
+    }
+
+    #[inline] pub fn as_int(&self) -> Option<i64> {
+        match self.value_type() {
+            Some(ValueType::Int) => unsafe { Some(self.value_typed_data.inner.int) },
+            _ => None
+        }
+    }
+
+    #[inline] pub fn as_float(&self) -> Option<f64> {
+        match self.value_type() {
+            Some(ValueType::Float) => unsafe { Some(self.value_typed_data.inner.float) },
+            _ => None
+        }
+    }
+
+    #[inline] pub fn as_char(&self) -> Option<char> {
+        match self.value_type() {
+            Some(ValueType::Char) => unsafe { Some(self.value_typed_data.inner.ch) },
+            _ => None
+        }
+    }
+
+    #[inline] pub fn as_bool(&self) -> Option<bool> {
+        match self.value_type() {
+            Some(ValueType::Bool) => unsafe { Some(self.value_typed_data.inner.boolean) },
+            _ => None
+        }
+    }
+
+    /// 堆对象的起始地址，对于容器类型会去掉 `CONTAINER_MASK` 标记
+    #[inline] fn header(&self) -> *mut u8 {
+        unsafe {
+            (self.ptr_inner.part1 & !(CONTAINER_MASK as usize)) as *mut u8
+        }
+    }
+
+    /// 将 `Value` 所指向的对象中的数据移动到 `dest` 中，`dest` 应为一个 `MaybeUninit`
+    ///
+    /// # Safety
+    /// `Value` 必须指向一个堆对象或者容器对象，并且其中的数据尚未被移走
+    #[cfg(not(debug_assertions))]
+    #[inline] pub unsafe fn move_out(&self, dest: *mut ()) {
+        if self.is_container() {
+            let vtable = self.custom_fat_ptr.vtable;
+            ((*vtable).move_out)(self.header() as *mut (), vtable, dest)
+        } else {
+            self.ptr.as_mut().unwrap_unchecked().move_out(dest)
+        }
+    }
+
+    /// 将 `Value` 所指向的对象中的数据移动到 `dest` 中，这是带有运行时类型检查的版本
+    ///
+    /// # Safety
+    /// 同 `move_out`
+    #[cfg(debug_assertions)]
+    #[inline] pub unsafe fn move_out_ck(&self, dest: *mut (), dest_ty: TypeId) {
+        if self.is_container() {
+            let vtable = self.custom_fat_ptr.vtable;
+            ((*vtable).move_out_ck)(self.header() as *mut (), vtable, dest, dest_ty)
+        } else {
+            self.ptr.as_mut().unwrap_unchecked().move_out_ck(dest, dest_ty)
+        }
+    }
+
     #[inline] pub fn gc_info(&self) -> GcInfo {
         if self.is_value() {
             GcInfo::TempObject
         } else {
             unsafe {
-                GcInfo::from(*(self.ptr as *mut u8))
+                GcInfo::from(*self.header())
             }
         }
     }
 
     #[inline] pub unsafe fn set_gc_info(&self, gc_info: GcInfo) {
         if self.is_ptr() {
-            *(self.ptr as *mut u8) = gc_info as u8;
+            *self.header() = gc_info as u8;
         } else {
             // do nothing, does not matter
         }
     }
 
+    /// 释放 `Value` 所指向的堆对象，对象中的数据是否被丢弃由其 `GcInfo` 决定。对于值类型数据和
+    /// 空值，不进行任何操作
+    ///
+    /// # Safety
+    /// `Value` 所指向的堆对象必须是通过 `Box` 分配的，并且不归任何 `OxProvider` 管理。释放之后，
+    /// 所有指向这个对象的 `Value` 都不能再使用
+    pub unsafe fn drop_object(self) {
+        if self.is_ptr() && !self.is_null() {
+            debug_assert!(!self.is_container());
+            drop(Box::from_raw(self.ptr));
+        }
+    }
+
     #[inline] pub unsafe fn as_ref<'a, T>(&self) -> &'a T {
         // TODO this is nasty
         debug_assert!(self.is_ptr());
         // TODO this offset operation is for 64bit platform only
         if self.gc_info() as u8 & GCINFO_OWNED_MASK != 0 {
-            let offset = *(self.ptr as *mut u8).offset(1);
-            let r = NonNull::new_unchecked((self.ptr as *mut u8).offset(offset as isize) as *mut T);
+            let offset = *self.header().offset(1);
+            let r = NonNull::new_unchecked(self.header().offset(offset as isize) as *mut T);
             transmute::<&T, &'a T>(r.as_ref())
         } else {
-            let offset = *(self.ptr as *mut u8).offset(1);
-            let rr = NonNull::new_unchecked((self.ptr as *mut u8).offset(offset as isize) as *mut *mut T);
+            let offset = *self.header().offset(1);
+            let rr = NonNull::new_unchecked(self.header().offset(offset as isize) as *mut *mut T);
             let r = NonNull::new_unchecked(*rr.as_ref());
             transmute::<&T, &'a T>(r.as_ref())
         }
@@ -359,12 +605,12 @@ impl Value {
         // TODO this is nasty
         debug_assert!(self.is_ptr());
         if self.gc_info() as u8 & GCINFO_OWNED_MASK != 0 {
-            let offset = *(self.ptr as *mut u8).offset(1);
-            let mut mr = NonNull::new_unchecked((self.ptr as *mut u8).offset(offset as isize) as *mut T);
+            let offset = *self.header().offset(1);
+            let mut mr = NonNull::new_unchecked(self.header().offset(offset as isize) as *mut T);
             transmute::<&mut T, &'a mut T>(mr.as_mut())
         } else {
-            let offset = *(self.ptr as *mut u8).offset(1);
-            let rmr = NonNull::new_unchecked((self.ptr as *mut u8).offset(offset as isize) as *mut *mut T);
+            let offset = *self.header().offset(1);
+            let rmr = NonNull::new_unchecked(self.header().offset(offset as isize) as *mut *mut T);
             let mut mr = NonNull::new_unchecked(*rmr.as_ref());
             transmute::<&mut T, &'a mut T>(mr.as_mut())
         }
@@ -391,14 +637,28 @@ impl<'a> From<i64> for Value {
 }
 
 impl<'a> From<f64> for Value {
-    fn from(_float: f64) -> Self {
-        unimplemented!()
+    fn from(float: f64) -> Self {
+        Self {
+            value_typed_data: ValueTypedData {
+                tag: (ValueType::Float as usize) | (VALUE_MASK as usize),
+                inner: ValueTypedDataInner {
+                    float
+                }
+            }
+        }
     }
 }
 
 impl<'a> From<char> for Value {
-    fn from(_ch: char) -> Self {
-        unimplemented!()
+    fn from(ch: char) -> Self {
+        Self {
+            value_typed_data: ValueTypedData {
+                tag: (ValueType::Char as usize) | (VALUE_MASK as usize),
+                inner: ValueTypedDataInner {
+                    ch
+                }
+            }
+        }
     }
 }
 
@@ -414,3 +674,141 @@ impl<'a> From<bool> for Value {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::cast::from_value::FromValue;
+    use crate::cast::into_value::IntoValue;
+    use crate::data::{
+        GcInfo, Value, ValueType, ValueTypedData, ValueTypedDataInner, VALUE_MASK
+    };
+    use crate::intake::{Adopt, OxProvider};
+    use crate::intake::ramped::RampedIntake;
+    use crate::tyck::TypeCheckInfo;
+    use crate::void::Void;
+
+    struct Sentinel(Rc<Cell<usize>>);
+
+    impl Drop for Sentinel {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_value_typed_data() {
+        let int = Value::from(42i64);
+        let float = Value::from(2.5f64);
+        let ch = Value::from('草');
+        let boolean = Value::from(true);
+
+        unsafe {
+            assert_eq!(int.type_id(), TypeId::of::<i64>());
+            assert_eq!(float.type_id(), TypeId::of::<f64>());
+            assert_eq!(ch.type_id(), TypeId::of::<char>());
+            assert_eq!(boolean.type_id(), TypeId::of::<bool>());
+        }
+
+        assert_eq!(int.as_int(), Some(42));
+        assert_eq!(float.as_float(), Some(2.5));
+        assert_eq!(ch.as_char(), Some('草'));
+        assert_eq!(boolean.as_bool(), Some(true));
+
+        assert_eq!(int.as_float(), None);
+        assert_eq!(float.as_int(), None);
+        assert_eq!(ch.as_bool(), None);
+        assert_eq!(boolean.as_char(), None);
+        assert_eq!(Value::null().as_int(), None);
+    }
+
+    #[test]
+    fn test_any_type() {
+        let any = Value {
+            value_typed_data: ValueTypedData {
+                tag: (ValueType::AnyType as usize) | (VALUE_MASK as usize),
+                inner: ValueTypedDataInner { int: 0 }
+            }
+        };
+        unsafe {
+            assert_eq!(any.type_id(), TypeId::of::<Value>());
+            assert_eq!(any.type_name(), "any");
+            assert!(!any.type_check(&TypeCheckInfo::SimpleType(TypeId::of::<i64>())));
+        }
+    }
+
+    #[test]
+    fn test_drop_owned() {
+        let drops = Rc::new(Cell::new(0));
+        let value = <Void as IntoValue<Sentinel>>::into_value(Sentinel(drops.clone())).unwrap();
+        assert_eq!(value.gc_info(), GcInfo::Owned);
+        assert_eq!(drops.get(), 0);
+        unsafe { value.drop_object(); }
+        assert_eq!(drops.get(), 1);
+
+        let value = <Void as IntoValue<Sentinel>>::into_value(Sentinel(drops.clone())).unwrap();
+        unsafe {
+            value.set_gc_info(GcInfo::Dropped);
+            value.drop_object();
+        }
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn test_drop_moved_to_host() {
+        let drops = Rc::new(Cell::new(0));
+        let value = <Void as IntoValue<Sentinel>>::into_value(Sentinel(drops.clone())).unwrap();
+        let mut guard = <Void as FromValue<Sentinel>>::lifetime_check(&value).unwrap();
+        let sentinel = unsafe { <Void as FromValue<Sentinel>>::from_value(&value) };
+        guard.finish();
+        drop(guard);
+        assert_eq!(value.gc_info(), GcInfo::MovedToHost);
+
+        unsafe { value.drop_object(); }
+        assert_eq!(drops.get(), 0);
+        drop(sentinel);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn test_drop_shared() {
+        let drops = Rc::new(Cell::new(0));
+        let value = <Void as IntoValue<Sentinel>>::into_value(Sentinel(drops.clone())).unwrap();
+        {
+            let mut guard = <Void as FromValue<&Sentinel>>::lifetime_check(&value).unwrap();
+            let sentinel = unsafe { <Void as FromValue<&Sentinel>>::from_value(&value) };
+            assert_eq!(sentinel.0.get(), 0);
+            guard.finish();
+        }
+        assert_eq!(value.gc_info(), GcInfo::Owned);
+        assert_eq!(drops.get(), 0);
+        unsafe { value.drop_object(); }
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn test_drop_shared_from_host() {
+        let drops = Rc::new(Cell::new(0));
+        let host = Sentinel(drops.clone());
+        let mut host_mut = Sentinel(drops.clone());
+        {
+            let mut intake = RampedIntake::default();
+            let shared = unsafe { intake.allocate_ref(&host).unwrap() };
+            let mut_shared = unsafe { intake.allocate_ref_mut(&mut host_mut).unwrap() };
+            assert_eq!(shared.gc_info(), GcInfo::SharedFromHost);
+            assert_eq!(mut_shared.gc_info(), GcInfo::MutSharedFromHost);
+
+            let owned = <Void as IntoValue<Sentinel>>::into_value(Sentinel(drops.clone())).unwrap();
+            unsafe { intake.adopt(owned).unwrap(); }
+            intake.force_collect();
+            assert_eq!(drops.get(), 1);
+        }
+        assert_eq!(drops.get(), 1);
+        drop(host);
+        drop(host_mut);
+        assert_eq!(drops.get(), 3);
+    }
+}
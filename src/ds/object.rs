@@ -2,7 +2,7 @@ use std::collections::BTreeMap;
 
 use crate::cast::from_value::FromValue;
 use crate::error::TError;
-use crate::data::Value;
+use crate::data::{Trace, Value};
 use crate::tyck::fusion::Fusion;
 use crate::void::Void;
 
@@ -16,6 +16,12 @@ impl Default for DynamicObject {
     }
 }
 
+impl Trace for DynamicObject {
+    fn trace(&self, visit: &mut dyn FnMut(&Value)) {
+        self.fields.values().for_each(visit);
+    }
+}
+
 impl DynamicObject {
     pub fn new() -> Self {
         Self {
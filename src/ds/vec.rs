@@ -1,8 +1,14 @@
-use std::any::TypeId;
+use std::any::{TypeId, type_name};
 use std::marker::PhantomData;
-use crate::data::{CustomVTable, DynBase};
+use std::mem::{MaybeUninit, size_of};
+use crate::data::{CustomVTable, GcInfo, HeapSize, StaticWrapper, Trace, Value};
 use crate::ds::ContainerElement;
-use crate::tyck::TypeCheckInfo;
+use crate::error::{LifetimeError, TError};
+use crate::intake::OxProvider;
+use crate::tyck::{FFIAction, TypeCheckInfo};
+use crate::tyck::base::StaticBase;
+use crate::tyck::names;
+use crate::void::Void;
 
 #[repr(C, align(8))]
 pub struct VecVTable {
@@ -22,20 +28,151 @@ pub fn vec_type_name(vt: *const CustomVTable) -> String {
             let elem_vt = (*vec_vt).element.element_vtable;
             format!("VMVec<{}>", ((*elem_vt).dyn_type_name)(elem_vt))
         } else {
-            format!("VMVec<{}>", (*vec_vt).element.element_type_id.1.to_string())
+            format!("VMVec<{}>", (*vec_vt).element.element_type_id.1)
         }
     }
 }
 
-pub fn vec_tyck(_vt: *const CustomVTable, _tyck_info: &TypeCheckInfo) -> bool {
-    unimplemented!()
+pub fn vec_tyck(vt: *const CustomVTable, tyck_info: &TypeCheckInfo) -> bool {
+    let vec_vt = vt as *const VecVTable;
+    match tyck_info {
+        TypeCheckInfo::Bypass => true,
+        TypeCheckInfo::Container(tid, elems)
+            if *tid == TypeId::of::<VMGenericVec>() && elems.len() == 1 => unsafe {
+            if (*vec_vt).nested {
+                let elem_vt = (*vec_vt).element.element_vtable;
+                ((*elem_vt).dyn_tyck)(elem_vt, &elems[0])
+            } else {
+                match &elems[0] {
+                    TypeCheckInfo::Bypass => true,
+                    TypeCheckInfo::SimpleType(elem_tid) =>
+                        *elem_tid == (*vec_vt).element.element_type_id.0,
+                    TypeCheckInfo::Container(..) => false
+                }
+            }
+        },
+        _ => false
+    }
+}
+
+pub fn vec_tyck_info(vt: *const CustomVTable) -> TypeCheckInfo {
+    let vec_vt = vt as *const VecVTable;
+    let elem_tyck_info = unsafe {
+        if (*vec_vt).nested {
+            let elem_vt = (*vec_vt).element.element_vtable;
+            ((*elem_vt).dyn_tyck_info)(elem_vt)
+        } else {
+            TypeCheckInfo::SimpleType((*vec_vt).element.element_type_id.0)
+        }
+    };
+    TypeCheckInfo::Container(TypeId::of::<VMGenericVec>(), vec![elem_tyck_info])
+}
+
+/// 将 `data` 所指向的包装对象中的 `VMGenericVec` 移动到 `dest` 中
+unsafe fn vec_move_out_impl(data: *mut (), dest: *mut ()) {
+    let wrapper = (data as *mut StaticWrapper<VMGenericVec>).as_mut().unwrap();
+    let dest = (dest as *mut MaybeUninit<VMGenericVec>).as_mut().unwrap();
+    dest.write(wrapper.take_value());
+}
+
+/// # Safety
+/// `data` 必须指向一个存有 `VMGenericVec` 的包装对象，`dest` 必须指向一个
+/// `MaybeUninit<VMGenericVec>`
+#[cfg(not(debug_assertions))]
+pub unsafe fn vec_move_out(data: *mut (), _vt: *const CustomVTable, dest: *mut ()) {
+    vec_move_out_impl(data, dest)
+}
+
+/// # Safety
+/// 同 `vec_move_out`
+#[cfg(debug_assertions)]
+pub unsafe fn vec_move_out_ck(
+    data: *mut (),
+    _vt: *const CustomVTable,
+    dest: *mut (),
+    dest_ty: TypeId
+) {
+    debug_assert_eq!(dest_ty, TypeId::of::<VMGenericVec>());
+    vec_move_out_impl(data, dest)
+}
+
+/// `VMVec<T>` 的元素信息，嵌套的 `VMVec` 通过虚表记录，其他类型记录类型 ID 和类型名
+pub trait VecElement {
+    const ELEMENT: ContainerElement;
+    const NESTED: bool;
+}
+
+impl<T: 'static> VecElement for T {
+    default const ELEMENT: ContainerElement = ContainerElement {
+        element_type_id: (TypeId::of::<T>(), type_name::<T>())
+    };
+    default const NESTED: bool = false;
+}
+
+impl<T: 'static> VecElement for VMVec<T> {
+    const ELEMENT: ContainerElement = ContainerElement {
+        element_vtable: VMVec::<T>::VTABLE as *const VecVTable as *const CustomVTable
+    };
+    const NESTED: bool = true;
+}
+
+/// `VMVec` 中元素的存储方式：值类型直接存储在 `Value` 中，其他类型通过 `OxProvider` 分配在堆上
+trait ElementRepr: Sized {
+    fn store<'a>(self, intake: &mut impl OxProvider<'a>) -> Result<Value, TError>;
+    unsafe fn load(value: &Value) -> &Self;
+    unsafe fn take(value: &Value) -> Result<Self, TError>;
+}
+
+const ELEMENT_MOVE_LIFETIMES: [GcInfo; 1] = [ GcInfo::Owned ];
+
+impl<T: 'static> ElementRepr for T {
+    default fn store<'a>(self, intake: &mut impl OxProvider<'a>) -> Result<Value, TError> {
+        intake.allocate(self)
+    }
+
+    default unsafe fn load(value: &Value) -> &Self {
+        value.as_ref()
+    }
+
+    default unsafe fn take(value: &Value) -> Result<Self, TError> {
+        let actual = value.gc_info();
+        if actual != GcInfo::Owned {
+            return Err(LifetimeError::new(&ELEMENT_MOVE_LIFETIMES, FFIAction::Move, actual).into());
+        }
+        let t = std::ptr::read(value.as_ref::<Self>());
+        value.set_gc_info(GcInfo::MovedToHost);
+        Ok(t)
+    }
+}
+
+/// 为 `i64` 一类的值类型生成 `ElementRepr` 实现
+macro_rules! impl_value_type_element {
+    ($ty:ty, $field:ident) => {
+        impl ElementRepr for $ty {
+            #[inline] fn store<'a>(self, _intake: &mut impl OxProvider<'a>) -> Result<Value, TError> {
+                Ok(Value::from(self))
+            }
+
+            #[inline] unsafe fn load(value: &Value) -> &Self {
+                &value.value_typed_data.inner.$field
+            }
+
+            #[inline] unsafe fn take(value: &Value) -> Result<Self, TError> {
+                Ok(value.value_typed_data.inner.$field)
+            }
+        }
+    };
 }
 
+impl_value_type_element!(i64, int);
+impl_value_type_element!(f64, float);
+impl_value_type_element!(char, ch);
+impl_value_type_element!(bool, boolean);
+
+/// 与元素类型无关的列表，元素是分配在 `OxProvider` 上的对象或者值类型数据
 #[repr(C)]
 pub struct VMGenericVec {
-    // 实现对 VMGenericVec 的操作需要先实现分配对象所需要的 VMContext
-    // VMGenericVec 中存储的对象只能创建在 VMContext 上
-    pub vec: Vec<*mut dyn DynBase>,
+    pub vec: Vec<Value>,
 }
 
 impl VMGenericVec {
@@ -46,26 +183,401 @@ impl VMGenericVec {
     }
 }
 
+impl Default for VMGenericVec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 元素本身是单独分配的对象，只统计存放 `Value` 的缓冲区
+impl HeapSize for VMGenericVec {
+    fn heap_size(&self) -> usize {
+        self.vec.capacity() * size_of::<Value>()
+    }
+}
+
+impl<T> HeapSize for VMVec<T> {
+    fn heap_size(&self) -> usize {
+        self.inner.heap_size()
+    }
+}
+
+impl Trace for VMGenericVec {
+    fn trace(&self, visit: &mut dyn FnMut(&Value)) {
+        self.vec.iter().for_each(visit);
+    }
+}
+
+/// 虚拟机中的列表类型
+///
+/// 非值类型的元素通过 `OxProvider` 分配，由分配器负责回收。`VMVec` 本身存放在分配器上并且可以从
+/// 根集合到达时，其中的元素会被垃圾回收器追踪；在此之前，分配器进行回收会释放已经存入的元素，
+/// 因此 `push` 和 `set` 是 `unsafe` 的。
 #[repr(transparent)]
 pub struct VMVec<T> {
-    // 实现对 VMVec 的操作需要先实现分配对象所需要的 VMContext
-    // VMVec 中存储的对象只能创建在 VMContext 上
-    #[allow(dead_code)]
     pub(crate) inner: VMGenericVec,
     _phantom: PhantomData<T>
 }
 
+impl<T> Trace for VMVec<T> {
+    fn trace(&self, visit: &mut dyn FnMut(&Value)) {
+        self.inner.trace(visit);
+    }
+}
+
 impl<T> VMVec<T> {
     pub fn new() -> Self {
         Self {
             inner: VMGenericVec::new(),
-            _phantom: PhantomData::default()
+            _phantom: PhantomData
         }
     }
 }
 
+impl<T> Default for VMVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: 'static> VMVec<T> {
-    pub fn push(&mut self, _t: T) {
-        todo!()
+    /// `VMVec<T>` 的虚表，用于通过 `CustomFatPtr` 传递 `VMVec<T>`
+    pub const VTABLE: &'static VecVTable = &VecVTable {
+        base: CustomVTable {
+            dyn_type_id: vec_type_id,
+            dyn_type_name: vec_type_name,
+            dyn_tyck: vec_tyck,
+            dyn_tyck_info: vec_tyck_info,
+
+            #[cfg(not(debug_assertions))]
+            move_out: vec_move_out,
+
+            #[cfg(debug_assertions)]
+            move_out_ck: vec_move_out_ck
+        },
+        element: <T as VecElement>::ELEMENT,
+        nested: <T as VecElement>::NESTED
+    };
+
+    pub fn len(&self) -> usize {
+        self.inner.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.vec.is_empty()
+    }
+
+    /// 在末尾添加一个元素，非值类型的元素会在 `intake` 上分配
+    ///
+    /// # Safety
+    /// `self` 中所有非值类型的元素都必须分配在 `intake` 上。如果 `self` 不能从 `intake` 的根集合
+    /// 到达（例如还没有通过 `into_value` 移动到 `intake` 上），在它变为可以到达之前 `intake`
+    /// 不能进行回收，否则已经存入的元素会被释放
+    pub unsafe fn push<'a>(&mut self, intake: &mut impl OxProvider<'a>, t: T) -> Result<(), TError> {
+        let value = t.store(intake)?;
+        self.inner.vec.push(value);
+        Ok(())
+    }
+
+    /// 移除并返回最后一个元素。元素正被共享给 Rust 时返回 `LifetimeError`，并且不移除元素
+    pub fn pop(&mut self) -> Result<Option<T>, TError> {
+        if let Some(value) = self.inner.vec.last() {
+            let t = unsafe { T::take(value)? };
+            self.inner.vec.pop();
+            Ok(Some(t))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.inner.vec.get(idx).map(|value| unsafe { T::load(value) })
+    }
+
+    /// 替换下标为 `idx` 的元素，被替换的元素交由分配器回收
+    ///
+    /// # Safety
+    /// 同 `push`
+    pub unsafe fn set<'a>(&mut self, intake: &mut impl OxProvider<'a>, idx: usize, t: T)
+        -> Result<(), TError>
+    {
+        if idx >= self.len() {
+            return Err(TError::unchecked_exception(
+                format!("index {} out of range for VMVec of length {}", idx, self.len())
+            ));
+        }
+        self.inner.vec[idx] = t.store(intake)?;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&T> {
+        self.inner.vec.iter().map(|value| unsafe { T::load(value) })
+    }
+
+    /// 将列表移动到 `intake` 上，得到一个可以传递给虚拟机的 `Value`
+    pub fn into_value<'a>(self, intake: &mut impl OxProvider<'a>) -> Result<Value, TError> {
+        let object = intake.allocate(self)?;
+        let vtable = &Self::VTABLE.base as *const CustomVTable;
+        Ok(Value::from_container(unsafe { object.ptr } as *mut (), vtable))
+    }
+}
+
+impl<T: 'static> StaticBase<VMVec<T>> for Void {
+    #[inline] fn base_type_id() -> TypeId {
+        TypeId::of::<VMGenericVec>()
+    }
+
+    fn tyck_info() -> TypeCheckInfo {
+        TypeCheckInfo::Container(
+            TypeId::of::<VMGenericVec>(),
+            vec![<Void as StaticBase<T>>::tyck_info()]
+        )
+    }
+
+    fn tyck(tyck_info: &TypeCheckInfo) -> bool {
+        match tyck_info {
+            TypeCheckInfo::Container(tid, elems)
+                if *tid == TypeId::of::<VMGenericVec>() && elems.len() == 1 =>
+                <Void as StaticBase<T>>::tyck(&elems[0]),
+            _ => false
+        }
+    }
+
+    #[inline] fn ffi_action() -> FFIAction {
+        FFIAction::Move
+    }
+
+    fn register_type_name() {
+        // 所有 `VMVec<T>` 共享同一个基础类型 ID，元素类型由类型检查信息中的子项表示
+        names::register::<VMGenericVec>();
+        <Void as StaticBase<T>>::register_type_name();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::marker::PhantomData;
+    use std::mem::MaybeUninit;
+
+    use crate::data::{DynBase, StaticWrapper, Value};
+    use crate::ds::vec::{VMGenericVec, VMVec};
+    use crate::error::TError;
+    use crate::func::{RustCallable, RustFunction1, RustFunction2};
+    use crate::intake::OxProvider;
+    use crate::intake::oxtank::OxTank;
+    use crate::intake::ramped::{IntakeOptions, RampedIntake};
+    use crate::tyck::TypeCheckInfo;
+    use crate::tyck::base::StaticBase;
+    use crate::void::Void;
+
+    type IntMatrix = VMVec<VMVec<i64>>;
+    type StringMatrix = VMVec<VMVec<String>>;
+
+    #[test] fn test_static_tyck() {
+        let int_info = <Void as StaticBase<IntMatrix>>::tyck_info();
+        let string_info = <Void as StaticBase<StringMatrix>>::tyck_info();
+        assert_eq!(int_info, TypeCheckInfo::Container(TypeId::of::<VMGenericVec>(), vec![
+            TypeCheckInfo::Container(TypeId::of::<VMGenericVec>(), vec![
+                TypeCheckInfo::SimpleType(TypeId::of::<i64>())
+            ])
+        ]));
+        assert_eq!(<Void as StaticBase<IntMatrix>>::base_type_id(), TypeId::of::<VMGenericVec>());
+
+        assert!(<Void as StaticBase<IntMatrix>>::tyck(&int_info));
+        assert!(!<Void as StaticBase<IntMatrix>>::tyck(&string_info));
+        assert!(!<Void as StaticBase<VMVec<i64>>>::tyck(&int_info));
+        assert!(int_info.accepts(&int_info));
+        assert!(!int_info.accepts(&string_info));
+    }
+
+    #[test] fn test_vtable() {
+        let vtable = &IntMatrix::VTABLE.base;
+        assert_eq!((vtable.dyn_type_id)(vtable), TypeId::of::<VMGenericVec>());
+        assert_eq!((vtable.dyn_type_name)(vtable), "VMVec<VMVec<i64>>");
+        assert_eq!((vtable.dyn_tyck_info)(vtable), <Void as StaticBase<IntMatrix>>::tyck_info());
+    }
+
+    #[test] fn test_runtime_tyck() {
+        let int_info = <Void as StaticBase<IntMatrix>>::tyck_info();
+        let string_info = <Void as StaticBase<StringMatrix>>::tyck_info();
+
+        let matrix = Box::into_raw(Box::new(StaticWrapper::owned(IntMatrix::new())));
+        let value = Value::from_container(matrix as *mut (), &IntMatrix::VTABLE.base);
+        assert!(value.is_container());
+        unsafe {
+            assert_eq!(value.type_id(), TypeId::of::<VMGenericVec>());
+            assert_eq!(value.type_name(), "VMVec<VMVec<i64>>");
+            assert!(value.type_check(&int_info));
+            assert!(!value.type_check(&string_info));
+            assert!(value.type_check(&TypeCheckInfo::SimpleType(TypeId::of::<VMGenericVec>())));
+        }
+
+        // 以普通堆对象的形式存储的 `VMVec` 也可以通过 `DynBase` 进行检查
+        let object = Value::from(matrix as *mut dyn DynBase);
+        assert!(!object.is_container());
+        unsafe {
+            assert_eq!(object.type_id(), TypeId::of::<VMGenericVec>());
+            assert!(object.type_check(&int_info));
+            assert!(!object.type_check(&string_info));
+            drop(Box::from_raw(matrix));
+        }
+    }
+
+    #[test] fn test_typed_api() {
+        // `OxTank` 不进行回收，可以直接向不在堆上的列表中添加元素
+        let mut intake = OxTank::default();
+
+        let mut ints = VMVec::<i64>::new();
+        assert!(ints.is_empty());
+        for i in 0..5i64 {
+            unsafe { ints.push(&mut intake, i * 10).unwrap(); }
+        }
+        assert_eq!(ints.len(), 5);
+        assert_eq!(ints.get(2), Some(&20));
+        assert_eq!(ints.get(5), None);
+        unsafe {
+            ints.set(&mut intake, 2, 25).unwrap();
+            assert!(matches!(ints.set(&mut intake, 5, 50), Err(TError::UncheckedException(_))));
+        }
+        assert_eq!(ints.iter().copied().collect::<Vec<_>>(), vec![0, 10, 25, 30, 40]);
+        assert_eq!(ints.pop().unwrap(), Some(40));
+        assert_eq!(ints.len(), 4);
+
+        let mut strings = VMVec::<String>::new();
+        unsafe {
+            strings.push(&mut intake, String::from("foo")).unwrap();
+            strings.push(&mut intake, String::from("bar")).unwrap();
+        }
+        assert_eq!(strings.get(0).map(String::as_str), Some("foo"));
+        assert_eq!(strings.iter().cloned().collect::<Vec<_>>(), vec!["foo", "bar"]);
+        assert_eq!(strings.pop().unwrap().as_deref(), Some("bar"));
+        assert_eq!(strings.len(), 1);
+
+        let mut matrix = IntMatrix::new();
+        for row in 0..3i64 {
+            let mut inner = VMVec::new();
+            for col in 0..3i64 {
+                unsafe { inner.push(&mut intake, row * 3 + col).unwrap(); }
+            }
+            unsafe { matrix.push(&mut intake, inner).unwrap(); }
+        }
+        assert_eq!(matrix.get(1).and_then(|row| row.get(2)), Some(&5));
+        assert_eq!(matrix.iter().map(|row| row.iter().sum::<i64>()).sum::<i64>(), 36);
+        let last = matrix.pop().unwrap().unwrap();
+        assert_eq!(last.iter().copied().collect::<Vec<_>>(), vec![6, 7, 8]);
+        assert_eq!(matrix.pop().unwrap().unwrap().len(), 3);
+        assert_eq!(matrix.pop().unwrap().unwrap().len(), 3);
+        assert_eq!(matrix.pop().unwrap().map(|row| row.len()), None);
+    }
+
+    #[test] fn test_trace_elements() {
+        let mut stack = vec![MaybeUninit::new(Value::null()); 1];
+        let mut intake = RampedIntake::default();
+        let mut strings = VMVec::<String>::new();
+        for s in ["a", "b", "c"] {
+            // 回收默认是禁用的
+            unsafe { strings.push(&mut intake, String::from(s)).unwrap(); }
+        }
+        let value = strings.into_value(&mut intake).unwrap();
+        assert!(value.is_container());
+        stack[0] = MaybeUninit::new(value);
+        intake.add_stack(&stack);
+
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 4);
+        unsafe {
+            let strings = value.as_ref::<VMVec<String>>();
+            assert_eq!(strings.iter().cloned().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        }
+
+        // 没有被根集合引用的列表和其中的元素都会被回收
+        let mut intake = RampedIntake::default();
+        let mut strings = VMVec::<String>::new();
+        unsafe { strings.push(&mut intake, String::from("d")).unwrap(); }
+        strings.into_value(&mut intake).unwrap();
+        assert_eq!(intake.object_count(), 2);
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 0);
+    }
+
+    #[test] fn test_push_reachable() {
+        let mut stack = vec![MaybeUninit::new(Value::null()); 1];
+        let mut intake = RampedIntake::new(IntakeOptions::new(1, 1));
+        let value = VMVec::<String>::new().into_value(&mut intake).unwrap();
+        stack[0] = MaybeUninit::new(value);
+        intake.add_stack(&stack);
+        intake.enable_collect();
+
+        // 列表可以从根集合到达，每次分配之前的回收都不会释放已经存入的元素
+        for s in ["first", "second", "third"] {
+            unsafe { value.as_mut::<VMVec<String>>().push(&mut intake, String::from(s)).unwrap(); }
+        }
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 4);
+        let strings = unsafe { value.as_ref::<VMVec<String>>() };
+        assert_eq!(strings.iter().cloned().collect::<Vec<_>>(), vec!["first", "second", "third"]);
+    }
+
+    fn sum(ints: &VMVec<i64>) -> i64 {
+        ints.iter().sum()
+    }
+
+    fn pop_last(ints: &mut VMVec<i64>) -> Option<i64> {
+        ints.pop().unwrap()
+    }
+
+    fn append(dest: &mut VMVec<i64>, src: &mut VMVec<i64>) -> i64 {
+        // `i64` 元素直接存储在 `Value` 中，不会在 `intake` 上分配
+        let mut intake = OxTank::default();
+        let mut popped = Vec::new();
+        while let Some(i) = src.pop().unwrap() {
+            popped.push(i);
+        }
+        for i in popped.into_iter().rev() {
+            unsafe { dest.push(&mut intake, i).unwrap(); }
+        }
+        dest.len() as i64
+    }
+
+    #[test] fn test_ffi() {
+        let mut intake = OxTank::default();
+        let mut ints = VMVec::<i64>::new();
+        for i in 1..=4i64 {
+            unsafe { ints.push(&mut intake, i).unwrap(); }
+        }
+        let ints = ints.into_value(&mut intake).unwrap();
+        let strings = VMVec::<String>::new().into_value(&mut intake).unwrap();
+
+        let f = RustFunction1 { f: sum, _phantom: PhantomData };
+        let rets = f.call_checked(&[ints], &mut intake).unwrap();
+        assert_eq!(unsafe { rets[0].value_typed_data.inner.int }, 10);
+        if let Err(TError::TypeError(e)) = f.call_checked(&[strings], &mut intake) {
+            assert!(e.required_name.unwrap().ends_with("VMVec<i64>"));
+            assert!(e.actual_name.unwrap().ends_with("VMVec<alloc::string::String>"));
+        } else {
+            panic!("expected a TypeError");
+        }
+
+        let f = RustFunction1 { f: pop_last, _phantom: PhantomData };
+        let rets = f.call_checked(&[ints], &mut intake).unwrap();
+        assert_eq!(unsafe { rets[0].value_typed_data.inner.int }, 4);
+        assert_eq!(unsafe { ints.as_ref::<VMVec<i64>>() }.len(), 3);
+
+        let f = RustFunction2 { f: append, _phantom: PhantomData };
+        let result = f.call_checked(&[ints, ints], &mut intake);
+        assert!(matches!(result, Err(TError::LifetimeError(_))));
+        assert_eq!(unsafe { ints.as_ref::<VMVec<i64>>() }.len(), 3);
+
+        let mut others = VMVec::<i64>::new();
+        for i in 5..=6i64 {
+            unsafe { others.push(&mut intake, i).unwrap(); }
+        }
+        let others = others.into_value(&mut intake).unwrap();
+        let rets = f.call_checked(&[ints, others], &mut intake).unwrap();
+        assert_eq!(unsafe { rets[0].value_typed_data.inner.int }, 5);
+        let ints = unsafe { ints.as_ref::<VMVec<i64>>() };
+        assert_eq!(ints.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3, 5, 6]);
+        assert!(unsafe { others.as_ref::<VMVec<i64>>() }.is_empty());
     }
 }
@@ -1,11 +1,12 @@
 //! `error` 模块中定义了错误处理工具类
 
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
-use crate::data::GcInfo;
-use crate::tyck::FFIAction;
+use crate::data::{GcInfo, Value};
+use crate::tyck::{FFIAction, TypeCheckInfo};
+use crate::tyck::names;
 
 /// T10 中所使用的错误处理类型
 #[derive(Debug)]
@@ -18,10 +19,14 @@ pub enum TError {
     ArgLenError(ArgLenError),
     /// 空指针/空值错误
     NullError(NullError),
+    /// 调用栈深度或者栈上的槽位数超出限制
+    StackOverflow(StackOverflow),
+    /// 堆上分配的内存超出限制
+    OutOfMemory(OutOfMemory),
     /// 非受检异常
     UncheckedException(String),
-    /// 用户定义的受检异常
-    UserException(Box<dyn 'static + Error>)
+    /// 用户定义的受检异常，同时记录异常的实际类型
+    UserException(Box<dyn 'static + Error>, TypeId)
 }
 
 impl TError {
@@ -29,9 +34,55 @@ impl TError {
         Self::UncheckedException(info.to_string())
     }
 
-    pub fn user_exception(exception: impl 'static + Error) -> Self {
-        Self::UserException(Box::new(exception))
+    pub fn user_exception<E: 'static + Error>(exception: E) -> Self {
+        // 宿主函数将回调 VM 时得到的 `TError` 原样返回时，不再重复包装
+        let mut exception = Some(exception);
+        if let Some(error) = (&mut exception as &mut dyn Any).downcast_mut::<Option<TError>>() {
+            return error.take().unwrap();
+        }
+        Self::UserException(Box::new(exception.unwrap()), TypeId::of::<E>())
     }
+
+    /// 异常处理时用来匹配异常处理器的类型 ID
+    ///
+    /// 用户定义的异常使用其实际类型，内建的错误使用对应错误结构的类型。非受检异常没有类型，
+    /// 只能被“捕获所有异常”的处理器捕获。
+    pub fn exception_type_id(&self) -> Option<TypeId> {
+        match self {
+            TError::LifetimeError(_) => Some(TypeId::of::<LifetimeError>()),
+            TError::TypeError(_) => Some(TypeId::of::<TypeError>()),
+            TError::ArgLenError(_) => Some(TypeId::of::<ArgLenError>()),
+            TError::NullError(_) => Some(TypeId::of::<NullError>()),
+            TError::StackOverflow(_) => Some(TypeId::of::<StackOverflow>()),
+            TError::OutOfMemory(_) => Some(TypeId::of::<OutOfMemory>()),
+            TError::UncheckedException(_) => None,
+            TError::UserException(_, type_id) => Some(*type_id)
+        }
+    }
+}
+
+/// `TError` 内置的异常类型及其在汇编和模块格式中使用的名字，不需要注册就可以使用
+pub const BUILTIN_EXCEPTIONS: [(&str, TypeId); 6] = [
+    ("TypeError", TypeId::of::<TypeError>()),
+    ("NullError", TypeId::of::<NullError>()),
+    ("ArgLenError", TypeId::of::<ArgLenError>()),
+    ("LifetimeError", TypeId::of::<LifetimeError>()),
+    ("StackOverflow", TypeId::of::<StackOverflow>()),
+    ("OutOfMemory", TypeId::of::<OutOfMemory>())
+];
+
+/// 根据名字查找内置异常类型
+pub fn builtin_exception_type(name: &str) -> Option<TypeId> {
+    BUILTIN_EXCEPTIONS.iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, type_id)| *type_id)
+}
+
+/// 查找内置异常类型的名字
+pub fn builtin_exception_name(type_id: TypeId) -> Option<&'static str> {
+    BUILTIN_EXCEPTIONS.iter()
+        .find(|(_, builtin)| *builtin == type_id)
+        .map(|(name, _)| *name)
 }
 
 impl From<LifetimeError> for TError {
@@ -46,12 +97,30 @@ impl From<TypeError> for TError {
     }
 }
 
+impl From<ArgLenError> for TError {
+    fn from(e: ArgLenError) -> Self {
+        Self::ArgLenError(e)
+    }
+}
+
 impl From<NullError> for TError {
     fn from(e: NullError) -> Self {
         Self::NullError(e)
     }
 }
 
+impl From<StackOverflow> for TError {
+    fn from(e: StackOverflow) -> Self {
+        Self::StackOverflow(e)
+    }
+}
+
+impl From<OutOfMemory> for TError {
+    fn from(e: OutOfMemory) -> Self {
+        Self::OutOfMemory(e)
+    }
+}
+
 impl Display for TError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -59,8 +128,10 @@ impl Display for TError {
             TError::TypeError(e) => write!(f, "{}", e),
             TError::ArgLenError(e) => write!(f, "{}", e),
             TError::NullError(e) => write!(f, "{}", e),
+            TError::StackOverflow(e) => write!(f, "{}", e),
+            TError::OutOfMemory(e) => write!(f, "{}", e),
             TError::UncheckedException(e) => write!(f, "{}", e),
-            TError::UserException(e) => write!(f, "{}", e)
+            TError::UserException(e, _) => write!(f, "{}", e)
         }
     }
 }
@@ -116,16 +187,25 @@ pub struct TypeError {
 }
 
 impl TypeError {
+    /// 创建 `TypeError`，两个类型的类型名从类型名注册表中查找
     pub fn new(required: TypeId, got: TypeId) -> Self {
         Self {
             required,
             actual: got,
-            required_name: None,
-            actual_name: None,
+            required_name: names::lookup(required),
+            actual_name: names::lookup(got),
             extra_info: None
         }
     }
 
+    /// 根据 `value` 的实际类型创建 `TypeError`
+    ///
+    /// # Safety
+    /// `value` 不能为空
+    pub unsafe fn of_value(required: TypeId, value: &Value) -> Self {
+        Self::new(required, value.type_id()).add_actual_name(value.type_name())
+    }
+
     pub fn add_required_name(self, required_name: String) -> Self {
         let mut ret = self;
         ret.required_name.replace(required_name);
@@ -192,3 +272,303 @@ impl Display for NullError {
         write!(f, "NullError")
     }
 }
+
+#[derive(Debug)]
+pub struct StackOverflow {
+    /// 栈帧个数的上限
+    pub max_depth: usize,
+    /// 栈上槽位个数的上限
+    pub max_slots: usize,
+    /// 宿主函数回调 VM 的嵌套层数上限
+    pub max_nesting: usize
+}
+
+impl StackOverflow {
+    pub fn new(max_depth: usize, max_slots: usize, max_nesting: usize) -> Self {
+        Self { max_depth, max_slots, max_nesting }
+    }
+}
+
+impl Display for StackOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StackOverflow: exceeded limit of {} frames, {} slots or {} nested calls",
+               self.max_depth, self.max_slots, self.max_nesting)
+    }
+}
+
+#[derive(Debug)]
+pub struct OutOfMemory {
+    /// 可以分配的字节数上限
+    pub capacity: usize,
+    /// 已经分配的字节数
+    pub used: usize,
+    /// 本次请求分配的字节数
+    pub requested: usize
+}
+
+impl OutOfMemory {
+    pub fn new(capacity: usize, used: usize, requested: usize) -> Self {
+        Self { capacity, used, requested }
+    }
+}
+
+impl Display for OutOfMemory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OutOfMemory: cannot allocate {} bytes, {} of {} bytes used",
+               self.requested, self.used, self.capacity)
+    }
+}
+
+/// 链接程序与宿主函数时发生的错误
+#[derive(Debug)]
+pub enum LinkError {
+    /// 找不到对应名字的宿主函数
+    UnresolvedFunction(String),
+    /// 同一个名字被重复注册
+    DuplicateFunction(String),
+    /// 参数个数不匹配
+    ArgLenMismatch {
+        func_name: String,
+        error: ArgLenError
+    },
+    /// 返回值个数不匹配
+    RetLenMismatch {
+        func_name: String,
+        error: ArgLenError
+    },
+    /// 参数类型不匹配
+    ArgTypeMismatch {
+        func_name: String,
+        index: usize,
+        expected: TypeCheckInfo,
+        got: TypeCheckInfo
+    },
+    /// 返回值类型不匹配
+    RetTypeMismatch {
+        func_name: String,
+        index: usize,
+        expected: TypeCheckInfo,
+        got: TypeCheckInfo
+    }
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LinkError: ")?;
+        match self {
+            LinkError::UnresolvedFunction(name) =>
+                write!(f, "unresolved function \"{}\"", name),
+            LinkError::DuplicateFunction(name) =>
+                write!(f, "duplicate function \"{}\"", name),
+            LinkError::ArgLenMismatch { func_name, error } =>
+                write!(f, "\"{}\": {}", func_name, error),
+            LinkError::RetLenMismatch { func_name, error } =>
+                write!(f, "\"{}\": expected {} return values, got {}",
+                       func_name, error.expected, error.got),
+            LinkError::ArgTypeMismatch { func_name, index, expected, got } =>
+                write!(f, "\"{}\": argument {}: expected {:?}, got {:?}",
+                       func_name, index, expected, got),
+            LinkError::RetTypeMismatch { func_name, index, expected, got } =>
+                write!(f, "\"{}\": return value {}: expected {:?}, got {:?}",
+                       func_name, index, expected, got)
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+/// 校验 `rd93` 字节码时发现的错误
+#[derive(Debug)]
+pub enum VerifyError {
+    /// 函数的起始地址越界
+    BadFuncStart { func_id: usize },
+    /// 函数的参数个数超过了栈帧大小
+    BadArgCount { func_id: usize },
+    /// 跳转目标不在当前函数内
+    BadJumpTarget { insc_ptr: usize, jump_dest: usize },
+    /// 访问的栈位置超过了栈帧大小
+    BadStackIndex { insc_ptr: usize, index: usize, stack_size: usize },
+    /// 调用了不存在的函数
+    BadFuncId { insc_ptr: usize, func_id: usize },
+    /// 调用了不存在的宿主函数
+    BadFFIFuncId { insc_ptr: usize, func_id: usize },
+    /// 调用时的参数个数与被调用函数不一致
+    ArgCountMismatch { insc_ptr: usize, error: ArgLenError },
+    /// 调用或返回时的返回值个数与函数签名不一致
+    RetCountMismatch { insc_ptr: usize, error: ArgLenError },
+    /// 控制流越过了函数末尾
+    FallThrough { func_id: usize, insc_ptr: usize },
+    /// 异常处理器的地址范围或者异常存放位置不合法
+    BadExceptionHandler { func_id: usize, handler_idx: usize },
+    /// 读取了可能未初始化的栈位置
+    UninitializedRead { insc_ptr: usize, index: usize },
+    /// 出现了只能由校验器生成的指令
+    BadInsc { insc_ptr: usize }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VerifyError: ")?;
+        match self {
+            VerifyError::BadFuncStart { func_id } =>
+                write!(f, "function {}: start address out of range", func_id),
+            VerifyError::BadArgCount { func_id } =>
+                write!(f, "function {}: argument count exceeds stack size", func_id),
+            VerifyError::BadJumpTarget { insc_ptr, jump_dest } =>
+                write!(f, "insc {}: jump target {} out of function", insc_ptr, jump_dest),
+            VerifyError::BadStackIndex { insc_ptr, index, stack_size } =>
+                write!(f, "insc {}: stack index {} out of stack size {}",
+                       insc_ptr, index, stack_size),
+            VerifyError::BadFuncId { insc_ptr, func_id } =>
+                write!(f, "insc {}: no function {}", insc_ptr, func_id),
+            VerifyError::BadFFIFuncId { insc_ptr, func_id } =>
+                write!(f, "insc {}: no ffi function {}", insc_ptr, func_id),
+            VerifyError::ArgCountMismatch { insc_ptr, error } =>
+                write!(f, "insc {}: {}", insc_ptr, error),
+            VerifyError::RetCountMismatch { insc_ptr, error } =>
+                write!(f, "insc {}: expected {} return values, got {}",
+                       insc_ptr, error.expected, error.got),
+            VerifyError::FallThrough { func_id, insc_ptr } =>
+                write!(f, "function {}: control flow falls off the end after insc {}",
+                       func_id, insc_ptr),
+            VerifyError::BadExceptionHandler { func_id, handler_idx } =>
+                write!(f, "function {}: bad exception handler {}", func_id, handler_idx),
+            VerifyError::UninitializedRead { insc_ptr, index } =>
+                write!(f, "insc {}: stack index {} may be uninitialized", insc_ptr, index),
+            VerifyError::BadInsc { insc_ptr } =>
+                write!(f, "insc {}: instruction cannot appear in input programs", insc_ptr)
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+/// 汇编或反汇编 `rd93` 程序时发生的错误，行号和列号都从 1 开始。反汇编时指向输出文本中的位置
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl AsmError {
+    pub fn new(line: usize, column: usize, message: impl ToString) -> Self {
+        Self {
+            line,
+            column,
+            message: message.to_string()
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AsmError: {}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// 将 `rd93` 程序编译为定长指令时发生的错误：第 `insc_ptr` 条指令的操作数 `operand` 超出了 `u32`
+/// 的范围
+#[derive(Debug)]
+pub struct FlatError {
+    pub insc_ptr: usize,
+    pub operand: usize
+}
+
+impl Display for FlatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FlatError: insc {}: operand {} too large for flat encoding",
+               self.insc_ptr, self.operand)
+    }
+}
+
+impl Error for FlatError {}
+
+/// 编码或加载 `rd93` 二进制模块时发生的错误
+#[derive(Debug)]
+pub enum ModuleError {
+    /// 模块的魔数不正确
+    BadMagic,
+    /// 不支持的模块格式版本
+    UnsupportedVersion(u16),
+    /// 负载的校验和与头部记录的不一致
+    ChecksumMismatch { expected: u32, got: u32 },
+    /// 模块在读取完成之前就结束了
+    Truncated,
+    /// 模块的内容不合法，例如未知的操作码
+    Malformed(String),
+    /// 程序没有导入声明，宿主函数无法以名字存储
+    MissingImports,
+    /// 异常类型没有在 `FunctionRegistry` 中注册名字，无法编码
+    UnnamedException,
+    /// 模块引用了 `FunctionRegistry` 中不存在的异常类型
+    UnknownException(String),
+    /// 导入声明中第 `index` 个参数（`is_return` 为真时是返回值）是对象类型，但注册的宿主函数在
+    /// 这个位置上不是对象类型
+    ObjectTypeMismatch { func_name: String, index: usize, is_return: bool },
+    /// 链接宿主函数失败
+    Link(LinkError),
+    /// 加载的程序无法通过校验
+    Verify(VerifyError)
+}
+
+impl Display for ModuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ModuleError: ")?;
+        match self {
+            ModuleError::BadMagic => write!(f, "bad magic number"),
+            ModuleError::UnsupportedVersion(version) =>
+                write!(f, "unsupported format version {}", version),
+            ModuleError::ChecksumMismatch { expected, got } =>
+                write!(f, "checksum mismatch: expected {:#010x}, got {:#010x}", expected, got),
+            ModuleError::Truncated => write!(f, "unexpected end of module"),
+            ModuleError::Malformed(message) => write!(f, "{}", message),
+            ModuleError::MissingImports => write!(f, "program has no import declarations"),
+            ModuleError::UnnamedException => write!(f, "exception type has no registered name"),
+            ModuleError::UnknownException(name) => write!(f, "unknown exception \"{}\"", name),
+            ModuleError::ObjectTypeMismatch { func_name, index, is_return } => write!(
+                f, "{} {} of \"{}\" is not an object type in the registered function",
+                if *is_return { "return value" } else { "parameter" }, index, func_name
+            ),
+            ModuleError::Link(error) => write!(f, "{}", error),
+            ModuleError::Verify(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl Error for ModuleError {}
+
+/// 虚拟机执行过程中抛出的错误
+///
+/// 除了错误本身之外，还记录了出错的指令位置，以及出错时调用栈上各个栈帧的返回地址
+#[derive(Debug)]
+pub struct VmError {
+    pub error: Box<TError>,
+    pub insc_ptr: usize,
+    /// 调用栈上各个栈帧的返回地址，自栈底向栈顶排列，不包括最外层的栈帧
+    pub ret_addrs: Vec<usize>
+}
+
+impl VmError {
+    pub fn new(error: TError, insc_ptr: usize, ret_addrs: Vec<usize>) -> Self {
+        Self {
+            error: Box::new(error),
+            insc_ptr,
+            ret_addrs
+        }
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at insc {}", self.error, self.insc_ptr)?;
+        for ret_addr in self.ret_addrs.iter().rev() {
+            write!(f, ", called before insc {}", ret_addr)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Error for VmError {}
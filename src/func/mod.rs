@@ -0,0 +1,658 @@
+//! `func` 模块中定义了与 FFI 调用函数相关的接口
+
+pub mod registry;
+
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::rc::Rc;
+
+use crate::cast::from_value::{FromValue, GcInfoGuard};
+use crate::cast::into_value::IntoValueMulti;
+use crate::data::Value;
+use crate::error::{ArgLenError, NullError, TError, TypeError};
+use crate::intake::Adopt;
+use crate::tyck::{FFIAction, TypeCheckInfo};
+use crate::tyck::fusion::{ExceptionSpec, Fusion, FusionRVMulti, Nullable};
+use crate::void::Void;
+
+/// 按照宿主函数参数的类型检查信息检查 `arg`，`type_name` 是参数的类型名
+pub(crate) fn check_arg(
+    arg: &Value,
+    tyck_info: &TypeCheckInfo,
+    nullable: bool,
+    type_name: &str
+) -> Result<(), TError> {
+    if arg.is_null() {
+        return if nullable { Ok(()) } else { Err(NullError().into()) };
+    }
+    unsafe {
+        if arg.type_check(tyck_info) {
+            return Ok(());
+        }
+        let required = match tyck_info {
+            TypeCheckInfo::SimpleType(tid) | TypeCheckInfo::Container(tid, _) => *tid,
+            TypeCheckInfo::Bypass => unreachable!()
+        };
+        Err(TypeError::of_value(required, arg).add_required_name(type_name.to_string()).into())
+    }
+}
+
+/// 由 `intake` 接管宿主函数 `callable` 写入 `dest` 的返回值中新创建的堆对象
+///
+/// `FFIAction` 不是 `Bypass` 的返回值由 `IntoValue` 包装成新的堆对象，需要交给 `intake` 管理；
+/// `Bypass` 的返回值是宿主原样返回的 `Value`，不重复接管。某个对象接管失败时，释放它和其后尚未
+/// 接管的对象，将对应的返回值置为空值，然后返回错误
+///
+/// # Safety
+/// `dest` 必须是 `callable.call_prechecked` 成功写入的返回值
+pub(crate) unsafe fn adopt_rets<C: RustCallable + ?Sized>(
+    callable: &C,
+    dest: &mut [&mut MaybeUninit<Value>],
+    intake: &mut dyn Adopt
+) -> Result<(), TError> {
+    let is_object = |ret: &MaybeUninit<Value>| {
+        let ret = ret.assume_init_ref();
+        ret.is_ptr() && !ret.is_null()
+    };
+    // 大多数宿主函数只返回值类型，此时不需要查询返回值的 `FFIAction`
+    if !dest.iter().any(|ret| is_object(ret)) {
+        return Ok(());
+    }
+
+    let mut objects = dest.iter_mut()
+        .zip(callable.return_value_spec())
+        .filter(|(ret, (_, action, _))| *action != FFIAction::Bypass && is_object(ret))
+        .map(|(ret, _)| ret)
+        .collect::<Vec<_>>();
+    for i in 0..objects.len() {
+        if let Err(e) = intake.adopt(objects[i].assume_init_read()) {
+            for ret in &mut objects[i..] {
+                ret.assume_init_read().drop_object();
+                ret.write(Value::null());
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// 正在执行的 VM 提供给宿主函数的上下文，宿主函数可以通过它回调 VM 中的函数
+pub trait VmContext {
+    /// 在当前的调用栈上调用 VM 中的函数 `func_id`，返回它的返回值
+    ///
+    /// 嵌套调用的栈帧压在调用宿主函数的栈帧之上。被调用函数中没有被捕获的异常作为 `Err` 返回，
+    /// 宿主函数可以处理它，也可以将它原样返回，由外层的 VM 继续查找异常处理器。
+    fn call_func(&mut self, func_id: usize, args: &[Value]) -> Result<Vec<Value>, TError>;
+}
+
+pub trait RustCallable {
+    fn param_specs(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)>;
+    /// 各个参数的类型名，用于生成错误信息
+    fn param_type_names(&self) -> Vec<&'static str>;
+    fn return_value_spec(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)>;
+    fn exception_spec(&self) -> ExceptionSpec;
+
+    /// 在 `names` 注册表中登记参数和返回值的类型名，`FunctionRegistry::register` 注册函数时调用
+    fn register_type_names(&self) {}
+
+    unsafe fn call_prechecked(
+        &self,
+        args: &[Value],
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError>;
+
+    /// VM 执行 FFI 调用时使用的入口，`vm` 用于回调 VM 中的函数。默认忽略 `vm`，直接调用
+    /// `call_prechecked`
+    ///
+    /// # Safety
+    /// 同 `call_prechecked`
+    unsafe fn call_with_vm(
+        &self,
+        vm: &mut dyn VmContext,
+        args: &[Value],
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        let _ = vm;
+        self.call_prechecked(args, dest)
+    }
+
+    /// 带有运行时类型检查的调用
+    ///
+    /// 在调用之前，根据 `param_specs` 检查参数个数、空值和每个参数的类型，然后再进行实际的调用。
+    /// 适用于动态构建的程序和从宿主一侧发起的调用。返回值中新创建的堆对象由 `intake` 接管
+    fn call_checked(&self, args: &[Value], intake: &mut dyn Adopt) -> Result<Vec<Value>, TError> {
+        let param_specs = self.param_specs();
+        if param_specs.len() != args.len() {
+            return Err(ArgLenError::new(param_specs.len(), args.len()).into());
+        }
+
+        let type_names = self.param_type_names();
+        for ((arg, (tyck_info, _, nullable)), type_name) in
+            args.iter().zip(&param_specs).zip(type_names)
+        {
+            check_arg(arg, tyck_info, *nullable, type_name)?;
+        }
+
+        let ret_count = self.return_value_spec().len();
+        let mut rets = vec![MaybeUninit::uninit(); ret_count];
+        {
+            let mut dest = rets.iter_mut().collect::<Vec<_>>();
+            unsafe {
+                self.call_prechecked(args, &mut dest)?;
+                adopt_rets(self, &mut dest, intake)?;
+            }
+        }
+        Ok(rets.into_iter().map(|ret| unsafe { ret.assume_init() }).collect())
+    }
+}
+
+impl<T: RustCallable + ?Sized> RustCallable for Rc<T> {
+    fn param_specs(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+        (**self).param_specs()
+    }
+
+    fn param_type_names(&self) -> Vec<&'static str> {
+        (**self).param_type_names()
+    }
+
+    fn return_value_spec(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+        (**self).return_value_spec()
+    }
+
+    fn exception_spec(&self) -> ExceptionSpec {
+        (**self).exception_spec()
+    }
+
+    fn register_type_names(&self) {
+        (**self).register_type_names()
+    }
+
+    unsafe fn call_prechecked(
+        &self,
+        args: &[Value],
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        (**self).call_prechecked(args, dest)
+    }
+
+    unsafe fn call_with_vm(
+        &self,
+        vm: &mut dyn VmContext,
+        args: &[Value],
+        dest: &mut [&mut MaybeUninit<Value>]
+    ) -> Result<(), TError> {
+        (**self).call_with_vm(vm, args, dest)
+    }
+}
+
+/// 为接受 N 个参数的 Rust 函数生成 `RustFunctionN` 及其 `RustCallable` 实现
+///
+/// 每个参数以 `(类型参数, 参数变量, guard 变量, 参数下标)` 的形式给出
+macro_rules! impl_rust_function {
+    ($name:ident, $arg_count:expr $(, ($arg_ty:ident, $arg:ident, $guard:ident, $idx:expr))*) => {
+        pub struct $name<F, $($arg_ty,)* RET>
+            where F: 'static + Fn($($arg_ty),*) -> RET + Send + Sync,
+                  $(Void: FromValue<$arg_ty> + Fusion<$arg_ty>,)*
+                  Void: IntoValueMulti<RET> + FusionRVMulti<RET>
+        {
+            pub f: F,
+            pub _phantom: PhantomData<($($arg_ty,)* RET,)>
+        }
+
+        impl<F, $($arg_ty,)* RET> RustCallable for $name<F, $($arg_ty,)* RET>
+            where F: 'static + Fn($($arg_ty),*) -> RET + Send + Sync,
+                  $(Void: FromValue<$arg_ty> + Fusion<$arg_ty>,)*
+                  Void: IntoValueMulti<RET> + FusionRVMulti<RET>
+        {
+            fn param_specs(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                vec![
+                    $((<Void as Fusion<$arg_ty>>::fusion_tyck_info(),
+                       <Void as Fusion<$arg_ty>>::fusion_ffi_action(),
+                       <Void as Fusion<$arg_ty>>::nullable()),)*
+                ]
+            }
+
+            fn param_type_names(&self) -> Vec<&'static str> {
+                vec![$(std::any::type_name::<$arg_ty>(),)*]
+            }
+
+            fn return_value_spec(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                <Void as FusionRVMulti<RET>>::rv_specs()
+            }
+
+            fn exception_spec(&self) -> ExceptionSpec {
+                <Void as FusionRVMulti<RET>>::exception_multi()
+            }
+
+            fn register_type_names(&self) {
+                $(<Void as Fusion<$arg_ty>>::fusion_register_names();)*
+                <Void as FusionRVMulti<RET>>::register_names_multi();
+            }
+
+            unsafe fn call_prechecked(
+                &self,
+                args: &[Value],
+                dest: &mut [&mut MaybeUninit<Value>]
+            ) -> Result<(), TError> {
+                debug_assert_eq!(args.len(), $arg_count);
+                $(let $arg = args.get_unchecked($idx);)*
+                // 任何一个参数的检查失败时，先前获得的 guard 会按照声明的逆序被 drop，从而回滚
+                $(let mut $guard: GcInfoGuard = <Void as FromValue<$arg_ty>>::lifetime_check($arg)?;)*
+
+                let ret = (self.f)(
+                    $(<Void as FromValue<$arg_ty>>::from_value($arg)),*
+                );
+                $($guard.finish();)*
+
+                <Void as IntoValueMulti<RET>>::into_value_multi(ret, dest)
+            }
+        }
+    };
+}
+
+impl_rust_function!(RustFunction0, 0);
+impl_rust_function!(RustFunction1, 1, (A, arg1, arg1_guard, 0));
+impl_rust_function!(RustFunction2, 2, (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1));
+impl_rust_function!(RustFunction3, 3,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2));
+impl_rust_function!(RustFunction4, 4,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3));
+impl_rust_function!(RustFunction5, 5,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4));
+impl_rust_function!(RustFunction6, 6,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5));
+impl_rust_function!(RustFunction7, 7,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6));
+impl_rust_function!(RustFunction8, 8,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6), (I, arg8, arg8_guard, 7));
+impl_rust_function!(RustFunction9, 9,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6), (I, arg8, arg8_guard, 7), (J, arg9, arg9_guard, 8));
+impl_rust_function!(RustFunction10, 10,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6), (I, arg8, arg8_guard, 7), (J, arg9, arg9_guard, 8),
+    (K, arg10, arg10_guard, 9));
+impl_rust_function!(RustFunction11, 11,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6), (I, arg8, arg8_guard, 7), (J, arg9, arg9_guard, 8),
+    (K, arg10, arg10_guard, 9), (L, arg11, arg11_guard, 10));
+impl_rust_function!(RustFunction12, 12,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3), (E, arg5, arg5_guard, 4), (G, arg6, arg6_guard, 5),
+    (H, arg7, arg7_guard, 6), (I, arg8, arg8_guard, 7), (J, arg9, arg9_guard, 8),
+    (K, arg10, arg10_guard, 9), (L, arg11, arg11_guard, 10), (M, arg12, arg12_guard, 11));
+
+/// 为第一个参数是 `&mut dyn VmContext` 的 Rust 函数生成 `RustVmFunctionN` 及其 `RustCallable` 实现
+///
+/// 这样的宿主函数只能由 VM 调用，`call_prechecked` 和 `call_checked` 没有 VM 上下文，总是失败
+macro_rules! impl_rust_vm_function {
+    ($name:ident, $arg_count:expr $(, ($arg_ty:ident, $arg:ident, $guard:ident, $idx:expr))*) => {
+        pub struct $name<F, $($arg_ty,)* RET>
+            where F: 'static + Fn(&mut dyn VmContext, $($arg_ty),*) -> RET + Send + Sync,
+                  $(Void: FromValue<$arg_ty> + Fusion<$arg_ty>,)*
+                  Void: IntoValueMulti<RET> + FusionRVMulti<RET>
+        {
+            pub f: F,
+            pub _phantom: PhantomData<($($arg_ty,)* RET,)>
+        }
+
+        impl<F, $($arg_ty,)* RET> RustCallable for $name<F, $($arg_ty,)* RET>
+            where F: 'static + Fn(&mut dyn VmContext, $($arg_ty),*) -> RET + Send + Sync,
+                  $(Void: FromValue<$arg_ty> + Fusion<$arg_ty>,)*
+                  Void: IntoValueMulti<RET> + FusionRVMulti<RET>
+        {
+            fn param_specs(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                vec![
+                    $((<Void as Fusion<$arg_ty>>::fusion_tyck_info(),
+                       <Void as Fusion<$arg_ty>>::fusion_ffi_action(),
+                       <Void as Fusion<$arg_ty>>::nullable()),)*
+                ]
+            }
+
+            fn param_type_names(&self) -> Vec<&'static str> {
+                vec![$(std::any::type_name::<$arg_ty>(),)*]
+            }
+
+            fn return_value_spec(&self) -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                <Void as FusionRVMulti<RET>>::rv_specs()
+            }
+
+            fn exception_spec(&self) -> ExceptionSpec {
+                <Void as FusionRVMulti<RET>>::exception_multi()
+            }
+
+            fn register_type_names(&self) {
+                $(<Void as Fusion<$arg_ty>>::fusion_register_names();)*
+                <Void as FusionRVMulti<RET>>::register_names_multi();
+            }
+
+            unsafe fn call_prechecked(
+                &self,
+                _args: &[Value],
+                _dest: &mut [&mut MaybeUninit<Value>]
+            ) -> Result<(), TError> {
+                Err(TError::unchecked_exception("this host function can only be called by a VM"))
+            }
+
+            unsafe fn call_with_vm(
+                &self,
+                vm: &mut dyn VmContext,
+                args: &[Value],
+                dest: &mut [&mut MaybeUninit<Value>]
+            ) -> Result<(), TError> {
+                debug_assert_eq!(args.len(), $arg_count);
+                $(let $arg = args.get_unchecked($idx);)*
+                // 参数在回调期间保持借用状态，VM 中的函数不能同时以冲突的方式使用它们
+                $(let mut $guard: GcInfoGuard = <Void as FromValue<$arg_ty>>::lifetime_check($arg)?;)*
+
+                let ret = (self.f)(
+                    vm,
+                    $(<Void as FromValue<$arg_ty>>::from_value($arg)),*
+                );
+                $($guard.finish();)*
+
+                <Void as IntoValueMulti<RET>>::into_value_multi(ret, dest)
+            }
+        }
+    };
+}
+
+impl_rust_vm_function!(RustVmFunction0, 0);
+impl_rust_vm_function!(RustVmFunction1, 1, (A, arg1, arg1_guard, 0));
+impl_rust_vm_function!(RustVmFunction2, 2, (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1));
+impl_rust_vm_function!(RustVmFunction3, 3,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2));
+impl_rust_vm_function!(RustVmFunction4, 4,
+    (A, arg1, arg1_guard, 0), (B, arg2, arg2_guard, 1), (C, arg3, arg3_guard, 2),
+    (D, arg4, arg4_guard, 3));
+
+/// 两个参数的 `RustFunction`，保留这个名字以兼容现有代码
+pub type RustFunction<F, A, B, RET> = RustFunction2<F, A, B, RET>;
+
+#[cfg(test)]
+mod test {
+    extern crate test;
+
+    use std::any::TypeId;
+    use std::cell::Cell;
+    use std::marker::PhantomData;
+    use std::mem::MaybeUninit;
+    use test::Bencher;
+
+    use crate::data::{StaticWrapper, DynBase, GcInfo};
+    use crate::error::{TError, TypeError};
+    use crate::intake::oxtank::OxTank;
+    use crate::func::{
+        Value, RustFunction, RustFunction0, RustFunction1, RustFunction3, RustFunction5, RustCallable
+    };
+
+    struct S(i32);
+
+    fn bar(x: &mut S, y: &S) -> i64 {
+        x.0 = y.0;
+        x.0 as i64
+    }
+
+    #[test] fn test_simple_call() {
+        let s1 = Box::leak(Box::new(StaticWrapper::owned(S(0)))) as *mut dyn DynBase;
+        let s2 = Box::leak(Box::new(StaticWrapper::owned(S(4)))) as *mut dyn DynBase;
+        let v1 = Value::from(s1);
+        let v2 = Value::from(s2);
+        let f = RustFunction { f: bar, _phantom: PhantomData::default() };
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        unsafe {
+            f.call_prechecked(&[v1, v2], &mut dest_value_ref).unwrap();
+        }
+    }
+
+    fn baz(x: i64, y: i64) -> i64 {
+        x + y
+    }
+
+    #[test] fn test_simple_call2() {
+        let v1 = Value::from(14i64);
+        let v2 = Value::from(40i64);
+        let f = RustFunction { f: baz, _phantom: PhantomData::default() };
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        unsafe {
+            f.call_prechecked(&[v1, v2], &mut dest_value_ref).unwrap();
+        }
+    }
+
+    fn answer() -> i64 {
+        42
+    }
+
+    #[test] fn test_nullary_call() {
+        let f = RustFunction0 { f: answer, _phantom: PhantomData };
+        assert_eq!(f.param_specs().len(), 0);
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        unsafe {
+            f.call_prechecked(&[], &mut dest_value_ref).unwrap();
+            assert_eq!(dest.assume_init().value_typed_data.inner.int, 42);
+        }
+    }
+
+    fn sum5(a: i64, b: i64, c: i64, d: &mut S, e: Option<i64>) -> i64 {
+        d.0 = 1;
+        a + b + c + e.unwrap_or(0)
+    }
+
+    #[test] fn test_call5() {
+        let s = Box::leak(Box::new(StaticWrapper::owned(S(0)))) as *mut dyn DynBase;
+        let f = RustFunction5 { f: sum5, _phantom: PhantomData };
+        let specs = f.param_specs();
+        assert_eq!(specs.len(), 5);
+        assert!(!specs[3].2);
+        assert!(specs[4].2);
+
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        let args = [Value::from(1i64), Value::from(2i64), Value::from(3i64),
+                    Value::from(s), Value::from(4i64)];
+        unsafe {
+            f.call_prechecked(&args, &mut dest_value_ref).unwrap();
+            assert_eq!(dest.assume_init().value_typed_data.inner.int, 10);
+            assert_eq!(args[3].as_ref::<S>().0, 1);
+        }
+    }
+
+    fn swap_s(x: &mut S, y: &mut S) -> i64 {
+        std::mem::swap(x, y);
+        0
+    }
+
+    #[test] fn test_double_mut_borrow() {
+        let s = Box::leak(Box::new(StaticWrapper::owned(S(0)))) as *mut dyn DynBase;
+        let v = Value::from(s);
+        let f = RustFunction { f: swap_s, _phantom: PhantomData };
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        let result = unsafe { f.call_prechecked(&[v, v], &mut dest_value_ref) };
+        assert!(matches!(result, Err(TError::LifetimeError(_))));
+        assert_eq!(v.gc_info(), GcInfo::Owned);
+    }
+
+    #[test] fn test_null_arg() {
+        let s = Box::leak(Box::new(StaticWrapper::owned(S(0)))) as *mut dyn DynBase;
+        let v = Value::from(s);
+        let f = RustFunction { f: bar, _phantom: PhantomData };
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        let result = unsafe { f.call_prechecked(&[v, Value::null()], &mut dest_value_ref) };
+        assert!(matches!(result, Err(TError::NullError(_))));
+        assert_eq!(v.gc_info(), GcInfo::Owned);
+
+        let f = RustFunction5 { f: sum5, _phantom: PhantomData };
+        let args = [Value::from(1i64), Value::from(2i64), Value::from(3i64),
+                    v, Value::null()];
+        unsafe {
+            f.call_prechecked(&args, &mut dest_value_ref).unwrap();
+            assert_eq!(dest.assume_init().value_typed_data.inner.int, 6);
+        }
+        assert_eq!(v.gc_info(), GcInfo::Owned);
+    }
+
+    fn div_mod(x: i64, y: i64) -> Result<(i64, i64), std::fmt::Error> {
+        if y == 0 {
+            Err(std::fmt::Error)
+        } else {
+            Ok((x / y, x % y))
+        }
+    }
+
+    #[test] fn test_multiple_returns() {
+        let f = RustFunction { f: div_mod, _phantom: PhantomData };
+        assert_eq!(f.return_value_spec().len(), 2);
+        assert_eq!(f.exception_spec(), Some(std::any::TypeId::of::<std::fmt::Error>()));
+
+        let mut quot = MaybeUninit::uninit();
+        let mut rem = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut quot, &mut rem];
+        unsafe {
+            f.call_prechecked(&[Value::from(17i64), Value::from(5i64)], &mut dest_value_ref)
+                .unwrap();
+            let result = f.call_prechecked(&[Value::from(17i64), Value::from(0i64)],
+                                           &mut dest_value_ref);
+            assert!(matches!(result, Err(TError::UserException(..))));
+            assert_eq!(quot.assume_init().value_typed_data.inner.int, 3);
+            assert_eq!(rem.assume_init().value_typed_data.inner.int, 2);
+        }
+    }
+
+    fn consume(_x: i64) {}
+
+    #[test] fn test_no_return() {
+        let f = RustFunction1 { f: consume, _phantom: PhantomData };
+        assert_eq!(f.return_value_spec().len(), 0);
+        unsafe {
+            f.call_prechecked(&[Value::from(1i64)], &mut []).unwrap();
+        }
+    }
+
+    #[test] fn test_call_checked() {
+        let mut intake = OxTank::default();
+        let f = RustFunction { f: baz, _phantom: PhantomData };
+        let rets = f.call_checked(&[Value::from(14i64), Value::from(40i64)], &mut intake).unwrap();
+        assert_eq!(rets.len(), 1);
+        assert_eq!(unsafe { rets[0].value_typed_data.inner.int }, 54);
+
+        let result = f.call_checked(&[Value::from(14i64)], &mut intake);
+        assert!(matches!(result, Err(TError::ArgLenError(_))));
+
+        let result = f.call_checked(&[Value::from(14i64), Value::null()], &mut intake);
+        assert!(matches!(result, Err(TError::NullError(_))));
+
+        let s = Box::leak(Box::new(StaticWrapper::owned(S(0)))) as *mut dyn DynBase;
+        let result = f.call_checked(&[Value::from(14i64), Value::from(s)], &mut intake);
+        if let Err(TError::TypeError(e)) = result {
+            assert_eq!(e.required_name.as_deref(), Some("i64"));
+            assert!(e.actual_name.unwrap().ends_with("S"));
+        } else {
+            panic!("expected a TypeError");
+        }
+
+        let f = RustFunction { f: bar, _phantom: PhantomData };
+        let result = f.call_checked(&[Value::from(s), Value::from(true)], &mut intake);
+        if let Err(TError::TypeError(e)) = result {
+            assert!(e.required_name.unwrap().ends_with("S"));
+            assert_eq!(e.actual_name.as_deref(), Some("bool"));
+        } else {
+            panic!("expected a TypeError");
+        }
+
+        // `S` 的类型名在上面的调用中已经登记
+        let err = TypeError::new(TypeId::of::<bool>(), TypeId::of::<S>());
+        assert_eq!(err.required_name.as_deref(), Some("bool"));
+        assert!(err.actual_name.unwrap().ends_with("S"));
+    }
+
+    thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Tracked(i64);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+
+    fn track(x: i64) -> Tracked {
+        Tracked(x)
+    }
+
+    fn echo(value: Value) -> Value {
+        value
+    }
+
+    #[test] fn test_call_checked_adopt() {
+        let mut intake = OxTank::default();
+        let f = RustFunction1 { f: track, _phantom: PhantomData };
+        let rets = f.call_checked(&[Value::from(3i64)], &mut intake).unwrap();
+        assert_eq!(unsafe { rets[0].as_ref::<Tracked>() }.0, 3);
+        assert_eq!(intake.object_count(), 1);
+
+        // 宿主原样返回的 `Value` 已经归 `intake` 所有，不会被再次接管
+        let f = RustFunction1 { f: echo, _phantom: PhantomData };
+        let echoed = f.call_checked(&rets, &mut intake).unwrap();
+        assert_eq!(unsafe { echoed[0].as_ref::<Tracked>() }.0, 3);
+        assert_eq!(intake.object_count(), 1);
+
+        assert_eq!(DROPS.with(Cell::get), 0);
+        drop(intake);
+        assert_eq!(DROPS.with(Cell::get), 1);
+
+        // 接管失败时，返回的对象被立即释放
+        let mut intake = OxTank::new_limited(0);
+        let f = RustFunction1 { f: track, _phantom: PhantomData };
+        let result = f.call_checked(&[Value::from(4i64)], &mut intake);
+        assert!(matches!(result, Err(TError::OutOfMemory(_))));
+        assert_eq!(DROPS.with(Cell::get), 2);
+    }
+
+    fn scale(x: f64, c: char, negate: bool) -> (f64, char, bool) {
+        (if negate { -x * 2.0 } else { x * 2.0 }, c.to_ascii_uppercase(), !negate)
+    }
+
+    #[test] fn test_value_types() {
+        let mut intake = OxTank::default();
+        let f = RustFunction3 { f: scale, _phantom: PhantomData };
+        let rets = f.call_checked(&[Value::from(1.5f64), Value::from('q'), Value::from(true)], &mut intake)
+            .unwrap();
+        assert_eq!(rets[0].as_float(), Some(-3.0));
+        assert_eq!(rets[1].as_char(), Some('Q'));
+        assert_eq!(rets[2].as_bool(), Some(false));
+    }
+
+    #[bench] fn bench_simple_call2(b: &mut Bencher) {
+        let f = RustFunction { f: baz, _phantom: PhantomData::default() };
+        let mut dest = MaybeUninit::uninit();
+        let mut dest_value_ref = [&mut dest];
+        b.iter(|| {
+            for i in 0..1000i64 {
+                for j in 0..1000i64 {
+                    let v1 = Value::from(i);
+                    let v2 = Value::from(j);
+                    unsafe {
+                        let _ = f.call_prechecked(&[v1, v2], &mut dest_value_ref);
+                    }
+                }
+            }
+        })
+    }
+}
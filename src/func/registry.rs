@@ -0,0 +1,280 @@
+//! `registry` 模块实现了按名字绑定宿主函数的 `FunctionRegistry`
+//!
+//! 程序通过 `FFIImport` 声明自己需要的宿主函数及其签名，在链接时由 `FunctionRegistry` 将名字解析为
+//! 下标，并检查参数、返回值的个数和类型。这样签名不匹配的问题会在链接时暴露出来，而不是在调用时。
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+
+use crate::error::{ArgLenError, LinkError};
+use crate::func::RustCallable;
+use crate::tyck::{FFIAction, TypeCheckInfo};
+use crate::tyck::fusion::{ExceptionSpec, Nullable};
+
+/// 程序对一个宿主函数的导入声明
+#[derive(Debug, Clone)]
+pub struct FFIImport {
+    /// 带有限定的函数名，例如 `"math::add"`
+    pub name: String,
+    /// 程序所期望的参数类型
+    pub param_tyck_info: Vec<TypeCheckInfo>,
+    /// 程序所期望的返回值类型
+    pub ret_tyck_info: Vec<TypeCheckInfo>
+}
+
+impl FFIImport {
+    pub fn new(
+        name: impl ToString,
+        param_tyck_info: Vec<TypeCheckInfo>,
+        ret_tyck_info: Vec<TypeCheckInfo>
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            param_tyck_info,
+            ret_tyck_info
+        }
+    }
+}
+
+/// 一个已经注册的宿主函数，以及注册时取得的签名信息
+pub struct RegisteredFunction {
+    pub name: String,
+    pub callable: Rc<dyn RustCallable>,
+    pub param_specs: Vec<(TypeCheckInfo, FFIAction, Nullable)>,
+    pub return_value_spec: Vec<(TypeCheckInfo, FFIAction, Nullable)>,
+    pub exception_spec: ExceptionSpec
+}
+
+/// 按名字管理宿主函数的注册表
+#[derive(Default)]
+pub struct FunctionRegistry {
+    funcs: Vec<RegisteredFunction>,
+    name_map: HashMap<String, usize>,
+    /// 按名字注册的异常类型，供汇编器等需要以文本形式引用异常类型的地方使用
+    exception_types: HashMap<String, TypeId>
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以 `name` 为名注册一个宿主函数，返回它在注册表中的下标
+    ///
+    /// 注册时将函数参数和返回值的类型名登记到 `tyck::names` 中，供 `TypeError` 打印类型名
+    pub fn register(
+        &mut self,
+        name: impl ToString,
+        callable: impl 'static + RustCallable
+    ) -> Result<usize, LinkError> {
+        let name = name.to_string();
+        if self.name_map.contains_key(&name) {
+            return Err(LinkError::DuplicateFunction(name));
+        }
+
+        callable.register_type_names();
+        let idx = self.funcs.len();
+        self.funcs.push(RegisteredFunction {
+            name: name.clone(),
+            param_specs: callable.param_specs(),
+            return_value_spec: callable.return_value_spec(),
+            exception_spec: callable.exception_spec(),
+            callable: Rc::new(callable)
+        });
+        self.name_map.insert(name, idx);
+        Ok(idx)
+    }
+
+    /// 以 `name` 为名注册异常类型 `E`
+    pub fn register_exception<E: 'static + Error>(&mut self, name: impl ToString) {
+        self.exception_types.insert(name.to_string(), TypeId::of::<E>());
+    }
+
+    pub fn exception_type(&self, name: &str) -> Option<TypeId> {
+        self.exception_types.get(name).copied()
+    }
+
+    pub fn exception_name(&self, type_id: TypeId) -> Option<&str> {
+        self.exception_types.iter()
+            .find(|(_, registered)| **registered == type_id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<usize> {
+        self.name_map.get(name).copied()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&RegisteredFunction> {
+        self.funcs.get(idx)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&RegisteredFunction> {
+        self.resolve(name).map(|idx| &self.funcs[idx])
+    }
+
+    pub fn len(&self) -> usize {
+        self.funcs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.funcs.is_empty()
+    }
+
+    /// 解析一个导入声明，并检查其签名与注册的宿主函数是否一致
+    pub fn link_import(&self, import: &FFIImport) -> Result<usize, LinkError> {
+        let idx = self.resolve(&import.name)
+            .ok_or_else(|| LinkError::UnresolvedFunction(import.name.clone()))?;
+        let func = &self.funcs[idx];
+
+        if func.param_specs.len() != import.param_tyck_info.len() {
+            return Err(LinkError::ArgLenMismatch {
+                func_name: import.name.clone(),
+                error: ArgLenError::new(func.param_specs.len(), import.param_tyck_info.len())
+            });
+        }
+        if func.return_value_spec.len() != import.ret_tyck_info.len() {
+            return Err(LinkError::RetLenMismatch {
+                func_name: import.name.clone(),
+                error: ArgLenError::new(func.return_value_spec.len(), import.ret_tyck_info.len())
+            });
+        }
+
+        for (index, ((expected, _, _), got)) in
+            func.param_specs.iter().zip(&import.param_tyck_info).enumerate()
+        {
+            if !expected.accepts(got) {
+                return Err(LinkError::ArgTypeMismatch {
+                    func_name: import.name.clone(),
+                    index,
+                    expected: expected.clone(),
+                    got: got.clone()
+                });
+            }
+        }
+        for (index, ((got, _, _), expected)) in
+            func.return_value_spec.iter().zip(&import.ret_tyck_info).enumerate()
+        {
+            if !expected.accepts(got) {
+                return Err(LinkError::RetTypeMismatch {
+                    func_name: import.name.clone(),
+                    index,
+                    expected: expected.clone(),
+                    got: got.clone()
+                });
+            }
+        }
+
+        Ok(idx)
+    }
+
+    /// 依次解析所有的导入声明，返回每个导入在注册表中的下标
+    pub fn link(&self, imports: &[FFIImport]) -> Result<Vec<usize>, LinkError> {
+        imports.iter().map(|import| self.link_import(import)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::marker::PhantomData;
+
+    use crate::error::{LinkError, TypeError};
+    use crate::func::{RustFunction, RustFunction1};
+    use crate::func::registry::{FFIImport, FunctionRegistry};
+    use crate::tyck::TypeCheckInfo;
+
+    fn add(x: i64, y: i64) -> i64 {
+        x + y
+    }
+
+    struct S(i64);
+
+    fn get(s: &S) -> i64 {
+        s.0
+    }
+
+    fn int() -> TypeCheckInfo {
+        TypeCheckInfo::SimpleType(TypeId::of::<i64>())
+    }
+
+    fn registry() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry.register("math::add", RustFunction { f: add, _phantom: PhantomData }).unwrap();
+        registry.register("s::get", RustFunction1 { f: get, _phantom: PhantomData }).unwrap();
+        registry
+    }
+
+    #[test] fn test_register_resolve() {
+        let mut registry = registry();
+        assert_eq!(registry.resolve("math::add"), Some(0));
+        assert_eq!(registry.resolve("s::get"), Some(1));
+        assert_eq!(registry.resolve("math::sub"), None);
+        assert_eq!(registry.get_by_name("math::add").unwrap().param_specs.len(), 2);
+
+        let result = registry.register("math::add", RustFunction { f: add, _phantom: PhantomData });
+        assert!(matches!(result, Err(LinkError::DuplicateFunction(_))));
+
+        registry.register_exception::<std::fmt::Error>("fmt::Error");
+        assert_eq!(registry.exception_type("fmt::Error"), Some(TypeId::of::<std::fmt::Error>()));
+        assert_eq!(registry.exception_name(TypeId::of::<std::fmt::Error>()), Some("fmt::Error"));
+        assert_eq!(registry.exception_type("io::Error"), None);
+    }
+
+    #[test] fn test_link() {
+        let registry = registry();
+        let imports = [
+            FFIImport::new("s::get", vec![TypeCheckInfo::SimpleType(TypeId::of::<S>())],
+                           vec![int()]),
+            FFIImport::new("math::add", vec![int(), int()], vec![int()])
+        ];
+        assert_eq!(registry.link(&imports).unwrap(), vec![1, 0]);
+    }
+
+    #[test] fn test_link_mismatch() {
+        let registry = registry();
+
+        let import = FFIImport::new("math::sub", vec![int(), int()], vec![int()]);
+        assert!(matches!(registry.link_import(&import),
+                         Err(LinkError::UnresolvedFunction(_))));
+
+        let import = FFIImport::new("math::add", vec![int()], vec![int()]);
+        assert!(matches!(registry.link_import(&import),
+                         Err(LinkError::ArgLenMismatch { .. })));
+
+        let import = FFIImport::new("math::add", vec![int(), int()], vec![]);
+        assert!(matches!(registry.link_import(&import),
+                         Err(LinkError::RetLenMismatch { .. })));
+
+        let import = FFIImport::new("s::get", vec![int()], vec![int()]);
+        assert!(matches!(registry.link_import(&import),
+                         Err(LinkError::ArgTypeMismatch { index: 0, .. })));
+
+        let import = FFIImport::new(
+            "s::get",
+            vec![TypeCheckInfo::SimpleType(TypeId::of::<S>())],
+            vec![TypeCheckInfo::SimpleType(TypeId::of::<bool>())]
+        );
+        assert!(matches!(registry.link_import(&import),
+                         Err(LinkError::RetTypeMismatch { index: 0, .. })));
+    }
+
+    #[test] fn test_register_type_names() {
+        struct Param(i64);
+        struct Ret;
+
+        fn convert(param: &Param) -> Option<Ret> {
+            (param.0 > 0).then_some(Ret)
+        }
+
+        let err = TypeError::new(TypeId::of::<Param>(), TypeId::of::<Ret>());
+        assert_eq!((err.required_name, err.actual_name), (None, None));
+
+        let mut registry = FunctionRegistry::new();
+        registry.register("convert", RustFunction1 { f: convert, _phantom: PhantomData }).unwrap();
+        let err = TypeError::new(TypeId::of::<Param>(), TypeId::of::<Ret>());
+        assert!(err.required_name.unwrap().ends_with("Param"));
+        assert!(err.actual_name.unwrap().ends_with("Ret"));
+    }
+}
@@ -1,13 +1,68 @@
+//! 虚拟机堆对象的分配和回收
+
 use std::mem::MaybeUninit;
-use crate::data::{StaticWrapper, Value};
+use crate::data::{GcInfo, Value};
+use crate::error::TError;
+
+pub mod ramped;
+pub mod oxtank;
+
+/// 接管在分配器之外创建的堆对象
+///
+/// 这一部分从 `OxProvider` 中分离出来，因此可以作为 trait 对象使用。虚拟机通过 `&mut dyn Adopt`
+/// 接管宿主函数返回的对象和被捕获的异常对象。VM 的栈不在分配器的根集合中，因此实现者不能在
+/// `adopt` 中进行垃圾回收
+pub trait Adopt {
+    /// 接管一个在分配器之外创建的堆对象，例如 `IntoValue` 的结果。接管失败时，对象仍归调用者所有
+    ///
+    /// # Safety
+    /// `value` 必须指向一个通过 `Box` 分配的包装对象，并且不归任何其他分配器管理
+    unsafe fn adopt(&mut self, value: Value) -> Result<(), TError>;
+}
 
-mod ramped;
-mod oxtank;
+/// 堆对象分配器
+///
+/// 分配得到的对象由分配器持有，以指向包装对象的 `Value` 的形式交给虚拟机使用。分配器可以限制
+/// 分配的内存总量，超出限制时分配操作返回 `TError::OutOfMemory`
+pub trait OxProvider<'a>: Adopt {
+    /// 将 `t` 移动到堆上，创建一个 `Owned` 状态的对象
+    fn allocate<T: 'static>(&mut self, t: T) -> Result<Value, TError>;
+    /// 创建一个 `SharedFromHost` 状态的对象，它只持有指向 `t` 的指针
+    ///
+    /// 借出非 `'static` 的引用时应当使用 `with_borrowed`
+    ///
+    /// # Safety
+    /// 在 `t` 的借用结束之前，调用者必须保证虚拟机不再访问返回的对象，例如将其标记为 `Dropped`
+    unsafe fn allocate_ref<T: 'static>(&mut self, t: &T) -> Result<Value, TError>;
+    /// 创建一个 `MutSharedFromHost` 状态的对象，它只持有指向 `t` 的指针
+    ///
+    /// 借出非 `'static` 的引用时应当使用 `with_borrowed_mut`
+    ///
+    /// # Safety
+    /// 同 `allocate_ref`
+    unsafe fn allocate_ref_mut<T: 'static>(&mut self, t: &mut T) -> Result<Value, TError>;
 
-pub trait OxProvider<'a> {
-    fn allocate<T>(&mut self, t: T) -> StaticWrapper<T>;
-    fn allocate_ref<T>(&mut self, t: &T) -> StaticWrapper<T>;
-    fn allocate_ref_mut<T>(&mut self, t: &mut T) -> StaticWrapper<T>;
+    /// 在 `f` 执行期间将 Rust 一侧的共享引用 `t` 借给虚拟机，`t` 不需要具有 `'static` 生存期
+    ///
+    /// `f` 返回或者 panic 之后，借出的对象会被标记为 `Dropped`，此后虚拟机对它的任何访问都会得到
+    /// `LifetimeError`，而不会访问悬垂指针
+    fn with_borrowed<T: 'static, R>(&mut self, t: &T, f: impl FnOnce(Value) -> R)
+        -> Result<R, TError>
+    {
+        // `RevokeGuard` 保证借用结束之后虚拟机不再访问借出的对象
+        let value = unsafe { self.allocate_ref(t)? };
+        let _revoke = RevokeGuard(value);
+        Ok(f(value))
+    }
+
+    /// 在 `f` 执行期间将 Rust 一侧的可变引用 `t` 借给虚拟机，参见 `with_borrowed`
+    fn with_borrowed_mut<T: 'static, R>(&mut self, t: &mut T, f: impl FnOnce(Value) -> R)
+        -> Result<R, TError>
+    {
+        let value = unsafe { self.allocate_ref_mut(t)? };
+        let _revoke = RevokeGuard(value);
+        Ok(f(value))
+    }
 
     fn force_collect(&mut self);
 
@@ -16,3 +71,12 @@ pub trait OxProvider<'a> {
     fn enable_collect(&mut self);
     fn disable_collect(&mut self);
 }
+
+/// 借用结束时撤销虚拟机对借用对象的访问权
+struct RevokeGuard(Value);
+
+impl Drop for RevokeGuard {
+    fn drop(&mut self) {
+        unsafe { self.0.set_gc_info(GcInfo::Dropped); }
+    }
+}
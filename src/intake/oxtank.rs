@@ -1,9 +1,18 @@
-use crate::data::{Value, StaticWrapper};
-use crate::intake::OxProvider;
-use std::mem::MaybeUninit;
+use crate::data::{DynBase, HeapSize, StaticWrapper, Value};
+use crate::error::{OutOfMemory, TError};
+use crate::intake::{Adopt, OxProvider};
+use std::mem::{MaybeUninit, size_of, size_of_val};
 
+/// 不进行垃圾回收的分配器
+///
+/// `OxTank` 适用于生命周期很短的脚本：分配的对象在 `OxTank` 被丢弃时一次性全部释放。使用
+/// `new_limited` 创建的 `OxTank` 最多分配 `capacity` 字节，超出限制的分配操作会返回
+/// `TError::OutOfMemory`，从而为嵌入者提供确定的内存上限。
+///
+/// 每个对象计入的字节数是包装对象的大小加上分配时载荷通过 `HeapSize` 给出的堆内存大小。载荷在
+/// 分配之后的增长（例如宿主通过 `&mut String` 追加内容）不会被计入。
 pub struct OxTank {
-    heap: Vec<*mut Value>,
+    heap: Vec<*mut dyn DynBase>,
     capacity: Option<usize>,
 
     used: usize
@@ -27,6 +36,37 @@ impl OxTank {
             used: 0
         }
     }
+
+    /// 可以分配的字节数上限，`None` 表示没有限制
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// 已经分配的字节数
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// 堆上现存的对象个数
+    pub fn object_count(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn reserve(&mut self, size: usize) -> Result<(), TError> {
+        match self.capacity {
+            Some(capacity) if capacity - self.used < size =>
+                Err(OutOfMemory::new(capacity, self.used, size).into()),
+            _ => {
+                self.used += size;
+                Ok(())
+            }
+        }
+    }
+
+    fn add_object(&mut self, object: *mut dyn DynBase) -> Value {
+        self.heap.push(object);
+        Value::from(object)
+    }
 }
 
 impl Default for OxTank {
@@ -35,17 +75,41 @@ impl Default for OxTank {
     }
 }
 
+impl Drop for OxTank {
+    fn drop(&mut self) {
+        for object in self.heap.drain(..) {
+            unsafe { drop(Box::from_raw(object)); }
+        }
+    }
+}
+
+impl Adopt for OxTank {
+    unsafe fn adopt(&mut self, value: Value) -> Result<(), TError> {
+        debug_assert!(value.is_ptr() && !value.is_null());
+        let object = &*value.ptr;
+        self.reserve(size_of_val(object) + object.dyn_heap_size())?;
+        self.add_object(value.ptr);
+        Ok(())
+    }
+}
+
 impl OxProvider<'_> for OxTank {
-    fn allocate<T>(&mut self, _t: T) -> StaticWrapper<T> {
-        unimplemented!()
+    fn allocate<T: 'static>(&mut self, t: T) -> Result<Value, TError> {
+        self.reserve(size_of::<StaticWrapper<T>>() + t.heap_size())?;
+        let object = Box::into_raw(Box::new(StaticWrapper::owned(t)));
+        Ok(self.add_object(object as *mut dyn DynBase))
     }
 
-    fn allocate_ref<T>(&mut self, _t: &T) -> StaticWrapper<T> {
-        unimplemented!()
+    unsafe fn allocate_ref<T: 'static>(&mut self, t: &T) -> Result<Value, TError> {
+        self.reserve(size_of::<StaticWrapper<T>>())?;
+        let object = Box::into_raw(Box::new(StaticWrapper::shared(t)));
+        Ok(self.add_object(object as *mut dyn DynBase))
     }
 
-    fn allocate_ref_mut<T>(&mut self, _t: &mut T) -> StaticWrapper<T> {
-        unimplemented!()
+    unsafe fn allocate_ref_mut<T: 'static>(&mut self, t: &mut T) -> Result<Value, TError> {
+        self.reserve(size_of::<StaticWrapper<T>>())?;
+        let object = Box::into_raw(Box::new(StaticWrapper::mut_shared(t)));
+        Ok(self.add_object(object as *mut dyn DynBase))
     }
 
     fn force_collect(&mut self) {}
@@ -56,3 +120,69 @@ impl OxProvider<'_> for OxTank {
 
     fn disable_collect(&mut self) {}
 }
+
+#[cfg(test)]
+mod test {
+    use std::mem::size_of;
+
+    use crate::data::StaticWrapper;
+    use crate::error::TError;
+    use crate::intake::OxProvider;
+    use crate::intake::oxtank::OxTank;
+
+    #[test] fn test_limited() {
+        let size = size_of::<StaticWrapper<i64>>();
+        let mut tank = OxTank::new_limited(size * 3);
+        let values = (0..3i64).map(|i| tank.allocate(i).unwrap()).collect::<Vec<_>>();
+        assert_eq!(tank.used(), size * 3);
+
+        match tank.allocate(3i64) {
+            Err(TError::OutOfMemory(e)) => {
+                assert_eq!(e.capacity, size * 3);
+                assert_eq!(e.used, size * 3);
+                assert_eq!(e.requested, size);
+            },
+            _ => panic!("expected OutOfMemory")
+        }
+        assert_eq!(tank.object_count(), 3);
+
+        tank.force_collect();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(unsafe { *value.as_ref::<i64>() }, i as i64);
+        }
+    }
+
+    #[test] fn test_payload_heap() {
+        let wrapper_size = size_of::<StaticWrapper<String>>();
+        let mut tank = OxTank::new_limited(wrapper_size + 512);
+
+        let large = String::with_capacity(1024);
+        match tank.allocate(large) {
+            Err(TError::OutOfMemory(e)) => assert_eq!(e.requested, wrapper_size + 1024),
+            _ => panic!("expected OutOfMemory")
+        }
+        assert_eq!(tank.used(), 0);
+
+        tank.allocate(String::with_capacity(256)).unwrap();
+        assert_eq!(tank.used(), wrapper_size + 256);
+
+        let strings = vec![String::with_capacity(512); 2];
+        assert!(matches!(tank.allocate(strings), Err(TError::OutOfMemory(_))));
+        assert_eq!(tank.object_count(), 1);
+    }
+
+    #[test] fn test_unlimited() {
+        let host = String::from("host");
+        {
+            let mut tank = OxTank::default();
+            for i in 0..1000i64 {
+                tank.allocate(i).unwrap();
+            }
+            let shared = unsafe { tank.allocate_ref(&host).unwrap() };
+            assert_eq!(unsafe { shared.as_ref::<String>() }, "host");
+            assert_eq!(tank.object_count(), 1001);
+            assert_eq!(tank.capacity(), None);
+        }
+        assert_eq!(host, "host");
+    }
+}
@@ -1,7 +1,8 @@
-use std::collections::HashSet;
-use std::mem::MaybeUninit;
-use crate::data::{StaticWrapper, Value};
-use crate::intake::OxProvider;
+use std::collections::{HashMap, HashSet};
+use std::mem::{MaybeUninit, size_of, size_of_val};
+use crate::data::{CONTAINER_MASK, DynBase, GcInfo, StaticWrapper, Value};
+use crate::error::TError;
+use crate::intake::{Adopt, OxProvider};
 
 pub struct IntakeOptions {
     max_debt: u32,
@@ -23,11 +24,20 @@ impl Default for IntakeOptions {
     }
 }
 
+/// 标记-清除式的垃圾回收器
+///
+/// `RampedIntake` 记录自上一次回收以来分配的对象个数和字节数（“债务”）。启用回收时，若债务超过了
+/// `IntakeOptions` 中的限制，在下一次分配之前进行一次回收。回收以所有通过 `add_stack` 注册的栈
+/// 作为根集合，注册的栈中的所有槽位都必须已经初始化，未使用的槽位可以填入 `Value::null()`。
+/// `adopt` 只记录债务而不进行回收，因此 VM 在执行期间接管对象是安全的。
+///
+/// 回收时不会回收正在共享给 Rust 的对象（`SharedToHost`, `MutSharedToHost` 和
+/// `MutReSharedToHost`）。对于从 Rust 共享过来的对象，只回收包装对象本身，不会触碰 Rust 一侧的数据。
 pub struct RampedIntake<'a> {
     options: IntakeOptions,
 
     stacks: Vec<&'a Vec<MaybeUninit<Value>>>,
-    heap: Vec<*mut Value>,
+    heap: Vec<*mut dyn DynBase>,
 
     collect_enabled: bool,
     debt: u32,
@@ -47,6 +57,95 @@ impl<'a> RampedIntake<'a> {
             debt_bytes: 0
         }
     }
+
+    /// 堆上现存的对象个数
+    pub fn object_count(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn add_object(&mut self, object: *mut dyn DynBase, size: usize) -> Value {
+        self.heap.push(object);
+        self.debt += 1;
+        self.debt_bytes += size;
+        Value::from(object)
+    }
+
+    fn maybe_collect(&mut self) {
+        if self.collect_enabled
+            && (self.debt >= self.options.max_debt || self.debt_bytes >= self.options.max_debt_bytes) {
+            self.force_collect();
+        }
+    }
+
+    /// 从根集合出发标记所有存活的对象，返回存活对象的地址
+    ///
+    /// 栈上的 `Value` 不一定是指针，因此只有与某个堆对象地址相同的槽位才会被视为对该对象的引用。
+    /// 正在共享给 Rust 的对象不会被回收，因此它们也是根集合的一部分。
+    fn mark(&self) -> HashSet<usize> {
+        let heap = self.heap.iter()
+            .map(|object| (*object as *mut () as usize, *object))
+            .collect::<HashMap<_, _>>();
+
+        let mut marked = HashSet::new();
+        let mut pending = Vec::new();
+        for stack in self.stacks.iter() {
+            for slot in stack.iter() {
+                let value = unsafe { slot.assume_init_ref() };
+                Self::mark_value(&heap, &mut marked, &mut pending, value);
+            }
+        }
+        for object in self.heap.iter() {
+            if Self::lent_to_host(*object) {
+                Self::mark_value(&heap, &mut marked, &mut pending, &Value::from(*object));
+            }
+        }
+
+        while let Some(object) = pending.pop() {
+            unsafe {
+                (*object).dyn_trace(&mut |value| {
+                    Self::mark_value(&heap, &mut marked, &mut pending, value)
+                });
+            }
+        }
+        marked
+    }
+
+    fn mark_value(
+        heap: &HashMap<usize, *mut dyn DynBase>,
+        marked: &mut HashSet<usize>,
+        pending: &mut Vec<*mut dyn DynBase>,
+        value: &Value
+    ) {
+        let addr = unsafe { value.ptr_inner.part1 } & !(CONTAINER_MASK as usize);
+        if let Some(object) = heap.get(&addr) {
+            if marked.insert(addr) {
+                pending.push(*object);
+            }
+        }
+    }
+
+    fn lent_to_host(object: *mut dyn DynBase) -> bool {
+        matches!(
+            Value::from(object).gc_info(),
+            GcInfo::SharedToHost | GcInfo::MutSharedToHost | GcInfo::MutReSharedToHost
+        )
+    }
+
+    fn sweep(&mut self, marked: &HashSet<usize>) {
+        self.heap.retain(|object| {
+            marked.contains(&(*object as *mut () as usize)) || unsafe { Self::try_reclaim(*object) }
+        });
+    }
+
+    /// 回收一个对象，对于仍然共享给 Rust 的对象，不进行回收并返回 `true`
+    unsafe fn try_reclaim(object: *mut dyn DynBase) -> bool {
+        if Self::lent_to_host(object) {
+            true
+        } else {
+            drop(Box::from_raw(object));
+            false
+        }
+    }
 }
 
 impl<'a> Default for RampedIntake<'a> {
@@ -55,21 +154,47 @@ impl<'a> Default for RampedIntake<'a> {
     }
 }
 
+impl<'a> Drop for RampedIntake<'a> {
+    fn drop(&mut self) {
+        for object in self.heap.drain(..) {
+            unsafe { Self::try_reclaim(object); }
+        }
+    }
+}
+
+impl Adopt for RampedIntake<'_> {
+    unsafe fn adopt(&mut self, value: Value) -> Result<(), TError> {
+        debug_assert!(value.is_ptr() && !value.is_null());
+        // 只记录债务，回收推迟到下一次分配
+        self.add_object(value.ptr, size_of_val(&*value.ptr));
+        Ok(())
+    }
+}
+
 impl<'a> OxProvider<'a> for RampedIntake<'a> {
-    fn allocate<T>(&mut self, _t: T) -> StaticWrapper<T> {
-        todo!()
+    fn allocate<T: 'static>(&mut self, t: T) -> Result<Value, TError> {
+        self.maybe_collect();
+        let object = Box::into_raw(Box::new(StaticWrapper::owned(t)));
+        Ok(self.add_object(object as *mut dyn DynBase, size_of::<StaticWrapper<T>>()))
     }
 
-    fn allocate_ref<T>(&mut self, _t: &T) -> StaticWrapper<T> {
-        todo!()
+    unsafe fn allocate_ref<T: 'static>(&mut self, t: &T) -> Result<Value, TError> {
+        self.maybe_collect();
+        let object = Box::into_raw(Box::new(StaticWrapper::shared(t)));
+        Ok(self.add_object(object as *mut dyn DynBase, size_of::<StaticWrapper<T>>()))
     }
 
-    fn allocate_ref_mut<T>(&mut self, _t: &mut T) -> StaticWrapper<T> {
-        todo!()
+    unsafe fn allocate_ref_mut<T: 'static>(&mut self, t: &mut T) -> Result<Value, TError> {
+        self.maybe_collect();
+        let object = Box::into_raw(Box::new(StaticWrapper::mut_shared(t)));
+        Ok(self.add_object(object as *mut dyn DynBase, size_of::<StaticWrapper<T>>()))
     }
 
     fn force_collect(&mut self) {
-        todo!()
+        let marked = self.mark();
+        self.sweep(&marked);
+        self.debt = 0;
+        self.debt_bytes = 0;
     }
 
     fn add_stack(&mut self, stack: &'a Vec<MaybeUninit<Value>>) {
@@ -84,3 +209,96 @@ impl<'a> OxProvider<'a> for RampedIntake<'a> {
         self.collect_enabled = false;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::mem::MaybeUninit;
+
+    use crate::data::{GcInfo, Value};
+    use crate::intake::OxProvider;
+    use crate::intake::ramped::{IntakeOptions, RampedIntake};
+
+    #[test] fn test_collect_unreachable() {
+        let mut stack = vec![MaybeUninit::new(Value::null()); 4];
+        let mut intake = RampedIntake::default();
+
+        let a = intake.allocate(String::from("a")).unwrap();
+        let _b = intake.allocate(String::from("b")).unwrap();
+        let c = intake.allocate(String::from("c")).unwrap();
+        stack[0] = MaybeUninit::new(a);
+        stack[1] = MaybeUninit::new(Value::from(42i64));
+        stack[3] = MaybeUninit::new(c);
+        intake.add_stack(&stack);
+
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 2);
+        unsafe {
+            assert_eq!(a.as_ref::<String>(), "a");
+            assert_eq!(c.as_ref::<String>(), "c");
+        }
+    }
+
+    #[test] fn test_keep_shared_to_host() {
+        let mut intake = RampedIntake::default();
+        let shared = intake.allocate(String::from("shared")).unwrap();
+        let mut_shared = intake.allocate(String::from("mut_shared")).unwrap();
+        unsafe {
+            shared.set_gc_info(GcInfo::SharedToHost);
+            mut_shared.set_gc_info(GcInfo::MutSharedToHost);
+        }
+
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 2);
+        unsafe {
+            assert_eq!(shared.as_ref::<String>(), "shared");
+            assert_eq!(mut_shared.as_ref::<String>(), "mut_shared");
+
+            shared.set_gc_info(GcInfo::Owned);
+            mut_shared.set_gc_info(GcInfo::Owned);
+        }
+        intake.force_collect();
+        assert_eq!(intake.object_count(), 0);
+    }
+
+    #[test] fn test_keep_host_data() {
+        let host = String::from("host");
+        let mut host_mut = vec![1i64, 2, 3];
+        {
+            let mut intake = RampedIntake::default();
+            let shared = unsafe { intake.allocate_ref(&host).unwrap() };
+            let mut_shared = unsafe { intake.allocate_ref_mut(&mut host_mut).unwrap() };
+            assert_eq!(shared.gc_info(), GcInfo::SharedFromHost);
+            assert_eq!(mut_shared.gc_info(), GcInfo::MutSharedFromHost);
+            unsafe { mut_shared.as_mut::<Vec<i64>>().push(4); }
+
+            intake.force_collect();
+            assert_eq!(intake.object_count(), 0);
+        }
+        assert_eq!(host, "host");
+        assert_eq!(host_mut, vec![1, 2, 3, 4]);
+    }
+
+    #[test] fn test_collect_on_debt() {
+        let stack = vec![MaybeUninit::new(Value::null()); 1];
+        let mut intake = RampedIntake::new(IntakeOptions::new(4, usize::MAX));
+        intake.add_stack(&stack);
+
+        for i in 0..10i64 {
+            intake.allocate(i).unwrap();
+        }
+        assert_eq!(intake.object_count(), 10);
+
+        intake.enable_collect();
+        for i in 0..10i64 {
+            intake.allocate(i).unwrap();
+            assert!(intake.object_count() <= 4);
+        }
+
+        let mut intake = RampedIntake::new(IntakeOptions::new(u32::MAX, 64));
+        intake.enable_collect();
+        for _ in 0..10 {
+            intake.allocate([0u8; 32]).unwrap();
+            assert!(intake.object_count() <= 2);
+        }
+    }
+}
@@ -4,6 +4,7 @@
 #![feature(test)]
 #![feature(core_intrinsics)]
 #![feature(option_result_unwrap_unchecked)]
+#![feature(const_type_name)]
 
 pub mod cast;
 pub mod checker;
@@ -1,45 +1,260 @@
-pub trait VMValueTyped : Copy {}
+//! `insc` 中约定了 `f110` 的 CISC 风格指令集
+//!
+//! 与 `rd93` 不同，`f110` 的运算指令的操作数可以是常量，也可以是栈上的值，并且每个操作数都带有
+//! 是否需要运行时类型检查（`tyck`）和运行时生命周期检查（`rtlc`）的标记。
 
-impl VMValueTyped for i64 {}
-impl VMValueTyped for f64 {}
-impl VMValueTyped for char {}
-impl VMValueTyped for bool {}
+use std::any::TypeId;
 
-#[derive(Copy, Clone)]
+use crate::cast::from_value::FromValue;
+use crate::data::{Value, ValueType};
+use crate::error::{NullError, TError, TypeError};
+use crate::func::RustCallable;
+use crate::turbofan::rd93::{CompiledFuncInfo, ExceptionHandler};
+use crate::turbofan::stack::StackSlice;
+use crate::tyck::fusion::ExceptionSpec;
+use crate::tyck::names;
+use crate::void::Void;
+
+pub trait VMValueTyped : Copy + 'static {
+    /// 这个类型在 `Value` 中的类型标记
+    const VALUE_TYPE: ValueType;
+
+    /// 读取值类型 `Value` 中存放的数据
+    ///
+    /// # Safety
+    /// `value` 必须是这个类型的值类型 `Value`
+    unsafe fn from_value_typed(value: &Value) -> Self;
+}
+
+impl VMValueTyped for i64 {
+    const VALUE_TYPE: ValueType = ValueType::Int;
+
+    #[inline(always)] unsafe fn from_value_typed(value: &Value) -> Self {
+        value.value_typed_data.inner.int
+    }
+}
+
+impl VMValueTyped for f64 {
+    const VALUE_TYPE: ValueType = ValueType::Float;
+
+    #[inline(always)] unsafe fn from_value_typed(value: &Value) -> Self {
+        value.value_typed_data.inner.float
+    }
+}
+
+impl VMValueTyped for char {
+    const VALUE_TYPE: ValueType = ValueType::Char;
+
+    #[inline(always)] unsafe fn from_value_typed(value: &Value) -> Self {
+        value.value_typed_data.inner.ch
+    }
+}
+
+impl VMValueTyped for bool {
+    const VALUE_TYPE: ValueType = ValueType::Bool;
+
+    #[inline(always)] unsafe fn from_value_typed(value: &Value) -> Self {
+        value.value_typed_data.inner.boolean
+    }
+}
+
+/// 操作数的来源
+#[derive(Copy, Clone, Debug)]
 pub enum OpData<T: VMValueTyped> {
+    /// 立即数
     Const(T),
+    /// 栈上的任意 `Value`，可能是值类型数据，也可能是指向堆上对象的指针
     Stack(usize),
+    /// 栈上的值类型数据
     StackValue(usize)
 }
 
-#[derive(Copy, Clone)]
+/// 运算指令的操作数
+///
+/// `tyck` 要求在读取之前检查栈上的值是否是 `T` 类型；`rtlc` 要求在读取之前检查栈上对象的
+/// `GcInfo` 是否允许读取。值类型数据总是可以读取的，所以 `rtlc` 只对 `OpData::Stack` 有意义，
+/// 两个标记对 `OpData::Const` 都没有意义。
+#[derive(Copy, Clone, Debug)]
 pub struct Operand<T: VMValueTyped> {
     tyck: bool,
     rtlc: bool,
     data: OpData<T>
 }
 
-/*
+impl<T: VMValueTyped> Operand<T> {
+    pub fn new(data: OpData<T>, tyck: bool, rtlc: bool) -> Self {
+        Self { tyck, rtlc, data }
+    }
+
+    /// 立即数操作数
+    pub fn constant(c: T) -> Self {
+        Self::new(OpData::Const(c), false, false)
+    }
+
+    /// 不需要任何运行时检查的值类型操作数
+    pub fn stack_value(idx: usize) -> Self {
+        Self::new(OpData::StackValue(idx), false, false)
+    }
+
+    pub fn tyck(&self) -> bool {
+        self.tyck
+    }
+
+    pub fn rtlc(&self) -> bool {
+        self.rtlc
+    }
+
+    pub fn data(&self) -> OpData<T> {
+        self.data
+    }
+}
+
+impl<T: VMValueTyped> Operand<T> where Void: FromValue<T> {
+    /// 从 `slice` 中读取操作数，按照 `tyck` 和 `rtlc` 标记进行运行时检查
+    ///
+    /// # Safety
+    /// 栈上的位置必须存在并且已经初始化。没有 `tyck` 标记时，栈上的值必须是 `T` 类型
+    #[inline(always)] pub unsafe fn read(&self, slice: &mut StackSlice) -> Result<T, TError> {
+        match self.data {
+            OpData::Const(c) => Ok(c),
+            OpData::StackValue(idx) => {
+                let value = slice.get_value(idx);
+                if self.tyck && value.value_type() != Some(T::VALUE_TYPE) {
+                    return Err(type_error::<T>(&value));
+                }
+                Ok(T::from_value_typed(&value))
+            },
+            OpData::Stack(idx) => {
+                let value = slice.get_value(idx);
+                if self.tyck && (value.is_null() || value.type_id() != TypeId::of::<T>()) {
+                    return Err(type_error::<T>(&value));
+                }
+                if self.rtlc {
+                    <Void as FromValue<T>>::lifetime_check(&value)?.finish();
+                }
+                Ok(<Void as FromValue<T>>::from_value(&value))
+            }
+        }
+    }
+}
+
+#[cold] unsafe fn type_error<T: 'static>(value: &Value) -> TError {
+    if value.is_null() {
+        TError::from(NullError())
+    } else {
+        names::register::<T>();
+        TError::from(TypeError::of_value(TypeId::of::<T>(), value))
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum Insc {
-    MakeIntConst{ c: i64, dest_value: usize },
-    IntAdd { lhs_value: usize, rhs_value: usize, dest_value: usize },
-    IntSub { lhs_value: usize, rhs_value: usize, dest_value: usize },
-    IntEq { lhs_value: usize, rhs_value: usize, dest_value: usize },
-    IntGt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    MakeIntConst { c: i64, dest_value: usize },
+    MakeFloatConst { c: f64, dest_value: usize },
+    MakeCharConst { c: char, dest_value: usize },
+    MakeBoolConst { c: bool, dest_value: usize },
+
+    // 运算的语义与 `rd93` 相同
+    IntAdd { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntSub { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntMul { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntDiv { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntMod { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntNeg { value: Operand<i64>, dest_value: usize },
+    IntBitAnd { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntBitOr { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntBitXor { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntBitNot { value: Operand<i64>, dest_value: usize },
+    IntShl { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntShr { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntEq { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntNe { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntLt { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntLe { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntGt { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    IntGe { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
+    /// 原地自增，`value` 必须存放整数
     Incr { value: usize },
-    JumpIfTrue { cond_value: usize, jump_dest: usize },
+
+    FloatAdd { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatSub { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatMul { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatDiv { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatNeg { value: Operand<f64>, dest_value: usize },
+    FloatEq { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatNe { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatLt { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatLe { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatGt { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+    FloatGe { lhs: Operand<f64>, rhs: Operand<f64>, dest_value: usize },
+
+    IntToFloat { value: Operand<i64>, dest_value: usize },
+    FloatToInt { value: Operand<f64>, dest_value: usize },
+
+    CharEq { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+    CharNe { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+    CharLt { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+    CharLe { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+    CharGt { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+    CharGe { lhs: Operand<char>, rhs: Operand<char>, dest_value: usize },
+
+    BoolAnd { lhs: Operand<bool>, rhs: Operand<bool>, dest_value: usize },
+    BoolOr { lhs: Operand<bool>, rhs: Operand<bool>, dest_value: usize },
+    BoolNot { value: Operand<bool>, dest_value: usize },
+
+    JumpIfTrue { cond: Operand<bool>, jump_dest: usize },
     Jump { jump_dest: usize },
     FuncCall { func_id: usize, arg_values: Vec<usize>, ret_value_locs: Vec<usize> },
+    FFICall { func_id: usize, arg_values: Vec<usize>, ret_value_locs: Vec<usize> },
     ReturnOne { ret_value: usize },
     ReturnMultiple { ret_values: Vec<usize> },
     ReturnNothing,
+    /// 将 `exception_value` 中由异常处理器捕获的异常重新抛出
+    Throw { exception_value: usize },
     UnreachableInsc
 }
-*/
 
-pub enum Insc {
-    IntAdd { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize },
-    IntSub { lhs: Operand<i64>, rhs: Operand<i64>, dest_value: usize }
+pub struct CompiledProgram {
+    pub inscs: Vec<Insc>,
+    pub funcs: Vec<CompiledFuncInfo>,
+    pub ffi_funcs: Vec<Box<dyn RustCallable>>,
+    /// 每个函数的异常处理器表，按照函数下标索引。排在前面的处理器优先匹配
+    pub exception_handlers: Vec<Vec<ExceptionHandler>>
+}
+
+impl CompiledProgram {
+    pub fn new(
+        inscs: Vec<Insc>,
+        funcs: Vec<CompiledFuncInfo>,
+        ffi_funcs: Vec<Box<dyn RustCallable>>
+    ) -> Self {
+        Self {
+            inscs,
+            funcs,
+            ffi_funcs,
+            exception_handlers: Vec::new()
+        }
+    }
+
+    /// 设置函数 `func_id` 的异常处理器表
+    pub fn set_exception_handlers(&mut self, func_id: usize, handlers: Vec<ExceptionHandler>) {
+        if self.exception_handlers.len() <= func_id {
+            self.exception_handlers.resize_with(func_id + 1, Vec::new);
+        }
+        self.exception_handlers[func_id] = handlers;
+    }
+
+    /// 查找函数 `func_id` 中能够处理 `insc_ptr` 处抛出的 `exception` 的异常处理器
+    pub fn find_exception_handler(
+        &self,
+        func_id: usize,
+        insc_ptr: usize,
+        exception: ExceptionSpec
+    ) -> Option<&ExceptionHandler> {
+        self.exception_handlers.get(func_id)?
+            .iter()
+            .find(|handler| handler.matches(insc_ptr, exception))
+    }
 }
 
 #[cfg(test)]
@@ -1,2 +1,365 @@
 //! 与 `rd93` 相对，`f110` 实现了一个 CISC 风格的虚拟机指令集，用于对比 benchmarking
 pub mod insc;
+
+use std::any::TypeId;
+use std::mem::MaybeUninit;
+
+use crate::data::Value;
+use crate::error::{TError, VmError};
+use crate::func::{adopt_rets, VmContext};
+use crate::intake::Adopt;
+use crate::turbofan::rd93::{check_callback, thrown_error, unwind, CompiledFuncInfo};
+use crate::turbofan::stack::{Stack, StackSlice};
+
+pub use insc::{CompiledProgram, Insc, OpData, Operand, VMValueTyped};
+
+/// `F110` 提供给宿主函数的 `VmContext`
+struct F110Context<'a> {
+    stack: &'a mut Stack,
+    program: &'a CompiledProgram,
+    intake: &'a mut dyn Adopt
+}
+
+impl VmContext for F110Context<'_> {
+    fn call_func(&mut self, func_id: usize, args: &[Value]) -> Result<Vec<Value>, TError> {
+        let func_info = check_callback(&self.program.funcs, func_id, args)?;
+        let mut outputs = vec![MaybeUninit::uninit(); func_info.ret_count];
+        unsafe {
+            F110::run_func_with_stack(
+                self.stack, self.program, func_id, args, &mut outputs, &mut *self.intake
+            )
+                .map_err(|e| *e.error)?;
+            Ok(outputs.into_iter().map(|output| output.assume_init()).collect())
+        }
+    }
+}
+
+pub struct F110 ();
+
+impl F110 {
+    /// 执行程序中的函数 `func_id`，将返回值写入 `outputs`。宿主函数返回的新对象和被捕获的异常
+    /// 对象由 `intake` 接管，参见 `RD93::run_func`
+    ///
+    /// # Safety
+    ///
+    /// `f110` 没有校验器，调用者需要保证程序本身是良构的：栈位置、跳转目标和函数下标都不越界，
+    /// 调用时参数、返回值的个数与函数一致，读取的栈位置都已经初始化，并且没有 `tyck` 标记的操作数
+    /// 总是读到正确类型的值，传给宿主函数的参数类型也必须正确。`args` 和 `outputs` 的长度必须与
+    /// 函数的参数、返回值个数一致。宿主函数回调 VM 时，只检查函数下标和参数个数，参数类型同样需要
+    /// 由程序保证
+    pub unsafe fn run_func(
+        program: &CompiledProgram,
+        func_id: usize,
+        args: &[Value],
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        Self::run_func_with_stack(&mut Stack::new(), program, func_id, args, outputs, intake)
+    }
+
+    /// 与 `run_func` 相同，但是使用调用者提供的 `stack`，以便在多次调用之间复用栈空间
+    ///
+    /// `stack` 上已有的栈帧不受影响，因此宿主函数也可以在 VM 正在使用的栈上发起嵌套调用
+    ///
+    /// # Safety
+    ///
+    /// 同 `run_func`
+    pub unsafe fn run_func_with_stack(
+        stack: &mut Stack,
+        program: &CompiledProgram,
+        func_id: usize,
+        args: &[Value],
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        #[cfg(not(debug_assertions))]
+        let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(func_id);
+        #[cfg(debug_assertions)]
+        let func_info: CompiledFuncInfo = program.funcs[func_id];
+
+        let dummy_ret_locs = [];
+        debug_assert_eq!(args.len(), func_info.arg_count);
+        debug_assert_eq!(outputs.len(), func_info.ret_count);
+
+        let cur_stack_slice = match stack.ext_func_call_grow_stack(
+            func_id,
+            func_info.stack_size,
+            args,
+            &dummy_ret_locs
+        ) {
+            Ok(stack_slice) => stack_slice,
+            Err(e) => return Err(VmError::new(TError::from(e), func_info.start_addr, vec![]))
+        };
+        let result =
+            Self::exec(stack, program, cur_stack_slice, func_info.start_addr, outputs, intake);
+        stack.ext_func_call_shrink_stack();
+        result
+    }
+
+    /// 解释器的主循环。调用者已经在 `stack` 上创建了最外层的栈帧，从 `insc_ptr` 开始执行
+    unsafe fn exec(
+        stack: &mut Stack,
+        program: &CompiledProgram,
+        mut cur_stack_slice: StackSlice,
+        mut insc_ptr: usize,
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        let mut ffi_args = Vec::with_capacity(8);
+        let mut ffi_rets = Vec::with_capacity(3);
+
+        // 抛出异常，跳转到异常处理器，或者从 `run_func` 返回错误
+        macro_rules! raise {
+            ($error:expr) => {{
+                let (stack_slice, catch_addr) = unwind(
+                    |func_id, insc_ptr, exception|
+                        program.find_exception_handler(func_id, insc_ptr, exception),
+                    stack, cur_stack_slice, insc_ptr, $error, &mut *intake
+                )?;
+                cur_stack_slice = stack_slice;
+                insc_ptr = catch_addr;
+                continue;
+            }};
+        }
+
+        // 读取操作数，运行时检查失败时抛出异常
+        macro_rules! read {
+            ($operand:expr) => {
+                match $operand.read(&mut cur_stack_slice) {
+                    Ok(value) => value,
+                    Err(e) => raise!(e)
+                }
+            };
+        }
+
+        // 读取两个操作数，进行二元运算后将结果写入 `dest`
+        macro_rules! binary_op {
+            ($lhs:expr, $rhs:expr, $dest:expr, |$l:ident, $r:ident| $op:expr) => {{
+                let $l = read!($lhs);
+                let $r = read!($rhs);
+                cur_stack_slice.set_value($dest, Value::from($op));
+            }};
+        }
+
+        // 读取一个操作数，进行一元运算后将结果写入 `dest`
+        macro_rules! unary_op {
+            ($value:expr, $dest:expr, |$v:ident| $op:expr) => {{
+                let $v = read!($value);
+                cur_stack_slice.set_value($dest, Value::from($op));
+            }};
+        }
+
+        loop {
+            match program.inscs.get_unchecked(insc_ptr) {
+                Insc::MakeIntConst { c, dest_value } =>
+                    cur_stack_slice.set_value(*dest_value, Value::from(*c)),
+                Insc::MakeFloatConst { c, dest_value } =>
+                    cur_stack_slice.set_value(*dest_value, Value::from(*c)),
+                Insc::MakeCharConst { c, dest_value } =>
+                    cur_stack_slice.set_value(*dest_value, Value::from(*c)),
+                Insc::MakeBoolConst { c, dest_value } =>
+                    cur_stack_slice.set_value(*dest_value, Value::from(*c)),
+                Insc::IntAdd { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l.wrapping_add(r)),
+                Insc::IntSub { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l.wrapping_sub(r)),
+                Insc::IntMul { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l.wrapping_mul(r)),
+                Insc::IntDiv { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| {
+                        if r == 0 {
+                            raise!(TError::unchecked_exception("division by zero"));
+                        }
+                        l.wrapping_div(r)
+                    }),
+                Insc::IntMod { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| {
+                        if r == 0 {
+                            raise!(TError::unchecked_exception("division by zero"));
+                        }
+                        l.wrapping_rem(r)
+                    }),
+                Insc::IntNeg { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| v.wrapping_neg()),
+                Insc::IntBitAnd { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l & r),
+                Insc::IntBitOr { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l | r),
+                Insc::IntBitXor { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l ^ r),
+                Insc::IntBitNot { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| !v),
+                Insc::IntShl { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l.wrapping_shl(r as u32)),
+                Insc::IntShr { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l.wrapping_shr(r as u32)),
+                Insc::IntEq { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l == r),
+                Insc::IntNe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l != r),
+                Insc::IntLt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l < r),
+                Insc::IntLe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l <= r),
+                Insc::IntGt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l > r),
+                Insc::IntGe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l >= r),
+                Insc::Incr { value } => {
+                    let v = cur_stack_slice.get_value(*value);
+                    debug_assert_eq!(v.type_id(), TypeId::of::<i64>());
+                    let i = v.value_typed_data.inner.int;
+                    cur_stack_slice.set_value(*value, Value::from(i.wrapping_add(1)))
+                },
+                Insc::FloatAdd { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l + r),
+                Insc::FloatSub { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l - r),
+                Insc::FloatMul { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l * r),
+                Insc::FloatDiv { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l / r),
+                Insc::FloatNeg { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| -v),
+                Insc::FloatEq { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l == r),
+                Insc::FloatNe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l != r),
+                Insc::FloatLt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l < r),
+                Insc::FloatLe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l <= r),
+                Insc::FloatGt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l > r),
+                Insc::FloatGe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l >= r),
+                Insc::IntToFloat { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| v as f64),
+                Insc::FloatToInt { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| v as i64),
+                Insc::CharEq { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l == r),
+                Insc::CharNe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l != r),
+                Insc::CharLt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l < r),
+                Insc::CharLe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l <= r),
+                Insc::CharGt { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l > r),
+                Insc::CharGe { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l >= r),
+                Insc::BoolAnd { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l && r),
+                Insc::BoolOr { lhs, rhs, dest_value } =>
+                    binary_op!(lhs, rhs, *dest_value, |l, r| l || r),
+                Insc::BoolNot { value, dest_value } =>
+                    unary_op!(value, *dest_value, |v| !v),
+                Insc::JumpIfTrue { cond, jump_dest } => {
+                    if read!(cond) {
+                        insc_ptr = *jump_dest;
+                        continue;
+                    }
+                },
+                Insc::Jump { jump_dest } => {
+                    insc_ptr = *jump_dest;
+                    continue;
+                },
+                Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
+                    #[cfg(not(debug_assertions))]
+                    let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(*func_id);
+                    #[cfg(debug_assertions)]
+                    let func_info: CompiledFuncInfo = program.funcs[*func_id];
+                    debug_assert_eq!(func_info.arg_count, arg_values.len());
+
+                    cur_stack_slice = match stack.func_call_grow_stack(
+                        *func_id,
+                        func_info.stack_size,
+                        arg_values,
+                        ret_value_locs,
+                        insc_ptr + 1
+                    ) {
+                        Ok(stack_slice) => stack_slice,
+                        Err(e) => raise!(TError::from(e))
+                    };
+                    insc_ptr = func_info.start_addr;
+                    continue;
+                },
+                Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+                    #[cfg(not(debug_assertions))]
+                    let ffi_func = program.ffi_funcs.get_unchecked(*func_id);
+                    #[cfg(debug_assertions)]
+                    let ffi_func = &program.ffi_funcs[*func_id];
+
+                    for arg_value in arg_values {
+                        ffi_args.push(cur_stack_slice.get_value(*arg_value));
+                    }
+                    for ret_value_loc in ret_value_locs {
+                        ffi_rets.push(cur_stack_slice.get_value_mut(*ret_value_loc));
+                    }
+
+                    let mut vm = F110Context { stack: &mut *stack, program, intake: &mut *intake };
+                    let result = ffi_func.call_with_vm(&mut vm, &ffi_args, &mut ffi_rets[..]);
+                    let result =
+                        result.and_then(|_| adopt_rets(&**ffi_func, &mut ffi_rets, intake));
+                    ffi_args.clear();
+                    ffi_rets.clear();
+                    if let Err(e) = result {
+                        raise!(e);
+                    }
+                },
+                Insc::ReturnMultiple { ret_values } => {
+                    if let Some((prev_stack_slice, ret_addr)) =
+                        stack.done_func_call_shrink_stack(ret_values)
+                    {
+                        insc_ptr = ret_addr;
+                        cur_stack_slice = prev_stack_slice;
+                        continue;
+                    } else {
+                        for (i, ret_value_loc) in ret_values.iter().enumerate() {
+                            outputs.get_unchecked_mut(i)
+                                .write(cur_stack_slice.get_value(*ret_value_loc));
+                        }
+                        return Ok(());
+                    }
+                },
+                Insc::ReturnOne { ret_value } => {
+                    if let Some((prev_stack_slice, ret_addr)) =
+                        stack.done_func_call_shrink_stack1(*ret_value)
+                    {
+                        insc_ptr = ret_addr;
+                        cur_stack_slice = prev_stack_slice;
+                        continue;
+                    } else {
+                        debug_assert_eq!(outputs.len(), 1);
+                        outputs.get_unchecked_mut(0).write(cur_stack_slice.get_value(*ret_value));
+                        return Ok(());
+                    }
+                },
+                Insc::ReturnNothing => {
+                    if let Some((prev_stack_slice, ret_addr)) =
+                        stack.done_func_call_shrink_stack(&[])
+                    {
+                        insc_ptr = ret_addr;
+                        cur_stack_slice = prev_stack_slice;
+                        continue;
+                    } else {
+                        debug_assert_eq!(outputs.len(), 0);
+                        return Ok(());
+                    }
+                },
+                Insc::Throw { exception_value } => {
+                    let value = cur_stack_slice.get_value(*exception_value);
+                    raise!(thrown_error(&value));
+                },
+                Insc::UnreachableInsc => return Err(VmError::new(
+                    TError::unchecked_exception("this is an internal, unreachable insc"),
+                    insc_ptr,
+                    stack.ret_addrs()
+                ))
+            }
+
+            insc_ptr += 1;
+        }
+    }
+}
@@ -0,0 +1,1029 @@
+//! `asm` 模块实现了 `rd93` 程序的文本汇编格式，以及对应的汇编器和反汇编器
+//!
+//! 汇编程序按行书写，`;` 之后的内容是注释。每一行可以以 `label:` 开头定义一个标号，标号的作用域
+//! 是它所在的函数。程序由以下几种语句组成：
+//!
+//! ```text
+//! ffi math::add(int, int) -> (int)      ; 按名字导入宿主函数，类型可以是 int/float/char/bool/any
+//!
+//! func fib(1) -> 1, stack 4             ; 定义函数：参数个数、返回值个数和栈帧大小
+//!     handler *, try, try_end, catch -> %3  ; 异常处理器：异常类型（* 表示所有异常）和三个标号
+//!     make_int_const 2 -> %1
+//!     int_lt %0, %1 -> %2
+//!     jump_if_true %2, done
+//! try:
+//!     func_call fib(%0) -> %0
+//!     ffi_call math::add(%0, %1) -> %0
+//! try_end:
+//! done:
+//!     return_one %0
+//! catch:
+//!     throw %3
+//! ```
+//!
+//! 指令的助记符是 `Insc` 中对应变体名的 snake_case 形式，先写输入，再在 `->` 之后写输出。函数按照
+//! 定义的顺序编号，函数调用可以引用后面才定义的函数。异常类型的名字通过
+//! `FunctionRegistry::register_exception` 注册，`TError` 内置的异常可以直接使用 `TypeError`、
+//! `NullError`、`ArgLenError` 和 `LifetimeError`。
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::data::ValueType;
+use crate::error::{AsmError, builtin_exception_name, builtin_exception_type};
+use crate::func::registry::{FFIImport, FunctionRegistry};
+use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc, TypeCheck};
+use crate::tyck::TypeCheckInfo;
+use crate::tyck::fusion::ExceptionSpec;
+
+/// 定义三个操作数的指令与助记符之间的对应关系
+macro_rules! binary_inscs {
+    ($($variant:ident => $mnemonic:literal),* $(,)?) => {
+        fn make_binary_insc(mnemonic: &str, lhs_value: usize, rhs_value: usize, dest_value: usize)
+            -> Option<Insc>
+        {
+            match mnemonic {
+                $($mnemonic => Some(Insc::$variant { lhs_value, rhs_value, dest_value }),)*
+                _ => None
+            }
+        }
+
+        fn binary_insc_parts(insc: &Insc) -> Option<(&'static str, usize, usize, usize)> {
+            match insc {
+                $(Insc::$variant { lhs_value, rhs_value, dest_value } =>
+                    Some(($mnemonic, *lhs_value, *rhs_value, *dest_value)),)*
+                _ => None
+            }
+        }
+    };
+}
+
+/// 定义两个操作数的指令与助记符之间的对应关系
+macro_rules! unary_inscs {
+    ($($variant:ident => $mnemonic:literal),* $(,)?) => {
+        fn make_unary_insc(mnemonic: &str, value: usize, dest_value: usize) -> Option<Insc> {
+            match mnemonic {
+                $($mnemonic => Some(Insc::$variant { value, dest_value }),)*
+                _ => None
+            }
+        }
+
+        fn unary_insc_parts(insc: &Insc) -> Option<(&'static str, usize, usize)> {
+            match insc {
+                $(Insc::$variant { value, dest_value } => Some(($mnemonic, *value, *dest_value)),)*
+                _ => None
+            }
+        }
+    };
+}
+
+binary_inscs! {
+    IntAdd => "int_add", IntSub => "int_sub", IntMul => "int_mul", IntDiv => "int_div",
+    IntMod => "int_mod", IntBitAnd => "int_bit_and", IntBitOr => "int_bit_or",
+    IntBitXor => "int_bit_xor", IntShl => "int_shl", IntShr => "int_shr",
+    IntEq => "int_eq", IntNe => "int_ne", IntLt => "int_lt", IntLe => "int_le",
+    IntGt => "int_gt", IntGe => "int_ge",
+    FloatAdd => "float_add", FloatSub => "float_sub", FloatMul => "float_mul",
+    FloatDiv => "float_div", FloatEq => "float_eq", FloatNe => "float_ne", FloatLt => "float_lt",
+    FloatLe => "float_le", FloatGt => "float_gt", FloatGe => "float_ge",
+    CharEq => "char_eq", CharNe => "char_ne", CharLt => "char_lt", CharLe => "char_le",
+    CharGt => "char_gt", CharGe => "char_ge",
+    BoolAnd => "bool_and", BoolOr => "bool_or"
+}
+
+unary_inscs! {
+    IntNeg => "int_neg", IntBitNot => "int_bit_not", FloatNeg => "float_neg",
+    IntToFloat => "int_to_float", FloatToInt => "float_to_int", BoolNot => "bool_not"
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Slot(usize),
+    Number(String),
+    Char(char),
+    Punct(&'static str)
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// 将一行源代码切分为记号，每个记号带有它的列号
+fn lex_line(line: &str, line_no: usize) -> Result<Vec<(Token, usize)>, AsmError> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ';' {
+            break;
+        } else if c == '%' {
+            i += 1;
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let slot = chars[start..i].iter().collect::<String>().parse::<usize>()
+                .map_err(|_| AsmError::new(line_no, column, "expected stack slot number"))?;
+            tokens.push((Token::Slot(slot), column));
+        } else if c == '-' && chars.get(i + 1) == Some(&'>') {
+            tokens.push((Token::Punct("->"), column));
+            i += 2;
+        } else if c.is_ascii_digit() || c == '-' {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (is_ident_continue(chars[i])
+                    || chars[i] == '.'
+                    || ((chars[i] == '+' || chars[i] == '-') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            tokens.push((Token::Number(chars[start..i].iter().collect()), column));
+        } else if is_ident_start(c) {
+            let start = i;
+            loop {
+                while i < chars.len() && is_ident_continue(chars[i]) {
+                    i += 1;
+                }
+                if chars.get(i) == Some(&':')
+                    && chars.get(i + 1) == Some(&':')
+                    && chars.get(i + 2).is_some_and(|c| is_ident_start(*c))
+                {
+                    i += 2;
+                } else {
+                    break;
+                }
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+        } else if c == '\'' {
+            let (ch, end) = lex_char(&chars, i + 1)
+                .ok_or_else(|| AsmError::new(line_no, column, "invalid character literal"))?;
+            tokens.push((Token::Char(ch), column));
+            i = end;
+        } else {
+            let punct = match c {
+                ',' => ",",
+                '(' => "(",
+                ')' => ")",
+                ':' => ":",
+                '*' => "*",
+                _ => return Err(AsmError::new(line_no, column,
+                                              format!("unexpected character {:?}", c)))
+            };
+            tokens.push((Token::Punct(punct), column));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// 从 `chars[start]` 开始解析字符字面量的内容和结尾的 `'`，返回字符和字面量之后的位置
+fn lex_char(chars: &[char], start: usize) -> Option<(char, usize)> {
+    let (ch, end) = match *chars.get(start)? {
+        '\\' => match *chars.get(start + 1)? {
+            'n' => ('\n', start + 2),
+            'r' => ('\r', start + 2),
+            't' => ('\t', start + 2),
+            '0' => ('\0', start + 2),
+            '\\' => ('\\', start + 2),
+            '\'' => ('\'', start + 2),
+            '"' => ('"', start + 2),
+            'u' => {
+                if *chars.get(start + 2)? != '{' {
+                    return None;
+                }
+                let close = start + 3 + chars[start + 3..].iter().position(|c| *c == '}')?;
+                let code = chars[start + 3..close].iter().collect::<String>();
+                (char::from_u32(u32::from_str_radix(&code, 16).ok()?)?, close + 1)
+            },
+            _ => return None
+        },
+        '\'' => return None,
+        c => (c, start + 1)
+    };
+    if *chars.get(end)? == '\'' {
+        Some((ch, end + 1))
+    } else {
+        None
+    }
+}
+
+/// 一行记号的解析器
+struct LineParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    line: usize,
+    line_len: usize
+}
+
+impl LineParser {
+    fn error(&self, message: impl ToString) -> AsmError {
+        AsmError::new(self.line, self.column(), message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.line_len + 1, |(_, column)| *column)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<(), AsmError> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", punct)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, AsmError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            },
+            _ => Err(self.error("expected identifier"))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), AsmError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident == keyword => {
+                self.pos += 1;
+                Ok(())
+            },
+            _ => Err(self.error(format!("expected `{}`", keyword)))
+        }
+    }
+
+    fn expect_slot(&mut self) -> Result<usize, AsmError> {
+        match self.peek() {
+            Some(Token::Slot(slot)) => {
+                let slot = *slot;
+                self.pos += 1;
+                Ok(slot)
+            },
+            _ => Err(self.error("expected stack slot"))
+        }
+    }
+
+    fn expect_usize(&mut self) -> Result<usize, AsmError> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                let number = number.parse::<usize>().map_err(|_| self.error("invalid number"))?;
+                self.pos += 1;
+                Ok(number)
+            },
+            _ => Err(self.error("expected number"))
+        }
+    }
+
+    /// 解析由逗号分隔、可能为空的栈位置列表
+    fn slot_list(&mut self) -> Result<Vec<usize>, AsmError> {
+        let mut slots = Vec::new();
+        if !matches!(self.peek(), Some(Token::Slot(_))) {
+            return Ok(slots);
+        }
+        loop {
+            slots.push(self.expect_slot()?);
+            if !self.eat_punct(",") {
+                return Ok(slots);
+            }
+        }
+    }
+
+    /// 解析 `(a, b, ...)` 形式的列表
+    fn paren_list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, AsmError>)
+        -> Result<Vec<T>, AsmError>
+    {
+        self.expect_punct("(")?;
+        let mut items = Vec::new();
+        if self.eat_punct(")") {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat_punct(")") {
+                return Ok(items);
+            }
+            self.expect_punct(",")?;
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), AsmError> {
+        if self.pos == self.tokens.len() {
+            Ok(())
+        } else {
+            Err(self.error("unexpected token"))
+        }
+    }
+}
+
+/// 需要在之后回填的名字引用
+struct Fixup {
+    insc_idx: usize,
+    name: String,
+    line: usize,
+    column: usize
+}
+
+/// 需要在函数结束时回填标号的异常处理器
+struct PendingHandler {
+    exception: ExceptionSpec,
+    labels: [(String, usize, usize); 3],
+    exception_value: usize
+}
+
+struct Assembler<'a> {
+    registry: &'a FunctionRegistry,
+    inscs: Vec<Insc>,
+    funcs: Vec<CompiledFuncInfo>,
+    func_names: HashMap<String, usize>,
+    ffi_imports: Vec<FFIImport>,
+    ffi_names: HashMap<String, usize>,
+    exception_handlers: Vec<Vec<ExceptionHandler>>,
+    func_fixups: Vec<Fixup>,
+    ffi_fixups: Vec<Fixup>,
+
+    // 当前函数的状态
+    in_func: bool,
+    labels: HashMap<String, usize>,
+    label_fixups: Vec<Fixup>,
+    pending_handlers: Vec<PendingHandler>
+}
+
+/// 汇编 `source`，使用 `registry` 解析其中的 `ffi` 导入
+pub fn assemble(source: &str, registry: &FunctionRegistry) -> Result<CompiledProgram, AsmError> {
+    let mut assembler = Assembler {
+        registry,
+        inscs: Vec::new(),
+        funcs: Vec::new(),
+        func_names: HashMap::new(),
+        ffi_imports: Vec::new(),
+        ffi_names: HashMap::new(),
+        exception_handlers: Vec::new(),
+        func_fixups: Vec::new(),
+        ffi_fixups: Vec::new(),
+        in_func: false,
+        labels: HashMap::new(),
+        label_fixups: Vec::new(),
+        pending_handlers: Vec::new()
+    };
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_no = line_idx + 1;
+        let mut parser = LineParser {
+            tokens: lex_line(line, line_no)?,
+            pos: 0,
+            line: line_no,
+            line_len: line.chars().count()
+        };
+        assembler.assemble_line(&mut parser)?;
+    }
+    assembler.finish_func()?;
+    assembler.finish()
+}
+
+impl<'a> Assembler<'a> {
+    fn assemble_line(&mut self, parser: &mut LineParser) -> Result<(), AsmError> {
+        if let (Some(Token::Ident(label)), Some((Token::Punct(":"), _))) =
+            (parser.peek(), parser.tokens.get(1))
+        {
+            if !self.in_func {
+                return Err(parser.error("label outside of function"));
+            }
+            let label = label.clone();
+            if self.labels.insert(label.clone(), self.inscs.len()).is_some() {
+                return Err(parser.error(format!("duplicate label `{}`", label)));
+            }
+            parser.pos += 2;
+        }
+
+        let column = parser.column();
+        let keyword = match parser.peek() {
+            None => return Ok(()),
+            Some(Token::Ident(keyword)) => keyword.clone(),
+            Some(_) => return Err(parser.error("expected instruction or directive"))
+        };
+        parser.pos += 1;
+
+        match keyword.as_str() {
+            "ffi" => self.ffi_directive(parser, column)?,
+            "func" => self.func_directive(parser, column)?,
+            "handler" => self.handler_directive(parser)?,
+            mnemonic => {
+                if !self.in_func {
+                    return Err(AsmError::new(parser.line, column,
+                                             "instruction outside of function"));
+                }
+                let insc = self.parse_insc(parser, mnemonic, column)?;
+                self.inscs.push(insc);
+            }
+        }
+        parser.expect_end()
+    }
+
+    fn ffi_directive(&mut self, parser: &mut LineParser, column: usize) -> Result<(), AsmError> {
+        let name = parser.expect_ident()?;
+        let params = parser.paren_list(parse_type)?;
+        parser.expect_punct("->")?;
+        let rets = parser.paren_list(parse_type)?;
+
+        if self.ffi_names.contains_key(&name) {
+            return Err(AsmError::new(parser.line, column,
+                                     format!("duplicate ffi import `{}`", name)));
+        }
+        let import = FFIImport::new(&name, params, rets);
+        self.registry.link_import(&import)
+            .map_err(|e| AsmError::new(parser.line, column, e))?;
+        self.ffi_names.insert(name, self.ffi_imports.len());
+        self.ffi_imports.push(import);
+        Ok(())
+    }
+
+    fn func_directive(&mut self, parser: &mut LineParser, column: usize) -> Result<(), AsmError> {
+        self.finish_func()?;
+
+        let name = parser.expect_ident()?;
+        parser.expect_punct("(")?;
+        let arg_count = parser.expect_usize()?;
+        parser.expect_punct(")")?;
+        parser.expect_punct("->")?;
+        let ret_count = parser.expect_usize()?;
+        parser.expect_punct(",")?;
+        parser.expect_keyword("stack")?;
+        let stack_size = parser.expect_usize()?;
+
+        if self.func_names.contains_key(&name) {
+            return Err(AsmError::new(parser.line, column,
+                                     format!("duplicate function `{}`", name)));
+        }
+        self.func_names.insert(name, self.funcs.len());
+        self.funcs.push(CompiledFuncInfo::new(self.inscs.len(), arg_count, ret_count, stack_size));
+        self.in_func = true;
+        Ok(())
+    }
+
+    fn handler_directive(&mut self, parser: &mut LineParser) -> Result<(), AsmError> {
+        if !self.in_func {
+            return Err(parser.error("handler outside of function"));
+        }
+
+        let exception = if parser.eat_punct("*") {
+            None
+        } else {
+            let name = parser.expect_ident()?;
+            let type_id = self.registry.exception_type(&name)
+                .or_else(|| builtin_exception_type(&name))
+                .ok_or_else(|| {
+                    parser.pos -= 1;
+                    parser.error(format!("unknown exception type `{}`", name))
+                })?;
+            Some(type_id)
+        };
+
+        let label = |parser: &mut LineParser| -> Result<(String, usize, usize), AsmError> {
+            parser.expect_punct(",")?;
+            let column = parser.column();
+            Ok((parser.expect_ident()?, parser.line, column))
+        };
+        let labels = [label(parser)?, label(parser)?, label(parser)?];
+        parser.expect_punct("->")?;
+        let exception_value = parser.expect_slot()?;
+
+        self.pending_handlers.push(PendingHandler { exception, labels, exception_value });
+        Ok(())
+    }
+
+    fn parse_insc(&mut self, parser: &mut LineParser, mnemonic: &str, column: usize)
+        -> Result<Insc, AsmError>
+    {
+        if let Some(insc) = parse_const_insc(parser, mnemonic)? {
+            return Ok(insc);
+        }
+
+        if make_binary_insc(mnemonic, 0, 0, 0).is_some() {
+            let lhs_value = parser.expect_slot()?;
+            parser.expect_punct(",")?;
+            let rhs_value = parser.expect_slot()?;
+            parser.expect_punct("->")?;
+            let dest_value = parser.expect_slot()?;
+            return Ok(make_binary_insc(mnemonic, lhs_value, rhs_value, dest_value).unwrap());
+        }
+
+        if make_unary_insc(mnemonic, 0, 0).is_some() {
+            let value = parser.expect_slot()?;
+            parser.expect_punct("->")?;
+            let dest_value = parser.expect_slot()?;
+            return Ok(make_unary_insc(mnemonic, value, dest_value).unwrap());
+        }
+
+        let insc = match mnemonic {
+            "incr" => Insc::Incr { value: parser.expect_slot()? },
+            "jump_if_true" => {
+                let cond_value = parser.expect_slot()?;
+                parser.expect_punct(",")?;
+                self.add_label_fixup(parser)?;
+                Insc::JumpIfTrue { cond_value, jump_dest: 0 }
+            },
+            "jump" => {
+                self.add_label_fixup(parser)?;
+                Insc::Jump { jump_dest: 0 }
+            },
+            "tail_call" => {
+                let name_column = parser.column();
+                let name = parser.expect_ident()?;
+                let arg_values = parser.paren_list(LineParser::expect_slot)?;
+                self.func_fixups.push(Fixup {
+                    insc_idx: self.inscs.len(),
+                    name,
+                    line: parser.line,
+                    column: name_column
+                });
+                Insc::TailCall { func_id: 0, arg_values }
+            },
+            "func_call" | "ffi_call" => {
+                let name_column = parser.column();
+                let name = parser.expect_ident()?;
+                let arg_values = parser.paren_list(LineParser::expect_slot)?;
+                let ret_value_locs = if parser.eat_punct("->") {
+                    parser.slot_list()?
+                } else {
+                    Vec::new()
+                };
+
+                let fixup = Fixup {
+                    insc_idx: self.inscs.len(),
+                    name,
+                    line: parser.line,
+                    column: name_column
+                };
+                if mnemonic == "func_call" {
+                    self.func_fixups.push(fixup);
+                    Insc::FuncCall { func_id: 0, arg_values, ret_value_locs }
+                } else {
+                    self.ffi_fixups.push(fixup);
+                    Insc::FFICall { func_id: 0, arg_values, ret_value_locs }
+                }
+            },
+            "return_one" => Insc::ReturnOne { ret_value: parser.expect_slot()? },
+            "return_multiple" => Insc::ReturnMultiple { ret_values: parser.slot_list()? },
+            "return_nothing" => Insc::ReturnNothing,
+            "throw" => Insc::Throw { exception_value: parser.expect_slot()? },
+            "unreachable_insc" => Insc::UnreachableInsc,
+            _ => return Err(AsmError::new(parser.line, column,
+                                          format!("unknown instruction `{}`", mnemonic)))
+        };
+        Ok(insc)
+    }
+
+    fn add_label_fixup(&mut self, parser: &mut LineParser) -> Result<(), AsmError> {
+        let column = parser.column();
+        let name = parser.expect_ident()?;
+        self.label_fixups.push(Fixup { insc_idx: self.inscs.len(), name, line: parser.line, column });
+        Ok(())
+    }
+
+    /// 结束当前函数，回填函数内的标号
+    fn finish_func(&mut self) -> Result<(), AsmError> {
+        if !self.in_func {
+            return Ok(());
+        }
+
+        let labels = std::mem::take(&mut self.labels);
+        let resolve = |(name, line, column): &(String, usize, usize)| {
+            labels.get(name).copied().ok_or_else(|| {
+                AsmError::new(*line, *column, format!("undefined label `{}`", name))
+            })
+        };
+
+        for fixup in std::mem::take(&mut self.label_fixups) {
+            let addr = resolve(&(fixup.name, fixup.line, fixup.column))?;
+            match &mut self.inscs[fixup.insc_idx] {
+                Insc::JumpIfTrue { jump_dest, .. } | Insc::Jump { jump_dest } => *jump_dest = addr,
+                _ => unreachable!()
+            }
+        }
+
+        let mut handlers = Vec::new();
+        for handler in std::mem::take(&mut self.pending_handlers) {
+            handlers.push(ExceptionHandler::new(
+                resolve(&handler.labels[0])?,
+                resolve(&handler.labels[1])?,
+                handler.exception,
+                resolve(&handler.labels[2])?,
+                handler.exception_value
+            ));
+        }
+        if !handlers.is_empty() {
+            let func_id = self.funcs.len() - 1;
+            self.exception_handlers.resize_with(func_id + 1, Vec::new);
+            self.exception_handlers[func_id] = handlers;
+        }
+
+        self.in_func = false;
+        Ok(())
+    }
+
+    /// 回填函数调用，然后链接宿主函数
+    fn finish(mut self) -> Result<CompiledProgram, AsmError> {
+        for fixup in self.func_fixups.iter().chain(&self.ffi_fixups) {
+            let (names, kind) = match &self.inscs[fixup.insc_idx] {
+                Insc::FuncCall { .. } | Insc::TailCall { .. } => (&self.func_names, "function"),
+                _ => (&self.ffi_names, "ffi import")
+            };
+            let id = *names.get(&fixup.name).ok_or_else(|| {
+                AsmError::new(fixup.line, fixup.column,
+                              format!("undefined {} `{}`", kind, fixup.name))
+            })?;
+            match &mut self.inscs[fixup.insc_idx] {
+                Insc::FuncCall { func_id, .. }
+                | Insc::TailCall { func_id, .. }
+                | Insc::FFICall { func_id, .. } => *func_id = id,
+                _ => unreachable!()
+            }
+        }
+
+        // 所有导入在解析时都已经检查过，这里不会失败
+        let mut program = CompiledProgram::link(self.inscs, self.funcs, self.ffi_imports,
+                                                self.registry)
+            .map_err(|e| AsmError::new(1, 1, e))?;
+        program.exception_handlers = self.exception_handlers;
+        Ok(program)
+    }
+}
+
+fn parse_type(parser: &mut LineParser) -> Result<TypeCheckInfo, AsmError> {
+    let tyck_info = match parser.peek() {
+        Some(Token::Ident(name)) => match name.as_str() {
+            "int" => TypeCheckInfo::SimpleType(TypeId::of::<i64>()),
+            "float" => TypeCheckInfo::SimpleType(TypeId::of::<f64>()),
+            "char" => TypeCheckInfo::SimpleType(TypeId::of::<char>()),
+            "bool" => TypeCheckInfo::SimpleType(TypeId::of::<bool>()),
+            "any" => TypeCheckInfo::Bypass,
+            _ => return Err(parser.error(format!("unknown type `{}`", name)))
+        },
+        _ => return Err(parser.error("expected type"))
+    };
+    parser.pos += 1;
+    Ok(tyck_info)
+}
+
+fn parse_const_insc(parser: &mut LineParser, mnemonic: &str) -> Result<Option<Insc>, AsmError> {
+    let token = parser.peek().cloned();
+    let invalid = |parser: &LineParser| parser.error(format!("invalid constant for `{}`", mnemonic));
+    let make_insc: Box<dyn FnOnce(usize) -> Insc> = match (mnemonic, token) {
+        ("make_int_const", Some(Token::Number(number))) => {
+            let c = number.parse::<i64>().map_err(|_| invalid(parser))?;
+            Box::new(move |dest_value| Insc::MakeIntConst { c, dest_value })
+        },
+        ("make_float_const", Some(Token::Number(number) | Token::Ident(number))) => {
+            let c = number.parse::<f64>().map_err(|_| invalid(parser))?;
+            Box::new(move |dest_value| Insc::MakeFloatConst { c, dest_value })
+        },
+        ("make_char_const", Some(Token::Char(c))) =>
+            Box::new(move |dest_value| Insc::MakeCharConst { c, dest_value }),
+        ("make_bool_const", Some(Token::Ident(ident))) => {
+            let c = match ident.as_str() {
+                "true" => true,
+                "false" => false,
+                _ => return Err(invalid(parser))
+            };
+            Box::new(move |dest_value| Insc::MakeBoolConst { c, dest_value })
+        },
+        ("make_int_const" | "make_float_const" | "make_char_const" | "make_bool_const", _) =>
+            return Err(invalid(parser)),
+        _ => return Ok(None)
+    };
+    parser.pos += 1;
+    parser.expect_punct("->")?;
+    Ok(Some(make_insc(parser.expect_slot()?)))
+}
+
+fn type_name(tyck_info: &TypeCheckInfo) -> &'static str {
+    if let TypeCheckInfo::SimpleType(type_id) = tyck_info {
+        match ValueType::from_type_id(*type_id) {
+            Some(ValueType::Int) => return "int",
+            Some(ValueType::Float) => return "float",
+            Some(ValueType::Char) => return "char",
+            Some(ValueType::Bool) => return "bool",
+            _ => {}
+        }
+    }
+    "any"
+}
+
+fn slot_list(slots: &[usize]) -> String {
+    slots.iter().map(|slot| format!("%{}", slot)).collect::<Vec<_>>().join(", ")
+}
+
+/// 将 `program` 打印为汇编格式，`registry` 用来查找异常类型的名字
+///
+/// 函数被命名为 `f0`、`f1` 等，标号被命名为 `L` 加上指令地址。由 `CompiledProgram::new` 创建的
+/// 程序没有导入声明，此时宿主函数被命名为 `ffi0`、`ffi1` 等，类型根据 `param_specs()` 生成，
+/// 无法表示为值类型的类型打印为 `any`。
+///
+/// 对于函数编号随起始地址递增、每条指令都属于某个函数的程序（包括所有由 `assemble` 生成的程序），
+/// 再次汇编输出的文本会得到相同的程序。
+///
+/// 异常处理器所处理的异常类型既不是内置异常，也没有在 `registry` 中注册时，这个异常类型无法用
+/// 文本表示，返回的 `AsmError` 指向输出文本中该处理器所在的行。
+pub fn disassemble(program: &CompiledProgram, registry: &FunctionRegistry)
+    -> Result<String, AsmError>
+{
+    let mut output = String::new();
+
+    if program.ffi_imports.len() == program.ffi_funcs.len() {
+        for import in program.ffi_imports.iter() {
+            let params = import.param_tyck_info.iter().map(type_name).collect::<Vec<_>>();
+            let rets = import.ret_tyck_info.iter().map(type_name).collect::<Vec<_>>();
+            writeln!(output, "ffi {}({}) -> ({})", import.name, params.join(", "), rets.join(", "))
+                .unwrap();
+        }
+    } else {
+        for (idx, ffi_func) in program.ffi_funcs.iter().enumerate() {
+            let params = ffi_func.param_specs().iter()
+                .map(|(tyck_info, _, _)| type_name(tyck_info))
+                .collect::<Vec<_>>();
+            let rets = ffi_func.return_value_spec().iter()
+                .map(|(tyck_info, _, _)| type_name(tyck_info))
+                .collect::<Vec<_>>();
+            writeln!(output, "ffi ffi{}({}) -> ({})", idx, params.join(", "), rets.join(", "))
+                .unwrap();
+        }
+    }
+    let ffi_name = |func_id: usize| match program.ffi_imports.get(func_id) {
+        Some(import) if program.ffi_imports.len() == program.ffi_funcs.len() => import.name.clone(),
+        _ => format!("ffi{}", func_id)
+    };
+
+    let mut label_addrs = Vec::new();
+    for insc in program.inscs.iter() {
+        if let Insc::JumpIfTrue { jump_dest, .. } | Insc::Jump { jump_dest } = insc {
+            label_addrs.push(*jump_dest);
+        }
+    }
+    for handler in program.exception_handlers.iter().flatten() {
+        label_addrs.extend([handler.try_start, handler.try_end, handler.catch_addr]);
+    }
+    label_addrs.sort_unstable();
+    label_addrs.dedup();
+
+    let mut func_ids = (0..program.funcs.len()).collect::<Vec<_>>();
+    func_ids.sort_by_key(|func_id| program.funcs[*func_id].start_addr);
+
+    for (idx, func_id) in func_ids.iter().enumerate() {
+        let func_info = program.funcs[*func_id];
+        let end = func_ids.get(idx + 1)
+            .map_or(program.inscs.len(), |next| program.funcs[*next].start_addr);
+
+        writeln!(output).unwrap();
+        writeln!(output, "func f{}({}) -> {}, stack {}",
+                 func_id, func_info.arg_count, func_info.ret_count, func_info.stack_size).unwrap();
+        for handler in program.exception_handlers.get(*func_id).into_iter().flatten() {
+            let exception = match handler.exception {
+                None => "*".to_string(),
+                Some(type_id) => registry.exception_name(type_id)
+                    .or_else(|| builtin_exception_name(type_id))
+                    .ok_or_else(|| AsmError::new(output.lines().count() + 1, 5, format!(
+                        "exception type {:?} of a handler in f{} is not registered", type_id, func_id
+                    )))?
+                    .to_string()
+            };
+            writeln!(output, "    handler {}, L{}, L{}, L{} -> %{}",
+                     exception, handler.try_start, handler.try_end, handler.catch_addr,
+                     handler.exception_value).unwrap();
+        }
+
+        for addr in func_info.start_addr..end {
+            if label_addrs.binary_search(&addr).is_ok() {
+                writeln!(output, "L{}:", addr).unwrap();
+            }
+            writeln!(output, "    {}", disassemble_insc(&program.inscs[addr], &ffi_name)).unwrap();
+        }
+        if idx + 1 == func_ids.len() && label_addrs.binary_search(&end).is_ok() {
+            writeln!(output, "L{}:", end).unwrap();
+        }
+    }
+
+    Ok(output)
+}
+
+fn disassemble_insc(insc: &Insc, ffi_name: &dyn Fn(usize) -> String) -> String {
+    if let Some((mnemonic, lhs_value, rhs_value, dest_value)) = binary_insc_parts(insc) {
+        return format!("{} %{}, %{} -> %{}", mnemonic, lhs_value, rhs_value, dest_value);
+    }
+    if let Some((mnemonic, value, dest_value)) = unary_insc_parts(insc) {
+        return format!("{} %{} -> %{}", mnemonic, value, dest_value);
+    }
+
+    let call = |mnemonic: &str, name: String, arg_values: &[usize], ret_value_locs: &[usize]| {
+        if ret_value_locs.is_empty() {
+            format!("{} {}({})", mnemonic, name, slot_list(arg_values))
+        } else {
+            format!("{} {}({}) -> {}", mnemonic, name, slot_list(arg_values),
+                    slot_list(ret_value_locs))
+        }
+    };
+
+    match insc {
+        Insc::MakeIntConst { c, dest_value } => format!("make_int_const {} -> %{}", c, dest_value),
+        Insc::MakeFloatConst { c, dest_value } =>
+            format!("make_float_const {:?} -> %{}", c, dest_value),
+        Insc::MakeCharConst { c, dest_value } =>
+            format!("make_char_const {:?} -> %{}", c, dest_value),
+        Insc::MakeBoolConst { c, dest_value } => format!("make_bool_const {} -> %{}", c, dest_value),
+        Insc::Incr { value } => format!("incr %{}", value),
+        Insc::JumpIfTrue { cond_value, jump_dest } =>
+            format!("jump_if_true %{}, L{}", cond_value, jump_dest),
+        Insc::Jump { jump_dest } => format!("jump L{}", jump_dest),
+        Insc::FuncCall { func_id, arg_values, ret_value_locs } =>
+            call("func_call", format!("f{}", func_id), arg_values, ret_value_locs),
+        Insc::TailCall { func_id, arg_values } =>
+            call("tail_call", format!("f{}", func_id), arg_values, &[]),
+        Insc::FFICall { func_id, arg_values, ret_value_locs } =>
+            call("ffi_call", ffi_name(*func_id), arg_values, ret_value_locs),
+        Insc::ReturnOne { ret_value } => format!("return_one %{}", ret_value),
+        Insc::ReturnMultiple { ret_values } =>
+            format!("return_multiple {}", slot_list(ret_values)).trim_end().to_string(),
+        Insc::ReturnNothing => "return_nothing".to_string(),
+        Insc::Throw { exception_value } => format!("throw %{}", exception_value),
+        Insc::UnreachableInsc => "unreachable_insc".to_string(),
+        Insc::TypeChecked { checks, insc } => {
+            let checks = checks.iter()
+                .map(|(slot, check)| match check {
+                    TypeCheck::Value(value_type) => format!("%{}: {:?}", slot, value_type),
+                    TypeCheck::Host { type_name, .. } => format!("%{}: {}", slot, type_name)
+                })
+                .collect::<Vec<_>>();
+            format!("{} ; checked {}", disassemble_insc(insc, ffi_name), checks.join(", "))
+        },
+        _ => unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::marker::PhantomData;
+
+    use crate::error::AsmError;
+    use crate::func::RustFunction;
+    use crate::func::registry::FunctionRegistry;
+    use crate::turbofan::rd93::asm::{assemble, disassemble};
+    use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc};
+
+    fn add(x: i64, y: i64) -> i64 {
+        x + y
+    }
+
+    fn registry() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry.register("math::add", RustFunction { f: add, _phantom: PhantomData }).unwrap();
+        registry.register_exception::<std::fmt::Error>("fmt::Error");
+        registry
+    }
+
+    const SOURCE: &str = "\
+ffi math::add(int, int) -> (int)
+
+; 测试所有的语法
+func main(2) -> 2, stack 6
+    handler fmt::Error, try, try_end, catch -> %5
+    handler *, try, try_end, catch -> %5
+    make_int_const -42 -> %2
+    make_float_const 1.5e3 -> %3
+    make_float_const NaN -> %3
+    make_char_const '\\u{301}' -> %4
+    make_char_const '\\'' -> %4
+    make_bool_const true -> %4
+try:
+    int_add %0, %1 -> %2      ; 注释
+    int_neg %2 -> %2
+    incr %2
+    ffi_call math::add(%0, %1) -> %2
+    func_call helper(%2)
+    jump_if_true %4, done
+try_end:
+    jump try
+done:
+    return_multiple %0, %1
+catch:
+    throw %5
+
+func helper(1) -> 0, stack 1
+    return_nothing
+";
+
+    #[test] fn test_assemble() {
+        let registry = registry();
+        let program = assemble(SOURCE, &registry).unwrap();
+        assert_eq!(program.funcs.len(), 2);
+        assert_eq!(program.funcs[1].start_addr, 15);
+        assert_eq!(program.ffi_imports.len(), 1);
+        assert!(matches!(program.inscs[6], Insc::IntAdd { lhs_value: 0, rhs_value: 1, dest_value: 2 }));
+        assert!(matches!(program.inscs[4], Insc::MakeCharConst { c: '\'', dest_value: 4 }));
+        assert!(matches!(program.inscs[10], Insc::FuncCall { func_id: 1, .. }));
+        assert!(matches!(program.inscs[11], Insc::JumpIfTrue { cond_value: 4, jump_dest: 13 }));
+        assert!(matches!(program.inscs[12], Insc::Jump { jump_dest: 6 }));
+
+        let handlers = &program.exception_handlers[0];
+        assert_eq!(handlers.len(), 2);
+        assert_eq!((handlers[0].try_start, handlers[0].try_end, handlers[0].catch_addr), (6, 12, 14));
+        assert_eq!(handlers[0].exception, Some(TypeId::of::<std::fmt::Error>()));
+        assert_eq!(handlers[1].exception, None);
+    }
+
+    #[test] fn test_disassemble_unregistered_exception() {
+        let program = assemble(SOURCE, &registry()).unwrap();
+        let mut partial = FunctionRegistry::new();
+        partial.register("math::add", RustFunction { f: add, _phantom: PhantomData }).unwrap();
+        let err = disassemble(&program, &partial).err().unwrap();
+        assert!(err.message.contains("is not registered"), "{}", err);
+        let text = disassemble(&program, &registry());
+        assert!(text.unwrap().lines().nth(err.line - 1).unwrap().starts_with("    handler "));
+    }
+
+    fn round_trip(program: &CompiledProgram, registry: &FunctionRegistry) {
+        let text = disassemble(program, registry).unwrap();
+        let reassembled = assemble(&text, registry).unwrap();
+        assert_eq!(format!("{:?}", program.inscs), format!("{:?}", reassembled.inscs));
+        assert_eq!(format!("{:?}", program.funcs), format!("{:?}", reassembled.funcs));
+        assert_eq!(format!("{:?}", program.exception_handlers),
+                   format!("{:?}", reassembled.exception_handlers));
+        assert_eq!(text, disassemble(&reassembled, registry).unwrap());
+    }
+
+    #[test] fn test_round_trip() {
+        let registry = registry();
+        round_trip(&assemble(SOURCE, &registry).unwrap(), &registry);
+
+        let mut program = CompiledProgram::new(vec![
+            Insc::MakeFloatConst { c: -0.0, dest_value: 0 },
+            Insc::MakeCharConst { c: '\n', dest_value: 1 },
+            Insc::MakeIntConst { c: i64::MIN, dest_value: 1 },
+            Insc::FFICall { func_id: 0, arg_values: vec![1, 1], ret_value_locs: vec![1] },
+            Insc::ReturnMultiple { ret_values: vec![] }
+        ], vec![
+            CompiledFuncInfo::new(0, 0, 0, 2)
+        ], vec![
+            Box::new(RustFunction { f: add, _phantom: PhantomData })
+        ]);
+        program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(3, 5, 4, 0)]);
+
+        let mut registry = FunctionRegistry::new();
+        registry.register("ffi0", RustFunction { f: add, _phantom: PhantomData }).unwrap();
+        round_trip(&program, &registry);
+    }
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, &registry()).err().unwrap()
+    }
+
+    #[test] fn test_errors() {
+        let e = error("func f(0) -> 0, stack 1\n    int_add %0, %1 %2\n");
+        assert_eq!((e.line, e.column), (2, 20));
+
+        let e = error("func f(0) -> 0, stack 1\n    jump nowhere\n");
+        assert_eq!((e.line, e.column), (2, 10));
+
+        let e = error("func f(0) -> 0, stack 1\n    func_call g()\n");
+        assert_eq!((e.line, e.column), (2, 15));
+
+        let e = error("    return_nothing\n");
+        assert_eq!((e.line, e.column), (1, 5));
+
+        let e = error("func f(0) -> 0, stack 1\n    frobnicate %0\n");
+        assert_eq!((e.line, e.column), (2, 5));
+
+        let e = error("ffi math::add(int) -> (int)\n");
+        assert_eq!((e.line, e.column), (1, 1));
+
+        let e = error("func f(0) -> 0, stack 1\n    make_char_const 'ab' -> %0\n");
+        assert_eq!((e.line, e.column), (2, 21));
+
+        let e = error("func f(0) -> 0, stack 1\n    handler io::Error, a, a, a -> %0\n");
+        assert_eq!((e.line, e.column), (2, 13));
+    }
+}
@@ -0,0 +1,652 @@
+//! `flat` 中实现了 `rd93` 的另一个执行后端
+//!
+//! `Insc` 是大小不一的枚举，`FuncCall`、`FFICall` 和 `ReturnMultiple` 还带有 `Vec`，这使得整个
+//! `Insc` 的尺寸较大，指令序列的缓存局部性也较差。`FlatProgram` 将校验过的程序预先编译为定长的
+//! `FlatInsc` 序列：
+//!
+//! - 每条指令由一个字节的操作码和三个 `u32` 操作数组成，共 16 字节
+//! - 常量统一存放在常量池中，由 `LoadConst` 指令读取
+//! - 参数、返回值列表存放在操作数表中，指令中只记录列表在表中的位置
+//! - 需要运行时类型检查的指令被替换为 `TypeChecked`，检查列表和原指令存放在单独的表中
+//!
+//! 编译时指令与 `FlatInsc` 一一对应，所以跳转目标、异常处理器和 `VmError` 中的指令地址与原程序
+//! 完全一致，两个后端可以共享同一套异常处理逻辑。
+
+use std::any::TypeId;
+use std::convert::TryFrom;
+use std::mem::MaybeUninit;
+
+use crate::data::{Value, ValueType};
+use crate::error::{FlatError, TError, VmError};
+use crate::func::{adopt_rets, VmContext};
+use crate::intake::Adopt;
+use crate::turbofan::rd93::{check_callback, check_type, check_value_type, RD93};
+use crate::turbofan::rd93::{thrown_error, unwind};
+use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, Insc, TypeCheck};
+use crate::turbofan::rd93::verify::VerifiedProgram;
+use crate::turbofan::stack::{Stack, StackSlice};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+enum FlatOp {
+    LoadConst,
+
+    IntAdd, IntSub, IntMul, IntDiv, IntMod, IntNeg,
+    IntBitAnd, IntBitOr, IntBitXor, IntBitNot, IntShl, IntShr,
+    IntEq, IntNe, IntLt, IntLe, IntGt, IntGe,
+    Incr,
+
+    FloatAdd, FloatSub, FloatMul, FloatDiv, FloatNeg,
+    FloatEq, FloatNe, FloatLt, FloatLe, FloatGt, FloatGe,
+
+    IntToFloat, FloatToInt,
+
+    CharEq, CharNe, CharLt, CharLe, CharGt, CharGe,
+
+    BoolAnd, BoolOr, BoolNot,
+
+    JumpIfTrue, Jump, FuncCall, TailCall, FFICall,
+    ReturnOne, ReturnMultiple, ReturnNothing,
+    Throw, UnreachableInsc,
+
+    TypeChecked
+}
+
+/// 定长的指令
+///
+/// 操作数的含义取决于操作码：
+///
+/// - 二元运算：`a`、`b` 为操作数，`c` 为结果
+/// - 一元运算：`a` 为操作数，`b` 为结果
+/// - `LoadConst`：`a` 为常量池下标，`b` 为结果
+/// - `FuncCall`、`FFICall`：`a` 为函数下标，`b`、`c` 分别为参数列表、返回值列表在操作数表中的位置
+/// - `TailCall`：`a` 为函数下标，`b` 为参数列表在操作数表中的位置
+/// - `ReturnMultiple`：`a` 为返回值列表在操作数表中的位置
+/// - `TypeChecked`：`a` 为 `checked_inscs` 下标
+/// - 其余指令：`a`、`b` 依次对应 `Insc` 中的各个字段
+#[derive(Copy, Clone, Debug)]
+struct FlatInsc {
+    op: FlatOp,
+    a: u32,
+    b: u32,
+    c: u32
+}
+
+/// 将 `Insc` 中的下标转换为 `FlatInsc` 的操作数，超出 `u32` 范围时返回原值
+fn operand(value: usize) -> Result<u32, usize> {
+    u32::try_from(value).map_err(|_| value)
+}
+
+impl FlatInsc {
+    fn new(op: FlatOp, a: usize, b: usize, c: usize) -> Result<Self, usize> {
+        Ok(Self {
+            op,
+            a: operand(a)?,
+            b: operand(b)?,
+            c: operand(c)?
+        })
+    }
+}
+
+/// 需要运行时类型检查的指令，以及检查列表
+struct CheckedInsc {
+    checks: Box<[(usize, TypeCheck)]>,
+    insc: FlatInsc
+}
+
+/// 预先编译为定长指令的程序
+pub struct FlatProgram<'a> {
+    program: &'a CompiledProgram,
+    inscs: Vec<FlatInsc>,
+    consts: Vec<Value>,
+    /// 参数、返回值列表，每个列表之前存放列表的长度
+    operands: Vec<usize>,
+    checked_inscs: Vec<CheckedInsc>,
+    param_types: Vec<Vec<Option<ValueType>>>
+}
+
+macro_rules! flatten_binary {
+    ($insc:expr; $($variant:ident),* $(,)?) => {
+        match $insc {
+            $(Insc::$variant { lhs_value, rhs_value, dest_value } =>
+                return FlatInsc::new(FlatOp::$variant, *lhs_value, *rhs_value, *dest_value),)*
+            _ => {}
+        }
+    };
+}
+
+macro_rules! flatten_unary {
+    ($insc:expr; $($variant:ident),* $(,)?) => {
+        match $insc {
+            $(Insc::$variant { value, dest_value } =>
+                return FlatInsc::new(FlatOp::$variant, *value, *dest_value, 0),)*
+            _ => {}
+        }
+    };
+}
+
+impl<'a> FlatProgram<'a> {
+    /// 编译校验过的程序。栈位置、函数下标等超出 `u32` 范围时返回 `FlatError`
+    pub fn new(verified: &VerifiedProgram<'a>) -> Result<Self, FlatError> {
+        let program = verified.program();
+        let mut flat = Self {
+            program,
+            inscs: Vec::with_capacity(verified.inscs().len()),
+            consts: Vec::new(),
+            operands: Vec::new(),
+            checked_inscs: Vec::new(),
+            param_types: (0..program.funcs.len())
+                .map(|func_id| verified.param_types(func_id).to_vec())
+                .collect()
+        };
+
+        for (insc_ptr, insc) in verified.inscs().iter().enumerate() {
+            let flat_insc = if let Insc::TypeChecked { checks, insc } = insc {
+                flat.flatten(insc).and_then(|insc| {
+                    flat.checked_inscs.push(CheckedInsc { checks: checks.clone(), insc });
+                    FlatInsc::new(FlatOp::TypeChecked, flat.checked_inscs.len() - 1, 0, 0)
+                })
+            } else {
+                flat.flatten(insc)
+            };
+            let flat_insc = flat_insc.map_err(|operand| FlatError { insc_ptr, operand })?;
+            flat.inscs.push(flat_insc);
+        }
+        Ok(flat)
+    }
+
+    pub fn program(&self) -> &'a CompiledProgram {
+        self.program
+    }
+
+    fn push_const(&mut self, value: Value) -> usize {
+        self.consts.push(value);
+        self.consts.len() - 1
+    }
+
+    fn push_operands(&mut self, operands: &[usize]) -> usize {
+        let offset = self.operands.len();
+        self.operands.push(operands.len());
+        self.operands.extend_from_slice(operands);
+        offset
+    }
+
+    /// 将一条指令转换为定长指令，操作数超出 `u32` 范围时返回该操作数
+    fn flatten(&mut self, insc: &Insc) -> Result<FlatInsc, usize> {
+        flatten_binary!(insc;
+            IntAdd, IntSub, IntMul, IntDiv, IntMod, IntBitAnd, IntBitOr, IntBitXor, IntShl, IntShr,
+            IntEq, IntNe, IntLt, IntLe, IntGt, IntGe,
+            FloatAdd, FloatSub, FloatMul, FloatDiv, FloatEq, FloatNe, FloatLt, FloatLe, FloatGt,
+            FloatGe,
+            CharEq, CharNe, CharLt, CharLe, CharGt, CharGe,
+            BoolAnd, BoolOr
+        );
+        flatten_unary!(insc; IntNeg, IntBitNot, FloatNeg, IntToFloat, FloatToInt, BoolNot);
+
+        match insc {
+            Insc::MakeIntConst { c, dest_value } => {
+                let idx = self.push_const(Value::from(*c));
+                FlatInsc::new(FlatOp::LoadConst, idx, *dest_value, 0)
+            },
+            Insc::MakeFloatConst { c, dest_value } => {
+                let idx = self.push_const(Value::from(*c));
+                FlatInsc::new(FlatOp::LoadConst, idx, *dest_value, 0)
+            },
+            Insc::MakeCharConst { c, dest_value } => {
+                let idx = self.push_const(Value::from(*c));
+                FlatInsc::new(FlatOp::LoadConst, idx, *dest_value, 0)
+            },
+            Insc::MakeBoolConst { c, dest_value } => {
+                let idx = self.push_const(Value::from(*c));
+                FlatInsc::new(FlatOp::LoadConst, idx, *dest_value, 0)
+            },
+            Insc::Incr { value } => FlatInsc::new(FlatOp::Incr, *value, 0, 0),
+            Insc::JumpIfTrue { cond_value, jump_dest } =>
+                FlatInsc::new(FlatOp::JumpIfTrue, *cond_value, *jump_dest, 0),
+            Insc::Jump { jump_dest } => FlatInsc::new(FlatOp::Jump, *jump_dest, 0, 0),
+            Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
+                let args = self.push_operands(arg_values);
+                let rets = self.push_operands(ret_value_locs);
+                FlatInsc::new(FlatOp::FuncCall, *func_id, args, rets)
+            },
+            Insc::TailCall { func_id, arg_values } => {
+                let args = self.push_operands(arg_values);
+                FlatInsc::new(FlatOp::TailCall, *func_id, args, 0)
+            },
+            Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+                let args = self.push_operands(arg_values);
+                let rets = self.push_operands(ret_value_locs);
+                FlatInsc::new(FlatOp::FFICall, *func_id, args, rets)
+            },
+            Insc::ReturnOne { ret_value } => FlatInsc::new(FlatOp::ReturnOne, *ret_value, 0, 0),
+            Insc::ReturnMultiple { ret_values } => {
+                let rets = self.push_operands(ret_values);
+                FlatInsc::new(FlatOp::ReturnMultiple, rets, 0, 0)
+            },
+            Insc::ReturnNothing => FlatInsc::new(FlatOp::ReturnNothing, 0, 0, 0),
+            Insc::Throw { exception_value } =>
+                FlatInsc::new(FlatOp::Throw, *exception_value, 0, 0),
+            Insc::UnreachableInsc => FlatInsc::new(FlatOp::UnreachableInsc, 0, 0, 0),
+            Insc::TypeChecked { .. } => unreachable!("nested TypeChecked insc"),
+            _ => unreachable!()
+        }
+    }
+}
+
+/// 读取操作数表中位于 `offset` 处的列表
+#[inline(always)] unsafe fn operand_list(operands: &[usize], offset: u32) -> &[usize] {
+    let offset = offset as usize;
+    let len = *operands.get_unchecked(offset);
+    operands.get_unchecked(offset + 1..offset + 1 + len)
+}
+
+/// 读取两个操作数并检查类型，进行二元运算后将结果写入 `c`
+macro_rules! flat_binary_op {
+    ($slice:ident, $insc:ident, $ty:ty, $field:ident, |$lhs:ident, $rhs:ident| $op:expr) => {{
+        let $lhs = $slice.get_value($insc.a as usize);
+        let $rhs = $slice.get_value($insc.b as usize);
+        debug_assert_eq!($lhs.type_id(), TypeId::of::<$ty>());
+        debug_assert_eq!($rhs.type_id(), TypeId::of::<$ty>());
+        let $lhs = $lhs.value_typed_data.inner.$field;
+        let $rhs = $rhs.value_typed_data.inner.$field;
+        $slice.set_value($insc.c as usize, Value::from($op));
+    }};
+}
+
+/// 读取一个操作数并检查类型，进行一元运算后将结果写入 `b`
+macro_rules! flat_unary_op {
+    ($slice:ident, $insc:ident, $ty:ty, $field:ident, |$v:ident| $op:expr) => {{
+        let $v = $slice.get_value($insc.a as usize);
+        debug_assert_eq!($v.type_id(), TypeId::of::<$ty>());
+        let $v = $v.value_typed_data.inner.$field;
+        $slice.set_value($insc.b as usize, Value::from($op));
+    }};
+}
+
+/// 定长指令后端提供给宿主函数的 `VmContext`
+struct FlatContext<'a, 'p> {
+    stack: &'a mut Stack,
+    flat: &'a FlatProgram<'p>,
+    intake: &'a mut dyn Adopt
+}
+
+impl VmContext for FlatContext<'_, '_> {
+    fn call_func(&mut self, func_id: usize, args: &[Value]) -> Result<Vec<Value>, TError> {
+        let func_info = check_callback(&self.flat.program.funcs, func_id, args)?;
+        let mut outputs = vec![MaybeUninit::uninit(); func_info.ret_count];
+        unsafe {
+            RD93::run_flat_func_with_stack(
+                self.stack, self.flat, func_id, args, &mut outputs, &mut *self.intake
+            )
+                .map_err(|e| *e.error)?;
+            Ok(outputs.into_iter().map(|output| output.assume_init()).collect())
+        }
+    }
+}
+
+impl RD93 {
+    /// 使用定长指令后端执行程序中的函数 `func_id`，将返回值写入 `outputs`。语义与 `run_func`
+    /// 完全一致
+    ///
+    /// # Safety
+    ///
+    /// 与 `run_func` 相同
+    pub unsafe fn run_flat_func(
+        flat: &FlatProgram,
+        func_id: usize,
+        args: &[Value],
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        Self::run_flat_func_with_stack(&mut Stack::new(), flat, func_id, args, outputs, intake)
+    }
+
+    /// 与 `run_flat_func` 相同，但是使用调用者提供的 `stack`
+    ///
+    /// # Safety
+    ///
+    /// 与 `run_func` 相同
+    pub unsafe fn run_flat_func_with_stack(
+        stack: &mut Stack,
+        flat: &FlatProgram,
+        func_id: usize,
+        args: &[Value],
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        let program = flat.program;
+
+        #[cfg(not(debug_assertions))]
+        let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(func_id);
+        #[cfg(debug_assertions)]
+        let func_info: CompiledFuncInfo = program.funcs[func_id];
+
+        let dummy_ret_locs = [];
+        debug_assert_eq!(args.len(), func_info.arg_count);
+        debug_assert_eq!(outputs.len(), func_info.ret_count);
+
+        for (arg, param_type) in args.iter().zip(&flat.param_types[func_id]) {
+            if let Some(param_type) = param_type {
+                if let Err(e) = check_value_type(arg, *param_type) {
+                    return Err(VmError::new(e, func_info.start_addr, vec![]));
+                }
+            }
+        }
+
+        let cur_stack_slice = match stack.ext_func_call_grow_stack(
+            func_id,
+            func_info.stack_size,
+            args,
+            &dummy_ret_locs
+        ) {
+            Ok(stack_slice) => stack_slice,
+            Err(e) => return Err(VmError::new(TError::from(e), func_info.start_addr, vec![]))
+        };
+        let result =
+            Self::exec_flat(stack, flat, cur_stack_slice, func_info.start_addr, outputs, intake);
+        stack.ext_func_call_shrink_stack();
+        result
+    }
+
+    /// 定长指令后端的主循环，与 `exec` 对应
+    unsafe fn exec_flat(
+        stack: &mut Stack,
+        flat: &FlatProgram,
+        mut cur_stack_slice: StackSlice,
+        mut insc_ptr: usize,
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        let program = flat.program;
+        let inscs = &flat.inscs[..];
+        let consts = &flat.consts[..];
+        let operands = &flat.operands[..];
+        let mut ffi_args = Vec::with_capacity(8);
+        let mut ffi_rets = Vec::with_capacity(3);
+
+        // 抛出异常，跳转到异常处理器，或者从 `run_flat_func` 返回错误
+        macro_rules! raise {
+            ($label:lifetime, $error:expr) => {{
+                let (stack_slice, catch_addr) = unwind(
+                    |func_id, insc_ptr, exception|
+                        program.find_exception_handler(func_id, insc_ptr, exception),
+                    stack, cur_stack_slice, insc_ptr, $error, &mut *intake
+                )?;
+                cur_stack_slice = stack_slice;
+                insc_ptr = catch_addr;
+                continue $label;
+            }};
+        }
+
+        'exec: loop {
+            let mut insc: &FlatInsc = inscs.get_unchecked(insc_ptr);
+            // `TypeChecked` 检查通过之后，回到这里执行被包装的指令
+            'dispatch: loop {
+                match insc.op {
+                    FlatOp::LoadConst => {
+                        let value = *consts.get_unchecked(insc.a as usize);
+                        cur_stack_slice.set_value(insc.b as usize, value);
+                    },
+                    FlatOp::IntAdd =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int,
+                                        |lhs, rhs| lhs.wrapping_add(rhs)),
+                    FlatOp::IntSub =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int,
+                                        |lhs, rhs| lhs.wrapping_sub(rhs)),
+                    FlatOp::IntMul =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int,
+                                        |lhs, rhs| lhs.wrapping_mul(rhs)),
+                    FlatOp::IntDiv =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| {
+                            if rhs == 0 {
+                                raise!('exec, TError::unchecked_exception("division by zero"));
+                            }
+                            lhs.wrapping_div(rhs)
+                        }),
+                    FlatOp::IntMod =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| {
+                            if rhs == 0 {
+                                raise!('exec, TError::unchecked_exception("division by zero"));
+                            }
+                            lhs.wrapping_rem(rhs)
+                        }),
+                    FlatOp::IntNeg =>
+                        flat_unary_op!(cur_stack_slice, insc, i64, int, |v| v.wrapping_neg()),
+                    FlatOp::IntBitAnd =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs & rhs),
+                    FlatOp::IntBitOr =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs | rhs),
+                    FlatOp::IntBitXor =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs ^ rhs),
+                    FlatOp::IntBitNot =>
+                        flat_unary_op!(cur_stack_slice, insc, i64, int, |v| !v),
+                    FlatOp::IntShl =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int,
+                                        |lhs, rhs| lhs.wrapping_shl(rhs as u32)),
+                    FlatOp::IntShr =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int,
+                                        |lhs, rhs| lhs.wrapping_shr(rhs as u32)),
+                    FlatOp::IntEq =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs == rhs),
+                    FlatOp::IntNe =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs != rhs),
+                    FlatOp::IntLt =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs < rhs),
+                    FlatOp::IntLe =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs <= rhs),
+                    FlatOp::IntGt =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs > rhs),
+                    FlatOp::IntGe =>
+                        flat_binary_op!(cur_stack_slice, insc, i64, int, |lhs, rhs| lhs >= rhs),
+                    FlatOp::Incr => {
+                        let v = cur_stack_slice.get_value(insc.a as usize);
+                        debug_assert_eq!(v.type_id(), TypeId::of::<i64>());
+                        let i = v.value_typed_data.inner.int;
+                        cur_stack_slice.set_value(insc.a as usize, Value::from(i.wrapping_add(1)))
+                    },
+                    FlatOp::FloatAdd =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs + rhs),
+                    FlatOp::FloatSub =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs - rhs),
+                    FlatOp::FloatMul =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs * rhs),
+                    FlatOp::FloatDiv =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs / rhs),
+                    FlatOp::FloatNeg =>
+                        flat_unary_op!(cur_stack_slice, insc, f64, float, |v| -v),
+                    FlatOp::FloatEq =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs == rhs),
+                    FlatOp::FloatNe =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs != rhs),
+                    FlatOp::FloatLt =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs < rhs),
+                    FlatOp::FloatLe =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs <= rhs),
+                    FlatOp::FloatGt =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs > rhs),
+                    FlatOp::FloatGe =>
+                        flat_binary_op!(cur_stack_slice, insc, f64, float, |lhs, rhs| lhs >= rhs),
+                    FlatOp::IntToFloat =>
+                        flat_unary_op!(cur_stack_slice, insc, i64, int, |v| v as f64),
+                    FlatOp::FloatToInt =>
+                        flat_unary_op!(cur_stack_slice, insc, f64, float, |v| v as i64),
+                    FlatOp::CharEq =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs == rhs),
+                    FlatOp::CharNe =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs != rhs),
+                    FlatOp::CharLt =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs < rhs),
+                    FlatOp::CharLe =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs <= rhs),
+                    FlatOp::CharGt =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs > rhs),
+                    FlatOp::CharGe =>
+                        flat_binary_op!(cur_stack_slice, insc, char, ch, |lhs, rhs| lhs >= rhs),
+                    FlatOp::BoolAnd =>
+                        flat_binary_op!(cur_stack_slice, insc, bool, boolean,
+                                        |lhs, rhs| lhs && rhs),
+                    FlatOp::BoolOr =>
+                        flat_binary_op!(cur_stack_slice, insc, bool, boolean,
+                                        |lhs, rhs| lhs || rhs),
+                    FlatOp::BoolNot =>
+                        flat_unary_op!(cur_stack_slice, insc, bool, boolean, |v| !v),
+                    FlatOp::JumpIfTrue => {
+                        let cv = cur_stack_slice.get_value(insc.a as usize);
+                        debug_assert_eq!(cv.type_id(), TypeId::of::<bool>());
+                        if cv.value_typed_data.inner.boolean {
+                            insc_ptr = insc.b as usize;
+                            continue 'exec;
+                        }
+                    },
+                    FlatOp::Jump => {
+                        insc_ptr = insc.a as usize;
+                        continue 'exec;
+                    },
+                    FlatOp::FuncCall => {
+                        #[cfg(not(debug_assertions))]
+                        let func_info: CompiledFuncInfo =
+                            *program.funcs.get_unchecked(insc.a as usize);
+                        #[cfg(debug_assertions)]
+                        let func_info: CompiledFuncInfo = program.funcs[insc.a as usize];
+                        let arg_values = operand_list(operands, insc.b);
+                        debug_assert_eq!(func_info.arg_count, arg_values.len());
+
+                        cur_stack_slice = match stack.func_call_grow_stack(
+                            insc.a as usize,
+                            func_info.stack_size,
+                            arg_values,
+                            operand_list(operands, insc.c),
+                            insc_ptr + 1
+                        ) {
+                            Ok(stack_slice) => stack_slice,
+                            Err(e) => raise!('exec, TError::from(e))
+                        };
+                        insc_ptr = func_info.start_addr;
+                        continue 'exec;
+                    },
+                    FlatOp::TailCall => {
+                        #[cfg(not(debug_assertions))]
+                        let func_info: CompiledFuncInfo =
+                            *program.funcs.get_unchecked(insc.a as usize);
+                        #[cfg(debug_assertions)]
+                        let func_info: CompiledFuncInfo = program.funcs[insc.a as usize];
+                        let arg_values = operand_list(operands, insc.b);
+                        debug_assert_eq!(func_info.arg_count, arg_values.len());
+
+                        cur_stack_slice = match stack.tail_call_reuse_frame(
+                            insc.a as usize,
+                            func_info.stack_size,
+                            arg_values
+                        ) {
+                            Ok(stack_slice) => stack_slice,
+                            Err(e) => raise!('exec, TError::from(e))
+                        };
+                        insc_ptr = func_info.start_addr;
+                        continue 'exec;
+                    },
+                    FlatOp::FFICall => {
+                        #[cfg(not(debug_assertions))]
+                        let ffi_func = program.ffi_funcs.get_unchecked(insc.a as usize);
+                        #[cfg(debug_assertions)]
+                        let ffi_func = &program.ffi_funcs[insc.a as usize];
+
+                        for arg_value in operand_list(operands, insc.b) {
+                            ffi_args.push(cur_stack_slice.get_value(*arg_value));
+                        }
+                        for ret_value_loc in operand_list(operands, insc.c) {
+                            ffi_rets.push(cur_stack_slice.get_value_mut(*ret_value_loc));
+                        }
+
+                        let mut vm = FlatContext { stack: &mut *stack, flat, intake: &mut *intake };
+                        let result = ffi_func.call_with_vm(&mut vm, &ffi_args, &mut ffi_rets[..]);
+                        let result = result
+                            .and_then(|_| adopt_rets(&**ffi_func, &mut ffi_rets, intake));
+                        ffi_args.clear();
+                        ffi_rets.clear();
+                        if let Err(e) = result {
+                            raise!('exec, e);
+                        }
+                    },
+                    FlatOp::ReturnMultiple => {
+                        let ret_values = operand_list(operands, insc.a);
+                        if let Some((prev_stack_slice, ret_addr)) =
+                            stack.done_func_call_shrink_stack(ret_values)
+                        {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            for (i, ret_value_loc) in ret_values.iter().enumerate() {
+                                outputs.get_unchecked_mut(i)
+                                    .write(cur_stack_slice.get_value(*ret_value_loc));
+                            }
+                            return Ok(());
+                        }
+                    },
+                    FlatOp::ReturnOne => {
+                        if let Some((prev_stack_slice, ret_addr)) =
+                            stack.done_func_call_shrink_stack1(insc.a as usize)
+                        {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            debug_assert_eq!(outputs.len(), 1);
+                            outputs.get_unchecked_mut(0)
+                                .write(cur_stack_slice.get_value(insc.a as usize));
+                            return Ok(());
+                        }
+                    },
+                    FlatOp::ReturnNothing => {
+                        if let Some((prev_stack_slice, ret_addr)) =
+                            stack.done_func_call_shrink_stack(&[])
+                        {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            debug_assert_eq!(outputs.len(), 0);
+                            return Ok(());
+                        }
+                    },
+                    FlatOp::Throw => {
+                        let value = cur_stack_slice.get_value(insc.a as usize);
+                        raise!('exec, thrown_error(&value));
+                    },
+                    FlatOp::UnreachableInsc => return Err(VmError::new(
+                        TError::unchecked_exception("this is an internal, unreachable insc"),
+                        insc_ptr,
+                        stack.ret_addrs()
+                    )),
+                    FlatOp::TypeChecked => {
+                        let checked_insc = flat.checked_inscs.get_unchecked(insc.a as usize);
+                        let mut slice = cur_stack_slice;
+                        let result = checked_insc.checks.iter().try_for_each(|(index, check)| {
+                            check_type(&slice.get_value(*index), check)
+                        });
+                        if let Err(e) = result {
+                            raise!('exec, e);
+                        }
+                        insc = &checked_insc.insc;
+                        continue 'dispatch;
+                    }
+                }
+                break 'dispatch;
+            }
+
+            insc_ptr += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::turbofan::rd93::flat::FlatInsc;
+
+    #[test]
+    fn print_flat_insc_size() {
+        eprintln!("std::mem::size_of::<t10::turbofan::rd93::flat::FlatInsc>() = {}",
+                  std::mem::size_of::<FlatInsc>());
+        assert_eq!(std::mem::size_of::<FlatInsc>(), 16);
+    }
+}
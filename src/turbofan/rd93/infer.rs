@@ -0,0 +1,553 @@
+//! `infer` 模块通过抽象解释推导 `rd93` 程序中每个栈位置在每个程序点上的类型
+//!
+//! 推导是过程间的：函数的参数类型是所有调用点上实参类型的并，返回值类型是所有返回指令上返回值
+//! 类型的并。没有被任何 `FuncCall` 或 `TailCall` 调用的函数，参数类型视为未知。从宿主调用函数时，`RD93::run_func`
+//! 会按照推导出的参数类型检查实参。
+//!
+//! 推导完成后，能够证明操作数类型正确的指令保持原样，无法证明的指令被包装为 `Insc::TypeChecked`，
+//! 在运行时检查操作数的类型。推导只跟踪值类型，因此传给宿主函数的非值类型参数总是在运行时按照
+//! `param_specs()` 检查。读取可能未初始化的栈位置的程序会被拒绝。
+
+use std::ops::Range;
+
+use crate::data::ValueType;
+use crate::error::VerifyError;
+use crate::turbofan::rd93::insc::{CompiledProgram, Insc, TypeCheck};
+use crate::tyck::TypeCheckInfo;
+
+/// 抽象解释中一个栈位置的类型
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SlotType {
+    /// 运行时不可能存放任何值，例如不会返回的函数的返回值
+    Unreached,
+    /// 一定存放着某种值类型的值
+    Known(ValueType),
+    /// 已经初始化，但类型未知
+    Any,
+    /// 可能尚未初始化
+    Uninit
+}
+
+impl SlotType {
+    pub fn join(self, other: SlotType) -> SlotType {
+        match (self, other) {
+            (SlotType::Unreached, t) | (t, SlotType::Unreached) => t,
+            (SlotType::Uninit, _) | (_, SlotType::Uninit) => SlotType::Uninit,
+            (SlotType::Known(t1), SlotType::Known(t2)) if t1 == t2 => SlotType::Known(t1),
+            _ => SlotType::Any
+        }
+    }
+
+    /// 这个位置上的值是否一定是 `value_type` 类型
+    pub fn proves(self, value_type: ValueType) -> bool {
+        match self {
+            SlotType::Unreached => true,
+            SlotType::Known(t) => t == value_type,
+            SlotType::Any | SlotType::Uninit => false
+        }
+    }
+}
+
+/// 类型推导的结果
+pub struct TypeInfo {
+    /// 插入了运行时类型检查的指令序列，与输入程序的指令一一对应
+    pub inscs: Vec<Insc>,
+    /// 每个函数的参数类型，`None` 表示类型未知
+    pub param_types: Vec<Vec<Option<ValueType>>>
+}
+
+/// 一条指令读取和写入的栈位置
+struct Effects {
+    /// 读取的栈位置，以及指令对读取的值的类型要求
+    reads: Vec<(usize, Option<TypeCheck>)>,
+    writes: Vec<(usize, SlotType)>
+}
+
+type State = Vec<SlotType>;
+
+struct Inferer<'a> {
+    program: &'a CompiledProgram,
+    func_ranges: &'a [Range<usize>],
+    params: Vec<Vec<SlotType>>,
+    rets: Vec<Vec<SlotType>>,
+    /// 分析某个函数时，其他函数的参数或返回值类型是否发生了变化
+    changed: bool
+}
+
+/// 推导 `program` 中的类型，并为无法证明类型正确的指令插入运行时类型检查
+///
+/// `func_ranges` 是每个函数的指令范围，调用者需要先保证程序在结构上是合法的。
+pub fn infer_types(program: &CompiledProgram, func_ranges: &[Range<usize>])
+    -> Result<TypeInfo, VerifyError>
+{
+    let mut called = vec![false; program.funcs.len()];
+    for insc in program.inscs.iter() {
+        if let Insc::FuncCall { func_id, .. } | Insc::TailCall { func_id, .. } = insc {
+            called[*func_id] = true;
+        }
+    }
+
+    let mut inferer = Inferer {
+        program,
+        func_ranges,
+        params: program.funcs.iter().zip(called).map(|(func_info, called)| {
+            let param_type = if called { SlotType::Unreached } else { SlotType::Any };
+            vec![param_type; func_info.arg_count]
+        }).collect(),
+        rets: program.funcs.iter()
+            .map(|func_info| vec![SlotType::Unreached; func_info.ret_count])
+            .collect(),
+        changed: false
+    };
+
+    loop {
+        inferer.changed = false;
+        for func_id in 0..program.funcs.len() {
+            inferer.analyze_func(func_id);
+        }
+        if inferer.changed {
+            continue;
+        }
+
+        // 所有调用点都不可达的函数仍然可能被宿主调用，此时参数类型未知
+        let mut seeded = false;
+        for param in inferer.params.iter_mut().flatten() {
+            if *param == SlotType::Unreached {
+                *param = SlotType::Any;
+                seeded = true;
+            }
+        }
+        if !seeded {
+            break;
+        }
+    }
+
+    let mut inscs = program.inscs.clone();
+    for (func_id, range) in func_ranges.iter().enumerate() {
+        let states = inferer.analyze_func(func_id);
+        for (insc_ptr, state) in range.clone().zip(states) {
+            let state = match state {
+                Some(state) => state,
+                None => continue
+            };
+
+            let mut checks = Vec::new();
+            for (index, check) in inferer.effects(&program.inscs[insc_ptr]).reads {
+                if state[index] == SlotType::Uninit {
+                    return Err(VerifyError::UninitializedRead { insc_ptr, index });
+                }
+                let proven = match &check {
+                    Some(TypeCheck::Value(value_type)) => state[index].proves(*value_type),
+                    Some(TypeCheck::Host { tyck_info: TypeCheckInfo::SimpleType(type_id), .. }) =>
+                        ValueType::from_type_id(*type_id)
+                            .is_some_and(|value_type| state[index].proves(value_type)),
+                    Some(TypeCheck::Host { .. }) => false,
+                    None => true
+                };
+                if let (false, Some(check)) = (proven, check) {
+                    if !checks.contains(&(index, check.clone())) {
+                        checks.push((index, check));
+                    }
+                }
+            }
+
+            if !checks.is_empty() {
+                inscs[insc_ptr] = Insc::TypeChecked {
+                    checks: checks.into_boxed_slice(),
+                    insc: Box::new(program.inscs[insc_ptr].clone())
+                };
+            }
+        }
+    }
+
+    let param_types = inferer.params.iter().map(|params| {
+        params.iter().map(|param| match param {
+            SlotType::Known(t) => Some(*t),
+            _ => None
+        }).collect()
+    }).collect();
+    Ok(TypeInfo { inscs, param_types })
+}
+
+impl<'a> Inferer<'a> {
+    /// 在当前的参数、返回值类型下分析函数 `func_id`，返回每条指令执行之前的栈类型。不可达的指令
+    /// 对应 `None`
+    fn analyze_func(&mut self, func_id: usize) -> Vec<Option<State>> {
+        let range = self.func_ranges[func_id].clone();
+        let func_info = self.program.funcs[func_id];
+        let handlers = self.program.exception_handlers.get(func_id).map_or(&[][..], Vec::as_slice);
+
+        let mut entry_state = vec![SlotType::Uninit; func_info.stack_size];
+        entry_state[..func_info.arg_count].copy_from_slice(&self.params[func_id]);
+
+        let mut states: Vec<Option<State>> = vec![None; range.len()];
+        states[0] = Some(entry_state);
+        let mut worklist = vec![range.start];
+
+        let merge = |states: &mut Vec<Option<State>>,
+                     worklist: &mut Vec<usize>,
+                     insc_ptr: usize,
+                     state: &State| {
+            match &mut states[insc_ptr - range.start] {
+                Some(old_state) => {
+                    let mut changed = false;
+                    for (old, new) in old_state.iter_mut().zip(state) {
+                        let joined = old.join(*new);
+                        if joined != *old {
+                            *old = joined;
+                            changed = true;
+                        }
+                    }
+                    if changed {
+                        worklist.push(insc_ptr);
+                    }
+                },
+                slot @ None => {
+                    *slot = Some(state.clone());
+                    worklist.push(insc_ptr);
+                }
+            }
+        };
+
+        while let Some(insc_ptr) = worklist.pop() {
+            let mut state = states[insc_ptr - range.start].clone().unwrap();
+            let insc = &self.program.inscs[insc_ptr];
+
+            for handler in handlers {
+                if handler.try_start <= insc_ptr && insc_ptr < handler.try_end {
+                    let mut handler_state = state.clone();
+                    // 宿主函数返回之后，返回值可能因为接管失败而被置为空值，再抛出异常
+                    if let Insc::FFICall { ret_value_locs, .. } = insc {
+                        for ret_value_loc in ret_value_locs {
+                            handler_state[*ret_value_loc] =
+                                handler_state[*ret_value_loc].join(SlotType::Any);
+                        }
+                    }
+                    handler_state[handler.exception_value] = SlotType::Any;
+                    merge(&mut states, &mut worklist, handler.catch_addr, &handler_state);
+                }
+            }
+
+            match insc {
+                Insc::FuncCall { func_id: callee, arg_values, .. } => {
+                    self.join_args(*callee, arg_values, &state);
+                },
+                Insc::TailCall { func_id: callee, arg_values } => {
+                    self.join_args(*callee, arg_values, &state);
+                    // 被调用函数的返回值就是当前函数的返回值
+                    for idx in 0..self.rets[func_id].len() {
+                        let joined = self.rets[func_id][idx].join(self.rets[*callee][idx]);
+                        if joined != self.rets[func_id][idx] {
+                            self.rets[func_id][idx] = joined;
+                            self.changed = true;
+                        }
+                    }
+                },
+                Insc::ReturnOne { ret_value } => {
+                    self.join_ret(func_id, &[*ret_value], &state);
+                },
+                Insc::ReturnMultiple { ret_values } => {
+                    self.join_ret(func_id, ret_values, &state);
+                },
+                _ => {}
+            }
+
+            // 通过了运行时类型检查的值，其类型在之后就是已知的
+            let effects = self.effects(insc);
+            for (index, check) in effects.reads {
+                if let (Some(TypeCheck::Value(value_type)), SlotType::Any | SlotType::Known(_)) =
+                    (check, state[index])
+                {
+                    state[index] = SlotType::Known(value_type);
+                }
+            }
+            for (index, slot_type) in effects.writes {
+                state[index] = slot_type;
+            }
+
+            match insc {
+                Insc::Jump { jump_dest } => {
+                    merge(&mut states, &mut worklist, *jump_dest, &state);
+                },
+                Insc::JumpIfTrue { jump_dest, .. } => {
+                    merge(&mut states, &mut worklist, *jump_dest, &state);
+                    merge(&mut states, &mut worklist, insc_ptr + 1, &state);
+                },
+                Insc::TailCall { .. }
+                | Insc::ReturnOne { .. }
+                | Insc::ReturnMultiple { .. }
+                | Insc::ReturnNothing
+                | Insc::Throw { .. }
+                | Insc::UnreachableInsc => {},
+                _ => merge(&mut states, &mut worklist, insc_ptr + 1, &state)
+            }
+        }
+
+        states
+    }
+
+    fn join_args(&mut self, callee: usize, arg_values: &[usize], state: &State) {
+        for (param, arg_value) in self.params[callee].iter_mut().zip(arg_values) {
+            let joined = param.join(state[*arg_value]);
+            if joined != *param {
+                *param = joined;
+                self.changed = true;
+            }
+        }
+    }
+
+    fn join_ret(&mut self, func_id: usize, ret_values: &[usize], state: &State) {
+        for (ret, ret_value) in self.rets[func_id].iter_mut().zip(ret_values) {
+            let joined = ret.join(state[*ret_value]);
+            if joined != *ret {
+                *ret = joined;
+                self.changed = true;
+            }
+        }
+    }
+
+    fn effects(&self, insc: &Insc) -> Effects {
+        use ValueType::{Bool, Char, Float, Int};
+
+        let binary = |lhs: usize, rhs: usize, dest: usize, ty: ValueType, ret_ty: ValueType| {
+            Effects {
+                reads: vec![(lhs, Some(TypeCheck::Value(ty))), (rhs, Some(TypeCheck::Value(ty)))],
+                writes: vec![(dest, SlotType::Known(ret_ty))]
+            }
+        };
+        let unary = |value: usize, dest: usize, ty: ValueType, ret_ty: ValueType| {
+            Effects {
+                reads: vec![(value, Some(TypeCheck::Value(ty)))],
+                writes: vec![(dest, SlotType::Known(ret_ty))]
+            }
+        };
+        let constant = |dest: usize, ty: ValueType| {
+            Effects { reads: vec![], writes: vec![(dest, SlotType::Known(ty))] }
+        };
+        let reads = |values: &[usize]| {
+            Effects { reads: values.iter().map(|value| (*value, None)).collect(), writes: vec![] }
+        };
+
+        match insc {
+            Insc::MakeIntConst { dest_value, .. } => constant(*dest_value, Int),
+            Insc::MakeFloatConst { dest_value, .. } => constant(*dest_value, Float),
+            Insc::MakeCharConst { dest_value, .. } => constant(*dest_value, Char),
+            Insc::MakeBoolConst { dest_value, .. } => constant(*dest_value, Bool),
+            Insc::IntAdd { lhs_value, rhs_value, dest_value }
+            | Insc::IntSub { lhs_value, rhs_value, dest_value }
+            | Insc::IntMul { lhs_value, rhs_value, dest_value }
+            | Insc::IntDiv { lhs_value, rhs_value, dest_value }
+            | Insc::IntMod { lhs_value, rhs_value, dest_value }
+            | Insc::IntBitAnd { lhs_value, rhs_value, dest_value }
+            | Insc::IntBitOr { lhs_value, rhs_value, dest_value }
+            | Insc::IntBitXor { lhs_value, rhs_value, dest_value }
+            | Insc::IntShl { lhs_value, rhs_value, dest_value }
+            | Insc::IntShr { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Int, Int),
+            Insc::IntEq { lhs_value, rhs_value, dest_value }
+            | Insc::IntNe { lhs_value, rhs_value, dest_value }
+            | Insc::IntLt { lhs_value, rhs_value, dest_value }
+            | Insc::IntLe { lhs_value, rhs_value, dest_value }
+            | Insc::IntGt { lhs_value, rhs_value, dest_value }
+            | Insc::IntGe { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Int, Bool),
+            Insc::IntNeg { value, dest_value } | Insc::IntBitNot { value, dest_value } =>
+                unary(*value, *dest_value, Int, Int),
+            Insc::Incr { value } => unary(*value, *value, Int, Int),
+            Insc::FloatAdd { lhs_value, rhs_value, dest_value }
+            | Insc::FloatSub { lhs_value, rhs_value, dest_value }
+            | Insc::FloatMul { lhs_value, rhs_value, dest_value }
+            | Insc::FloatDiv { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Float, Float),
+            Insc::FloatEq { lhs_value, rhs_value, dest_value }
+            | Insc::FloatNe { lhs_value, rhs_value, dest_value }
+            | Insc::FloatLt { lhs_value, rhs_value, dest_value }
+            | Insc::FloatLe { lhs_value, rhs_value, dest_value }
+            | Insc::FloatGt { lhs_value, rhs_value, dest_value }
+            | Insc::FloatGe { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Float, Bool),
+            Insc::FloatNeg { value, dest_value } => unary(*value, *dest_value, Float, Float),
+            Insc::IntToFloat { value, dest_value } => unary(*value, *dest_value, Int, Float),
+            Insc::FloatToInt { value, dest_value } => unary(*value, *dest_value, Float, Int),
+            Insc::CharEq { lhs_value, rhs_value, dest_value }
+            | Insc::CharNe { lhs_value, rhs_value, dest_value }
+            | Insc::CharLt { lhs_value, rhs_value, dest_value }
+            | Insc::CharLe { lhs_value, rhs_value, dest_value }
+            | Insc::CharGt { lhs_value, rhs_value, dest_value }
+            | Insc::CharGe { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Char, Bool),
+            Insc::BoolAnd { lhs_value, rhs_value, dest_value }
+            | Insc::BoolOr { lhs_value, rhs_value, dest_value } =>
+                binary(*lhs_value, *rhs_value, *dest_value, Bool, Bool),
+            Insc::BoolNot { value, dest_value } => unary(*value, *dest_value, Bool, Bool),
+            Insc::JumpIfTrue { cond_value, .. } => Effects {
+                reads: vec![(*cond_value, Some(TypeCheck::Value(Bool)))],
+                writes: vec![]
+            },
+            Insc::FuncCall { func_id, arg_values, ret_value_locs } => Effects {
+                reads: arg_values.iter().map(|value| (*value, None)).collect(),
+                writes: ret_value_locs.iter().copied().zip(self.rets[*func_id].iter().copied())
+                    .collect()
+            },
+            Insc::TailCall { arg_values, .. } => reads(arg_values),
+            Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+                let ffi_func = &self.program.ffi_funcs[*func_id];
+                let spec_value_type = |(tyck_info, _, nullable): &(_, _, bool)| match tyck_info {
+                    TypeCheckInfo::SimpleType(type_id) if !nullable =>
+                        ValueType::from_type_id(*type_id),
+                    _ => None
+                };
+                // 非值类型的参数无法静态证明，总是在运行时检查
+                let spec_check = |spec: &(TypeCheckInfo, _, bool), type_name| {
+                    match (spec_value_type(spec), &spec.0) {
+                        (Some(value_type), _) => Some(TypeCheck::Value(value_type)),
+                        (None, TypeCheckInfo::Bypass) => None,
+                        (None, tyck_info) => Some(TypeCheck::Host {
+                            tyck_info: tyck_info.clone(),
+                            nullable: spec.2,
+                            type_name
+                        })
+                    }
+                };
+                Effects {
+                    reads: arg_values.iter().copied()
+                        .zip(ffi_func.param_specs().iter().zip(ffi_func.param_type_names())
+                            .map(|(spec, type_name)| spec_check(spec, type_name)))
+                        .collect(),
+                    writes: ret_value_locs.iter().copied()
+                        .zip(ffi_func.return_value_spec().iter().map(|spec| {
+                            spec_value_type(spec).map_or(SlotType::Any, SlotType::Known)
+                        }))
+                        .collect()
+                }
+            },
+            Insc::ReturnOne { ret_value } => reads(&[*ret_value]),
+            Insc::ReturnMultiple { ret_values } => reads(ret_values),
+            Insc::Throw { exception_value } => reads(&[*exception_value]),
+            Insc::Jump { .. }
+            | Insc::ReturnNothing
+            | Insc::UnreachableInsc
+            | Insc::TypeChecked { .. } => Effects { reads: vec![], writes: vec![] }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::{TypeId, type_name};
+    use std::marker::PhantomData;
+
+    use crate::data::{Value, ValueType};
+    use crate::error::VerifyError;
+    use crate::func::RustFunction3;
+    use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, Insc, TypeCheck};
+    use crate::turbofan::rd93::verify::verify;
+    use crate::tyck::TypeCheckInfo;
+
+    fn checks(insc: &Insc) -> Option<Vec<(usize, TypeCheck)>> {
+        match insc {
+            Insc::TypeChecked { checks, .. } => Some(checks.to_vec()),
+            _ => None
+        }
+    }
+
+    #[test] fn test_infer_fibonacci() {
+        let program = CompiledProgram::new(vec![
+            /*00*/ Insc::MakeIntConst { c: 2, dest_value: 1 },
+            /*01*/ Insc::IntLt { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+            /*02*/ Insc::JumpIfTrue { cond_value: 2, jump_dest: 10 },
+            /*03*/ Insc::MakeIntConst { c: 1, dest_value: 1 },
+            /*04*/ Insc::IntSub { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+            /*05*/ Insc::Incr { value: 1 },
+            /*06*/ Insc::IntSub { lhs_value: 0, rhs_value: 1, dest_value: 3 },
+            /*07*/ Insc::FuncCall { func_id: 0, arg_values: vec![2], ret_value_locs: vec![2] },
+            /*08*/ Insc::FuncCall { func_id: 0, arg_values: vec![3], ret_value_locs: vec![3] },
+            /*09*/ Insc::IntAdd { lhs_value: 2, rhs_value: 3, dest_value: 0 },
+            /*10*/ Insc::ReturnOne { ret_value: 0 }
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 1, 4)
+        ], vec![]);
+
+        let program = verify(&program).unwrap();
+        assert!(program.inscs().iter().all(|insc| checks(insc).is_none()));
+        assert_eq!(program.param_types(0), &[Some(ValueType::Int)]);
+    }
+
+    #[test] fn test_infer_unknown() {
+        let program = CompiledProgram::new(vec![
+            // entry(x @%0, y @%1)
+            /*00*/ Insc::MakeIntConst { c: 1, dest_value: 2 },
+            /*01*/ Insc::IntAdd { lhs_value: 0, rhs_value: 2, dest_value: 2 },
+            /*02*/ Insc::IntMul { lhs_value: 0, rhs_value: 2, dest_value: 2 },
+            /*03*/ Insc::FloatNeg { value: 2, dest_value: 3 },
+            /*04*/ Insc::FuncCall { func_id: 1, arg_values: vec![1], ret_value_locs: vec![1] },
+            /*05*/ Insc::FuncCall { func_id: 1, arg_values: vec![2], ret_value_locs: vec![1] },
+            /*06*/ Insc::ReturnOne { ret_value: 1 },
+
+            // id(x @%0)
+            /*07*/ Insc::BoolNot { value: 0, dest_value: 0 },
+            /*08*/ Insc::ReturnOne { ret_value: 0 }
+        ], vec![
+            CompiledFuncInfo::new(0, 2, 1, 4),
+            CompiledFuncInfo::new(7, 1, 1, 1)
+        ], vec![]);
+
+        let program = verify(&program).unwrap();
+        let inscs = program.inscs();
+        assert_eq!(checks(&inscs[0]), None);
+        assert_eq!(checks(&inscs[1]), Some(vec![(0, TypeCheck::Value(ValueType::Int))]));
+        assert_eq!(checks(&inscs[2]), None);
+        assert_eq!(checks(&inscs[3]), Some(vec![(2, TypeCheck::Value(ValueType::Float))]));
+        assert_eq!(checks(&inscs[7]), Some(vec![(0, TypeCheck::Value(ValueType::Bool))]));
+        assert_eq!(program.param_types(0), &[None, None]);
+        assert_eq!(program.param_types(1), &[None]);
+    }
+
+    struct Opaque(i64);
+
+    fn peek(s: &Opaque, n: Option<i64>, _any: Value) -> i64 {
+        s.0 + n.unwrap_or(0)
+    }
+
+    #[test] fn test_infer_ffi_object() {
+        let program = CompiledProgram::new(vec![
+            // entry(s @%0, n @%1, any @%2)
+            /*00*/ Insc::MakeIntConst { c: 1, dest_value: 3 },
+            /*01*/ Insc::FFICall { func_id: 0, arg_values: vec![0, 3, 2], ret_value_locs: vec![3] },
+            /*02*/ Insc::FFICall { func_id: 0, arg_values: vec![0, 1, 2], ret_value_locs: vec![3] },
+            /*03*/ Insc::ReturnOne { ret_value: 3 }
+        ], vec![
+            CompiledFuncInfo::new(0, 3, 1, 4)
+        ], vec![
+            Box::new(RustFunction3 { f: peek, _phantom: PhantomData })
+        ]);
+
+        let program = verify(&program).unwrap();
+        let opaque_check = |slot| (slot, TypeCheck::Host {
+            tyck_info: TypeCheckInfo::SimpleType(TypeId::of::<Opaque>()),
+            nullable: false,
+            type_name: type_name::<&Opaque>()
+        });
+        let int_check = |slot| (slot, TypeCheck::Host {
+            tyck_info: TypeCheckInfo::SimpleType(TypeId::of::<i64>()),
+            nullable: true,
+            type_name: type_name::<Option<i64>>()
+        });
+        // 非值类型的参数总是需要检查，可空的值类型参数只在无法证明类型时检查，`Value` 类型的参数
+        // 不需要检查
+        assert_eq!(checks(&program.inscs()[1]), Some(vec![opaque_check(0)]));
+        assert_eq!(checks(&program.inscs()[2]), Some(vec![opaque_check(0), int_check(1)]));
+    }
+
+    #[test] fn test_infer_uninit() {
+        let program = CompiledProgram::new(vec![
+            /*00*/ Insc::JumpIfTrue { cond_value: 0, jump_dest: 2 },
+            /*01*/ Insc::MakeIntConst { c: 1, dest_value: 1 },
+            /*02*/ Insc::ReturnOne { ret_value: 1 }
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 1, 2)
+        ], vec![]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::UninitializedRead { insc_ptr: 2, index: 1 })));
+    }
+}
@@ -1,26 +1,141 @@
 //! `insc` 中约定了VM模拟使用的“指令集”
 //! 这里仅仅实现 micro bench 所需要的部分
 
+use std::rc::Rc;
+
+use crate::data::ValueType;
+use crate::error::LinkError;
 use crate::func::RustCallable;
+use crate::func::registry::{FFIImport, FunctionRegistry};
+use crate::tyck::TypeCheckInfo;
+use crate::tyck::fusion::ExceptionSpec;
 
+#[derive(Clone, Debug)]
 pub enum Insc {
     MakeIntConst { c: i64, dest_value: usize },
+    MakeFloatConst { c: f64, dest_value: usize },
+    MakeCharConst { c: char, dest_value: usize },
+    MakeBoolConst { c: bool, dest_value: usize },
+
+    // 整数运算均为回绕运算；除数为 0 时抛出非受检异常；移位时位移量对 64 取模
     IntAdd { lhs_value: usize, rhs_value: usize, dest_value: usize },
     IntSub { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntMul { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntDiv { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntMod { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntNeg { value: usize, dest_value: usize },
+    IntBitAnd { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntBitOr { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntBitXor { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntBitNot { value: usize, dest_value: usize },
+    IntShl { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntShr { lhs_value: usize, rhs_value: usize, dest_value: usize },
     IntEq { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntNe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntLt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntLe { lhs_value: usize, rhs_value: usize, dest_value: usize },
     IntGt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    IntGe { lhs_value: usize, rhs_value: usize, dest_value: usize },
     Incr { value: usize },
+
+    // 浮点运算遵循 IEEE 754
+    FloatAdd { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatSub { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatMul { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatDiv { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatNeg { value: usize, dest_value: usize },
+    FloatEq { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatNe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatLt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatLe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatGt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    FloatGe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+
+    // 整数转换为浮点数时就近舍入；浮点数转换为整数时向零截断并饱和，NaN 转换为 0
+    IntToFloat { value: usize, dest_value: usize },
+    FloatToInt { value: usize, dest_value: usize },
+
+    CharEq { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    CharNe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    CharLt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    CharLe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    CharGt { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    CharGe { lhs_value: usize, rhs_value: usize, dest_value: usize },
+
+    BoolAnd { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    BoolOr { lhs_value: usize, rhs_value: usize, dest_value: usize },
+    BoolNot { value: usize, dest_value: usize },
+
     JumpIfTrue { cond_value: usize, jump_dest: usize },
     Jump { jump_dest: usize },
     FuncCall { func_id: usize, arg_values: Vec<usize>, ret_value_locs: Vec<usize> },
+    /// 尾调用：复用当前栈帧调用 `func_id`，被调用函数的返回值直接交给当前函数的调用者。被调用
+    /// 函数的返回值个数必须与当前函数相同；当前函数的异常处理器不覆盖被调用函数内抛出的异常
+    TailCall { func_id: usize, arg_values: Vec<usize> },
     FFICall { func_id: usize, arg_values: Vec<usize>, ret_value_locs: Vec<usize> },
     ReturnOne { ret_value: usize },
     ReturnMultiple { ret_values: Vec<usize> },
     ReturnNothing,
-    UnreachableInsc
+    /// 将 `exception_value` 中由异常处理器捕获的异常重新抛出
+    Throw { exception_value: usize },
+    UnreachableInsc,
+
+    /// 带有运行时类型检查的指令，由校验器为无法静态证明类型正确的指令生成，不应出现在校验器的
+    /// 输入中。执行 `insc` 之前，先检查 `checks` 中的每个栈位置是否存放着对应类型的值
+    TypeChecked { checks: Box<[(usize, TypeCheck)]>, insc: Box<Insc> }
+}
+
+/// `TypeChecked` 对一个栈位置进行的运行时类型检查
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeCheck {
+    /// 检查值是否是某种值类型
+    Value(ValueType),
+    /// 按照宿主函数参数的类型检查信息检查值，`nullable` 为真时允许空值。`type_name` 是参数的
+    /// 类型名，用于生成错误信息
+    Host { tyck_info: TypeCheckInfo, nullable: bool, type_name: &'static str }
+}
+
+/// 异常处理器
+///
+/// 当 `[try_start, try_end)` 范围内的指令抛出异常，且异常类型与 `exception` 相符时，异常会被存入
+/// `exception_value`，然后跳转至 `catch_addr` 继续执行。
+#[derive(Copy, Clone, Debug)]
+pub struct ExceptionHandler {
+    pub try_start: usize,
+    pub try_end: usize,
+    /// 要捕获的异常类型，`None` 表示捕获所有异常
+    pub exception: ExceptionSpec,
+    pub catch_addr: usize,
+    pub exception_value: usize
+}
+
+impl ExceptionHandler {
+    pub fn new(
+        try_start: usize,
+        try_end: usize,
+        exception: ExceptionSpec,
+        catch_addr: usize,
+        exception_value: usize
+    ) -> Self {
+        Self {
+            try_start, try_end, exception, catch_addr, exception_value
+        }
+    }
+
+    pub fn catch_all(try_start: usize, try_end: usize, catch_addr: usize, exception_value: usize)
+        -> Self
+    {
+        Self::new(try_start, try_end, None, catch_addr, exception_value)
+    }
+
+    #[inline] pub fn matches(&self, insc_ptr: usize, exception: ExceptionSpec) -> bool {
+        self.try_start <= insc_ptr
+        && insc_ptr < self.try_end
+        && (self.exception.is_none() || self.exception == exception)
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct CompiledFuncInfo {
     pub start_addr: usize,
     pub arg_count: usize,
@@ -39,7 +154,11 @@ impl CompiledFuncInfo {
 pub struct CompiledProgram {
     pub inscs: Vec<Insc>,
     pub funcs: Vec<CompiledFuncInfo>,
-    pub ffi_funcs: Vec<Box<dyn RustCallable>>
+    pub ffi_funcs: Vec<Box<dyn RustCallable>>,
+    /// 通过 `link` 创建的程序所使用的导入声明，与 `ffi_funcs` 一一对应
+    pub ffi_imports: Vec<FFIImport>,
+    /// 每个函数的异常处理器表，按照函数下标索引。排在前面的处理器优先匹配
+    pub exception_handlers: Vec<Vec<ExceptionHandler>>
 }
 
 impl CompiledProgram {
@@ -51,8 +170,54 @@ impl CompiledProgram {
         Self {
             inscs,
             funcs,
-            ffi_funcs
+            ffi_funcs,
+            ffi_imports: Vec::new(),
+            exception_handlers: Vec::new()
+        }
+    }
+
+    /// 设置函数 `func_id` 的异常处理器表
+    pub fn set_exception_handlers(&mut self, func_id: usize, handlers: Vec<ExceptionHandler>) {
+        if self.exception_handlers.len() <= func_id {
+            self.exception_handlers.resize_with(func_id + 1, Vec::new);
         }
+        self.exception_handlers[func_id] = handlers;
+    }
+
+    /// 查找函数 `func_id` 中能够处理 `insc_ptr` 处抛出的 `exception` 的异常处理器
+    pub fn find_exception_handler(
+        &self,
+        func_id: usize,
+        insc_ptr: usize,
+        exception: ExceptionSpec
+    ) -> Option<&ExceptionHandler> {
+        self.exception_handlers.get(func_id)?
+            .iter()
+            .find(|handler| handler.matches(insc_ptr, exception))
+    }
+
+    /// 使用 `registry` 解析 `ffi_imports`，创建一个程序。`FFICall` 中的 `func_id` 是
+    /// `ffi_imports` 中的下标
+    pub fn link(
+        inscs: Vec<Insc>,
+        funcs: Vec<CompiledFuncInfo>,
+        ffi_imports: Vec<FFIImport>,
+        registry: &FunctionRegistry
+    ) -> Result<Self, LinkError> {
+        let ffi_funcs = registry.link(&ffi_imports)?
+            .into_iter()
+            .map(|idx| {
+                let callable = Rc::clone(&registry.get(idx).unwrap().callable);
+                Box::new(callable) as Box<dyn RustCallable>
+            })
+            .collect();
+        Ok(Self {
+            inscs,
+            funcs,
+            ffi_funcs,
+            ffi_imports,
+            exception_handlers: Vec::new()
+        })
     }
 }
 
@@ -1,24 +1,216 @@
 //! `rd93` 中实现了一个最小化、可运行的 VM，主要用作正式开发之前的 Benchmarking
 
+pub mod asm;
+pub mod flat;
+pub mod infer;
 pub mod insc;
+pub mod module;
+pub mod verify;
 
 use std::any::TypeId;
 use std::mem::MaybeUninit;
 
-use crate::data::Value;
-use crate::turbofan::stack::Stack;
+use crate::cast::from_value::FromValue;
+use crate::cast::into_value::IntoValue;
+use crate::data::{Value, ValueType};
+use crate::error::{ArgLenError, NullError, TError, TypeError, VmError};
+use crate::func::{adopt_rets, check_arg, VmContext};
+use crate::intake::Adopt;
+use crate::turbofan::stack::{Stack, StackSlice};
+use crate::tyck::fusion::ExceptionSpec;
+use crate::void::Void;
 
-pub use insc::{CompiledFuncInfo, CompiledProgram, Insc};
+pub use insc::{CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc, TypeCheck};
+pub use asm::{assemble, disassemble};
+pub use flat::FlatProgram;
+pub use module::{decode_module, encode_module};
+pub use verify::{verify, VerifiedProgram};
+
+/// 读取两个操作数并检查类型，进行二元运算后将结果写入 `dest`
+macro_rules! binary_op {
+    ($slice:ident, $lhs_value:expr, $rhs_value:expr, $dest:expr, $ty:ty, $field:ident,
+     |$lhs:ident, $rhs:ident| $op:expr) => {{
+        let $lhs = $slice.get_value($lhs_value);
+        let $rhs = $slice.get_value($rhs_value);
+        debug_assert_eq!($lhs.type_id(), TypeId::of::<$ty>());
+        debug_assert_eq!($rhs.type_id(), TypeId::of::<$ty>());
+        let $lhs = $lhs.value_typed_data.inner.$field;
+        let $rhs = $rhs.value_typed_data.inner.$field;
+        $slice.set_value($dest, Value::from($op));
+    }};
+}
+
+/// 读取一个操作数并检查类型，进行一元运算后将结果写入 `dest`
+macro_rules! unary_op {
+    ($slice:ident, $value:expr, $dest:expr, $ty:ty, $field:ident, |$v:ident| $op:expr) => {{
+        let $v = $slice.get_value($value);
+        debug_assert_eq!($v.type_id(), TypeId::of::<$ty>());
+        let $v = $v.value_typed_data.inner.$field;
+        $slice.set_value($dest, Value::from($op));
+    }};
+}
+
+/// 检查 `value` 是否是 `value_type` 类型的值
+#[inline] unsafe fn check_value_type(value: &Value, value_type: ValueType) -> Result<(), TError> {
+    if value.value_type() == Some(value_type) {
+        Ok(())
+    } else if value.is_null() {
+        Err(TError::from(NullError()))
+    } else {
+        Err(TError::from(TypeError::of_value(value_type.type_id(), value)))
+    }
+}
+
+/// 进行 `Insc::TypeChecked` 中的一项检查
+#[inline] pub(crate) unsafe fn check_type(value: &Value, check: &TypeCheck) -> Result<(), TError> {
+    match check {
+        TypeCheck::Value(value_type) => check_value_type(value, *value_type),
+        TypeCheck::Host { tyck_info, nullable, type_name } =>
+            check_arg(value, tyck_info, *nullable, type_name)
+    }
+}
+
+/// 检查宿主函数回调 VM 时给出的函数下标和参数个数，返回被调用函数的信息
+pub(crate) fn check_callback(
+    funcs: &[CompiledFuncInfo],
+    func_id: usize,
+    args: &[Value]
+) -> Result<CompiledFuncInfo, TError> {
+    let func_info = *funcs.get(func_id).ok_or_else(|| {
+        TError::unchecked_exception(format!("callback to undefined function {}", func_id))
+    })?;
+    if args.len() != func_info.arg_count {
+        return Err(TError::from(ArgLenError::new(func_info.arg_count, args.len())));
+    }
+    Ok(func_info)
+}
+
+/// 取出 `Throw` 指令重新抛出的异常。只有捕获到的异常（`TError`）可以被重新抛出，空值按照
+/// `FromValue` 的规则转换为 `NullError`
+pub(crate) unsafe fn thrown_error(value: &Value) -> TError {
+    if !value.is_null() && value.type_id() != TypeId::of::<TError>() {
+        return TError::from(
+            TypeError::of_value(TypeId::of::<TError>(), value)
+                .add_extra_info("only caught exceptions can be thrown")
+        );
+    }
+    match <Void as FromValue<TError>>::lifetime_check(value) {
+        Ok(mut guard) => {
+            let error = <Void as FromValue<TError>>::from_value(value);
+            guard.finish();
+            error
+        },
+        Err(e) => e
+    }
+}
+
+/// 抛出异常 `error`，沿调用栈查找能够处理它的异常处理器，`find_exception_handler` 根据函数下标、
+/// 指令地址和异常类型查找处理器
+///
+/// 找到异常处理器时，`error` 被包装为 `Value` 交给 `intake` 管理，存入异常处理器指定的位置，
+/// 返回异常处理器所在栈帧的 `StackSlice` 和异常处理代码的地址；找不到处理器或者 `intake` 无法
+/// 接管异常对象时返回 `VmError`，此时调用栈上只剩下最外层的栈帧。
+pub(crate) unsafe fn unwind<'h>(
+    find_exception_handler: impl Fn(usize, usize, ExceptionSpec) -> Option<&'h ExceptionHandler>,
+    stack: &mut Stack,
+    mut cur_stack_slice: StackSlice,
+    insc_ptr: usize,
+    error: TError,
+    intake: &mut dyn Adopt
+) -> Result<(StackSlice, usize), VmError> {
+    let exception = error.exception_type_id();
+    let ret_addrs = stack.ret_addrs();
+    let mut cur_insc_ptr = insc_ptr;
+    loop {
+        let func_id = stack.frames.last().unwrap_unchecked().func_id;
+        if let Some(handler) = find_exception_handler(func_id, cur_insc_ptr, exception) {
+            let exception_value = match <Void as IntoValue<TError>>::into_value(error) {
+                Ok(value) => value,
+                Err(_) => unreachable!()
+            };
+            if let Err(e) = intake.adopt(exception_value) {
+                // 异常对象无法交给 `intake` 管理时，改为抛出接管失败的错误，不再查找处理器
+                exception_value.drop_object();
+                while stack.unwind_frame().is_some() {}
+                return Err(VmError::new(e, insc_ptr, ret_addrs));
+            }
+            cur_stack_slice.set_value(handler.exception_value, exception_value);
+            return Ok((cur_stack_slice, handler.catch_addr));
+        }
+
+        if let Some((prev_stack_slice, ret_addr)) = stack.unwind_frame() {
+            cur_stack_slice = prev_stack_slice;
+            cur_insc_ptr = ret_addr - 1;
+        } else {
+            return Err(VmError::new(error, insc_ptr, ret_addrs));
+        }
+    }
+}
+
+/// `RD93` 提供给宿主函数的 `VmContext`，回调的函数在同一个调用栈上执行
+struct RD93Context<'a, 'p> {
+    stack: &'a mut Stack,
+    program: &'a VerifiedProgram<'p>,
+    intake: &'a mut dyn Adopt
+}
+
+impl VmContext for RD93Context<'_, '_> {
+    fn call_func(&mut self, func_id: usize, args: &[Value]) -> Result<Vec<Value>, TError> {
+        let func_info = check_callback(&self.program.program().funcs, func_id, args)?;
+        let mut outputs = vec![MaybeUninit::uninit(); func_info.ret_count];
+        unsafe {
+            RD93::run_func_with_stack(
+                self.stack, self.program, func_id, args, &mut outputs, &mut *self.intake
+            )
+                .map_err(|e| *e.error)?;
+            Ok(outputs.into_iter().map(|output| output.assume_init()).collect())
+        }
+    }
+}
 
 pub struct RD93 ();
 
 impl RD93 {
+    /// 执行程序中的函数 `func_id`，将返回值写入 `outputs`
+    ///
+    /// 宿主函数返回的新对象和被捕获的异常对象由 `intake` 接管，`outputs` 中的对象在 `intake` 被丢弃
+    /// 之后不再有效。`intake` 无法接管宿主函数的返回值时，在调用点抛出它返回的错误。VM 只通过
+    /// `Adopt::adopt` 使用 `intake`，执行期间不会触发回收；返回之后，`outputs` 中的对象只有在
+    /// 能够从 `intake` 的根集合到达时才会在下一次回收中存活
+    ///
+    /// # Safety
+    ///
+    /// `func_id` 必须是 `program` 中的函数，`args` 和 `outputs` 的长度必须与函数的参数、返回值个数
+    /// 一致，并且 `args` 的类型必须与函数所期望的类型一致
     pub unsafe fn run_func(
-        program: &CompiledProgram,
+        program: &VerifiedProgram,
         func_id: usize,
         args: &[Value],
-        outputs: &mut [MaybeUninit<Value>]
-    ) {
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        Self::run_func_with_stack(&mut Stack::new(), program, func_id, args, outputs, intake)
+    }
+
+    /// 与 `run_func` 相同，但是使用调用者提供的 `stack`，以便在多次调用之间复用栈空间
+    ///
+    /// `stack` 上已有的栈帧不受影响，因此宿主函数也可以在 VM 正在使用的栈上发起嵌套调用
+    ///
+    /// # Safety
+    ///
+    /// 同 `run_func`
+    pub unsafe fn run_func_with_stack(
+        stack: &mut Stack,
+        program: &VerifiedProgram,
+        func_id: usize,
+        args: &[Value],
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        let verified = program;
+        let param_types = program.param_types(func_id);
+        let program = program.program();
+
         #[cfg(not(debug_assertions))]
         let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(func_id);
         #[cfg(debug_assertions)]
@@ -28,156 +220,342 @@ impl RD93 {
         debug_assert_eq!(args.len(), func_info.arg_count);
         debug_assert_eq!(outputs.len(), func_info.ret_count);
 
-        let mut insc_ptr = func_info.start_addr;
-        let mut stack = Stack::new();
-        let mut cur_stack_slice = stack.ext_func_call_grow_stack(
+        // 校验器按照内部调用点推导了参数类型，从宿主传入的实参也要满足这些类型
+        for (arg, param_type) in args.iter().zip(param_types) {
+            if let Some(param_type) = param_type {
+                if let Err(e) = check_value_type(arg, *param_type) {
+                    return Err(VmError::new(e, func_info.start_addr, vec![]));
+                }
+            }
+        }
+
+        let cur_stack_slice = match stack.ext_func_call_grow_stack(
+            func_id,
             func_info.stack_size,
             args,
             &dummy_ret_locs
-        );
-        for (idx, arg) in args.iter().enumerate() {
-            cur_stack_slice.set_value(idx, *arg);
-        }
+        ) {
+            Ok(stack_slice) => stack_slice,
+            Err(e) => return Err(VmError::new(TError::from(e), func_info.start_addr, vec![]))
+        };
+        let result =
+            Self::exec(stack, verified, cur_stack_slice, func_info.start_addr, outputs, intake);
+        stack.ext_func_call_shrink_stack();
+        result
+    }
+
+    /// 解释器的主循环。调用者已经在 `stack` 上创建了最外层的栈帧，从 `insc_ptr` 开始执行
+    unsafe fn exec(
+        stack: &mut Stack,
+        verified: &VerifiedProgram,
+        mut cur_stack_slice: StackSlice,
+        mut insc_ptr: usize,
+        outputs: &mut [MaybeUninit<Value>],
+        intake: &mut dyn Adopt
+    ) -> Result<(), VmError> {
+        let inscs = verified.inscs();
+        let program = verified.program();
         let mut ffi_args = Vec::with_capacity(8);
         let mut ffi_rets = Vec::with_capacity(3);
 
-        loop {
-            let insc: &Insc = program.inscs.get_unchecked(insc_ptr);
-            match insc {
-                Insc::MakeIntConst { c, dest_value } => {
-                    cur_stack_slice.set_value(*dest_value, Value::from(*c));
-                },
-                Insc::IntAdd { lhs_value, rhs_value, dest_value } => {
-                    let lhs = cur_stack_slice.get_value(*lhs_value);
-                    let rhs = cur_stack_slice.get_value(*rhs_value);
-                    debug_assert_eq!(lhs.type_id(), TypeId::of::<i64>());
-                    debug_assert_eq!(rhs.type_id(), TypeId::of::<i64>());
-                    let lhs = lhs.value_typed_data.inner.int;
-                    let rhs = rhs.value_typed_data.inner.int;
-                    let sum = lhs.wrapping_add(rhs);
-                    cur_stack_slice.set_value(*dest_value, Value::from(sum));
-                },
-                Insc::IntSub { lhs_value, rhs_value, dest_value } => {
-                    let lhs = cur_stack_slice.get_value(*lhs_value);
-                    let rhs = cur_stack_slice.get_value(*rhs_value);
-                    debug_assert_eq!(lhs.type_id(), TypeId::of::<i64>());
-                    debug_assert_eq!(rhs.type_id(), TypeId::of::<i64>());
-                    let lhs = lhs.value_typed_data.inner.int;
-                    let rhs = rhs.value_typed_data.inner.int;
-                    let sub = lhs.wrapping_sub(rhs);
-                    cur_stack_slice.set_value(*dest_value, Value::from(sub));
-                },
-                Insc::IntEq { lhs_value, rhs_value, dest_value } => {
-                    let lhs = cur_stack_slice.get_value(*lhs_value);
-                    let rhs = cur_stack_slice.get_value(*rhs_value);
-                    debug_assert_eq!(lhs.type_id(), TypeId::of::<i64>());
-                    debug_assert_eq!(rhs.type_id(), TypeId::of::<i64>());
-                    let lhs = lhs.value_typed_data.inner.int;
-                    let rhs = rhs.value_typed_data.inner.int;
-                    cur_stack_slice.set_value(*dest_value, Value::from(lhs == rhs));
-                },
-                Insc::IntGt { lhs_value, rhs_value, dest_value } => {
-                    let lhs = cur_stack_slice.get_value(*lhs_value);
-                    let rhs = cur_stack_slice.get_value(*rhs_value);
-                    debug_assert_eq!(lhs.type_id(), TypeId::of::<i64>());
-                    debug_assert_eq!(rhs.type_id(), TypeId::of::<i64>());
-                    let lhs = lhs.value_typed_data.inner.int;
-                    let rhs = rhs.value_typed_data.inner.int;
-                    cur_stack_slice.set_value(*dest_value, Value::from(lhs > rhs));
-                },
-                Insc::Incr { value } => {
-                    let v = cur_stack_slice.get_value(*value);
-                    debug_assert_eq!(v.type_id(), TypeId::of::<i64>());
-                    let i = v.value_typed_data.inner.int;
-                    cur_stack_slice.set_value(*value, Value::from(i.wrapping_add(1)))
-                },
-                Insc::JumpIfTrue { cond_value, jump_dest } => {
-                    let cv = cur_stack_slice.get_value(*cond_value);
-                    debug_assert_eq!(cv.type_id(), TypeId::of::<bool>());
-                    let cond = cv.value_typed_data.inner.boolean;
-                    if cond {
+        // 抛出异常，跳转到异常处理器，或者从 `run_func` 返回错误
+        macro_rules! raise {
+            ($label:lifetime, $error:expr) => {{
+                let (stack_slice, catch_addr) = unwind(
+                    |func_id, insc_ptr, exception|
+                        program.find_exception_handler(func_id, insc_ptr, exception),
+                    stack, cur_stack_slice, insc_ptr, $error, &mut *intake
+                )?;
+                cur_stack_slice = stack_slice;
+                insc_ptr = catch_addr;
+                continue $label;
+            }};
+        }
+
+        'exec: loop {
+            let mut insc: &Insc = inscs.get_unchecked(insc_ptr);
+            // `TypeChecked` 检查通过之后，回到这里执行被包装的指令
+            'dispatch: loop {
+                match insc {
+                    Insc::MakeIntConst { c, dest_value } => {
+                        cur_stack_slice.set_value(*dest_value, Value::from(*c));
+                    },
+                    Insc::MakeFloatConst { c, dest_value } => {
+                        cur_stack_slice.set_value(*dest_value, Value::from(*c));
+                    },
+                    Insc::MakeCharConst { c, dest_value } => {
+                        cur_stack_slice.set_value(*dest_value, Value::from(*c));
+                    },
+                    Insc::MakeBoolConst { c, dest_value } => {
+                        cur_stack_slice.set_value(*dest_value, Value::from(*c));
+                    },
+                    Insc::IntAdd { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs.wrapping_add(rhs)),
+                    Insc::IntSub { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs.wrapping_sub(rhs)),
+                    Insc::IntMul { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs.wrapping_mul(rhs)),
+                    Insc::IntDiv { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| {
+                                       if rhs == 0 {
+                                           raise!('exec, TError::unchecked_exception(
+                                               "division by zero"
+                                           ));
+                                       }
+                                       lhs.wrapping_div(rhs)
+                                   }),
+                    Insc::IntMod { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| {
+                                       if rhs == 0 {
+                                           raise!('exec, TError::unchecked_exception(
+                                               "division by zero"
+                                           ));
+                                       }
+                                       lhs.wrapping_rem(rhs)
+                                   }),
+                    Insc::IntNeg { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, i64, int, |v| v.wrapping_neg()),
+                    Insc::IntBitAnd { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs & rhs),
+                    Insc::IntBitOr { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs | rhs),
+                    Insc::IntBitXor { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs ^ rhs),
+                    Insc::IntBitNot { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, i64, int, |v| !v),
+                    Insc::IntShl { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs.wrapping_shl(rhs as u32)),
+                    Insc::IntShr { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs.wrapping_shr(rhs as u32)),
+                    Insc::IntEq { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs == rhs),
+                    Insc::IntNe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs != rhs),
+                    Insc::IntLt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs < rhs),
+                    Insc::IntLe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs <= rhs),
+                    Insc::IntGt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs > rhs),
+                    Insc::IntGe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, i64, int,
+                                   |lhs, rhs| lhs >= rhs),
+                    Insc::Incr { value } => {
+                        let v = cur_stack_slice.get_value(*value);
+                        debug_assert_eq!(v.type_id(), TypeId::of::<i64>());
+                        let i = v.value_typed_data.inner.int;
+                        cur_stack_slice.set_value(*value, Value::from(i.wrapping_add(1)))
+                    },
+                    Insc::FloatAdd { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs + rhs),
+                    Insc::FloatSub { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs - rhs),
+                    Insc::FloatMul { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs * rhs),
+                    Insc::FloatDiv { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs / rhs),
+                    Insc::FloatNeg { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, f64, float, |v| -v),
+                    Insc::FloatEq { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs == rhs),
+                    Insc::FloatNe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs != rhs),
+                    Insc::FloatLt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs < rhs),
+                    Insc::FloatLe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs <= rhs),
+                    Insc::FloatGt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs > rhs),
+                    Insc::FloatGe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, f64, float,
+                                   |lhs, rhs| lhs >= rhs),
+                    Insc::IntToFloat { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, i64, int, |v| v as f64),
+                    Insc::FloatToInt { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, f64, float, |v| v as i64),
+                    Insc::CharEq { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs == rhs),
+                    Insc::CharNe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs != rhs),
+                    Insc::CharLt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs < rhs),
+                    Insc::CharLe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs <= rhs),
+                    Insc::CharGt { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs > rhs),
+                    Insc::CharGe { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, char, ch,
+                                   |lhs, rhs| lhs >= rhs),
+                    Insc::BoolAnd { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, bool, boolean,
+                                   |lhs, rhs| lhs && rhs),
+                    Insc::BoolOr { lhs_value, rhs_value, dest_value } =>
+                        binary_op!(cur_stack_slice, *lhs_value, *rhs_value, *dest_value, bool, boolean,
+                                   |lhs, rhs| lhs || rhs),
+                    Insc::BoolNot { value, dest_value } =>
+                        unary_op!(cur_stack_slice, *value, *dest_value, bool, boolean, |v| !v),
+                    Insc::JumpIfTrue { cond_value, jump_dest } => {
+                        let cv = cur_stack_slice.get_value(*cond_value);
+                        debug_assert_eq!(cv.type_id(), TypeId::of::<bool>());
+                        let cond = cv.value_typed_data.inner.boolean;
+                        if cond {
+                            insc_ptr = *jump_dest;
+                            continue 'exec;
+                        }
+                    },
+                    Insc::Jump { jump_dest } => {
                         insc_ptr = *jump_dest;
-                        continue;
-                    }
-                },
-                Insc::Jump { jump_dest } => {
-                    insc_ptr = *jump_dest;
-                    continue;
-                },
-                Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
-                    #[cfg(not(debug_assertions))]
-                    let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(*func_id);
-                    #[cfg(debug_assertions)]
-                    let func_info: CompiledFuncInfo = program.funcs[*func_id];
-                    debug_assert_eq!(func_info.arg_count, arg_values.len());
-
-                    cur_stack_slice = stack.func_call_grow_stack(
-                        func_info.stack_size,
-                        arg_values,
-                        ret_value_locs,
-                        insc_ptr + 1
-                    );
-                    insc_ptr = func_info.start_addr;
-                    continue;
-                },
-                Insc::FFICall { func_id, arg_values, ret_value_locs } => {
-                    #[cfg(not(debug_assertions))]
-                    let ffi_func = program.ffi_funcs.get_unchecked(*func_id);
-                    #[cfg(debug_assertions)]
-                    let ffi_func = &program.ffi_funcs[*func_id];
-
-                    for arg_value in arg_values {
-                        ffi_args.push(cur_stack_slice.get_value(*arg_value));
-                    }
-
-                    for ret_value_loc in ret_value_locs {
-                        ffi_rets.push(cur_stack_slice.get_value_mut(*ret_value_loc));
-                    }
-
-                    match ffi_func.call_prechecked(&ffi_args, &mut ffi_rets[..]) {
-                        Ok(()) => {},
-                        // TODO support exception handling
-                        Err(e) => panic!("exception: ".to_string() + &e.to_string())
-                    }
-
-                    ffi_args.clear();
-                    ffi_rets.clear();
-                },
-                Insc::ReturnMultiple { ret_values } => {
-                    if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack(&ret_values) {
-                        insc_ptr = ret_addr;
-                        cur_stack_slice = prev_stack_slice;
-                        continue;
-                    } else {
-                        for (i, ret_value_loc) in ret_values.iter().enumerate() {
-                            outputs.get_unchecked_mut(i).write(cur_stack_slice.get_value(*ret_value_loc));
+                        continue 'exec;
+                    },
+                    Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
+                        #[cfg(not(debug_assertions))]
+                        let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(*func_id);
+                        #[cfg(debug_assertions)]
+                        let func_info: CompiledFuncInfo = program.funcs[*func_id];
+                        debug_assert_eq!(func_info.arg_count, arg_values.len());
+
+                        cur_stack_slice = match stack.func_call_grow_stack(
+                            *func_id,
+                            func_info.stack_size,
+                            arg_values,
+                            ret_value_locs,
+                            insc_ptr + 1
+                        ) {
+                            Ok(stack_slice) => stack_slice,
+                            Err(e) => raise!('exec, TError::from(e))
+                        };
+                        insc_ptr = func_info.start_addr;
+                        continue 'exec;
+                    },
+                    Insc::TailCall { func_id, arg_values } => {
+                        #[cfg(not(debug_assertions))]
+                        let func_info: CompiledFuncInfo = *program.funcs.get_unchecked(*func_id);
+                        #[cfg(debug_assertions)]
+                        let func_info: CompiledFuncInfo = program.funcs[*func_id];
+                        debug_assert_eq!(func_info.arg_count, arg_values.len());
+
+                        cur_stack_slice = match stack.tail_call_reuse_frame(
+                            *func_id,
+                            func_info.stack_size,
+                            arg_values
+                        ) {
+                            Ok(stack_slice) => stack_slice,
+                            Err(e) => raise!('exec, TError::from(e))
+                        };
+                        insc_ptr = func_info.start_addr;
+                        continue 'exec;
+                    },
+                    Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+                        #[cfg(not(debug_assertions))]
+                        let ffi_func = program.ffi_funcs.get_unchecked(*func_id);
+                        #[cfg(debug_assertions)]
+                        let ffi_func = &program.ffi_funcs[*func_id];
+
+                        for arg_value in arg_values {
+                            ffi_args.push(cur_stack_slice.get_value(*arg_value));
+                        }
+
+                        for ret_value_loc in ret_value_locs {
+                            ffi_rets.push(cur_stack_slice.get_value_mut(*ret_value_loc));
+                        }
+
+                        // 宿主函数回调 VM 时，嵌套调用的栈帧位于当前栈帧之上，`cur_stack_slice`
+                        // 和 `ffi_rets` 在调用之后仍然有效
+                        let mut vm = RD93Context { stack: &mut *stack, program: verified, intake: &mut *intake };
+                        let result = ffi_func.call_with_vm(&mut vm, &ffi_args, &mut ffi_rets[..]);
+                        let result = result
+                            .and_then(|_| adopt_rets(&**ffi_func, &mut ffi_rets, intake));
+                        ffi_args.clear();
+                        ffi_rets.clear();
+                        if let Err(e) = result {
+                            raise!('exec, e);
+                        }
+                    },
+                    Insc::ReturnMultiple { ret_values } => {
+                        if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack(&ret_values) {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            for (i, ret_value_loc) in ret_values.iter().enumerate() {
+                                outputs.get_unchecked_mut(i).write(cur_stack_slice.get_value(*ret_value_loc));
+                            }
+                            return Ok(());
+                        }
+                    },
+                    Insc::ReturnOne { ret_value } => {
+                        if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack1(*ret_value) {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            debug_assert_eq!(outputs.len(), 1);
+                            outputs.get_unchecked_mut(0).write(cur_stack_slice.get_value(*ret_value));
+                            return Ok(());
+                        }
+                    },
+                    Insc::ReturnNothing => {
+                        if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack(&[]) {
+                            insc_ptr = ret_addr;
+                            cur_stack_slice = prev_stack_slice;
+                            continue 'exec;
+                        } else {
+                            debug_assert_eq!(outputs.len(), 0);
+                            return Ok(());
+                        }
+                    },
+                    Insc::Throw { exception_value } => {
+                        let value = cur_stack_slice.get_value(*exception_value);
+                        raise!('exec, thrown_error(&value));
+                    },
+                    Insc::UnreachableInsc => return Err(VmError::new(
+                        TError::unchecked_exception("this is an internal, unreachable insc"),
+                        insc_ptr,
+                        stack.ret_addrs()
+                    )),
+                    Insc::TypeChecked { checks, insc: checked_insc } => {
+                        let mut slice = cur_stack_slice;
+                        let result = checks.iter().try_for_each(|(index, check)| {
+                            check_type(&slice.get_value(*index), check)
+                        });
+                        if let Err(e) = result {
+                            raise!('exec, e);
                         }
-                        return;
-                    }
-                },
-                Insc::ReturnOne { ret_value } => {
-                    if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack1(*ret_value) {
-                        insc_ptr = ret_addr;
-                        cur_stack_slice = prev_stack_slice;
-                        continue;
-                    } else {
-                        debug_assert_eq!(outputs.len(), 1);
-                        outputs.get_unchecked_mut(0).write(cur_stack_slice.get_value(*ret_value));
-                        return;
-                    }
-                },
-                Insc::ReturnNothing => {
-                    if let Some((prev_stack_slice, ret_addr)) = stack.done_func_call_shrink_stack(&[]) {
-                        insc_ptr = ret_addr;
-                        cur_stack_slice = prev_stack_slice;
-                        continue;
-                    } else {
-                        debug_assert_eq!(outputs.len(), 0);
-                        return;
-                    }
-                },
-                Insc::UnreachableInsc => panic!("this is an internal, unreachable insc"),
-                // _ => todo!("unimplemented insc")
+                        insc = checked_insc;
+                        continue 'dispatch;
+                    },
+                    // _ => todo!("unimplemented insc")
+                }
+                break 'dispatch;
             }
 
             insc_ptr += 1;
@@ -0,0 +1,747 @@
+//! `module` 模块实现了 `CompiledProgram` 的二进制模块格式，用来在多次运行之间缓存编译好的程序
+//!
+//! 模块由头部和负载组成，所有整数都以小端序存储：
+//!
+//! | 偏移 | 长度 | 内容                                 |
+//! |------|------|--------------------------------------|
+//! | 0    | 8    | 魔数 `b"T10RD93\0"`                  |
+//! | 8    | 2    | 格式版本，目前为 `MODULE_VERSION`    |
+//! | 10   | 2    | 保留，必须为 0                       |
+//! | 12   | 4    | 负载长度                             |
+//! | 16   | 4    | 负载的 CRC-32 校验和                 |
+//! | 20   | -    | 负载                                 |
+//!
+//! 负载依次存放宿主函数的导入声明、函数信息、异常处理器表和指令。宿主函数以名字存储，加载时通过
+//! `FunctionRegistry` 重新链接；异常类型同样以 `FunctionRegistry::register_exception` 注册的名字
+//! 存储。`TypeId` 在不同的编译之间并不稳定，因此导入声明中的对象类型（既不是值类型，也不是
+//! `TypeCheckInfo::Bypass` 的类型）只记录为“对象”，加载时从注册的同名宿主函数的签名中重新取得
+//! 对应位置的类型；该位置在注册的函数中不是对象类型时，加载失败。
+//!
+//! 加载时除了检查头部和校验和之外，还会对程序运行校验器，保证加载得到的程序总是可以执行的。
+
+use std::any::TypeId;
+use std::convert::{TryFrom, TryInto};
+
+use crate::data::ValueType;
+use crate::error::{LinkError, ModuleError, builtin_exception_name, builtin_exception_type};
+use crate::func::registry::{FFIImport, FunctionRegistry};
+use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc};
+use crate::turbofan::rd93::verify::verify;
+use crate::tyck::{FFIAction, TypeCheckInfo};
+use crate::tyck::fusion::Nullable;
+
+pub const MODULE_MAGIC: [u8; 8] = *b"T10RD93\0";
+pub const MODULE_VERSION: u16 = 1;
+
+const HEADER_SIZE: usize = 20;
+
+/// 定义三个操作数的指令与操作码之间的对应关系
+macro_rules! binary_opcodes {
+    ($($variant:ident => $opcode:literal),* $(,)?) => {
+        fn make_binary_insc(opcode: u8, lhs_value: usize, rhs_value: usize, dest_value: usize)
+            -> Option<Insc>
+        {
+            match opcode {
+                $($opcode => Some(Insc::$variant { lhs_value, rhs_value, dest_value }),)*
+                _ => None
+            }
+        }
+
+        fn binary_insc_parts(insc: &Insc) -> Option<(u8, usize, usize, usize)> {
+            match insc {
+                $(Insc::$variant { lhs_value, rhs_value, dest_value } =>
+                    Some(($opcode, *lhs_value, *rhs_value, *dest_value)),)*
+                _ => None
+            }
+        }
+    };
+}
+
+/// 定义两个操作数的指令与操作码之间的对应关系
+macro_rules! unary_opcodes {
+    ($($variant:ident => $opcode:literal),* $(,)?) => {
+        fn make_unary_insc(opcode: u8, value: usize, dest_value: usize) -> Option<Insc> {
+            match opcode {
+                $($opcode => Some(Insc::$variant { value, dest_value }),)*
+                _ => None
+            }
+        }
+
+        fn unary_insc_parts(insc: &Insc) -> Option<(u8, usize, usize)> {
+            match insc {
+                $(Insc::$variant { value, dest_value } => Some(($opcode, *value, *dest_value)),)*
+                _ => None
+            }
+        }
+    };
+}
+
+// 操作码一经发布就不能修改，新的指令只能使用新的操作码
+binary_opcodes! {
+    IntAdd => 0x10, IntSub => 0x11, IntMul => 0x12, IntDiv => 0x13, IntMod => 0x14,
+    IntBitAnd => 0x15, IntBitOr => 0x16, IntBitXor => 0x17, IntShl => 0x18, IntShr => 0x19,
+    IntEq => 0x1a, IntNe => 0x1b, IntLt => 0x1c, IntLe => 0x1d, IntGt => 0x1e, IntGe => 0x1f,
+    FloatAdd => 0x30, FloatSub => 0x31, FloatMul => 0x32, FloatDiv => 0x33,
+    FloatEq => 0x34, FloatNe => 0x35, FloatLt => 0x36, FloatLe => 0x37, FloatGt => 0x38,
+    FloatGe => 0x39,
+    CharEq => 0x50, CharNe => 0x51, CharLt => 0x52, CharLe => 0x53, CharGt => 0x54,
+    CharGe => 0x55,
+    BoolAnd => 0x60, BoolOr => 0x61
+}
+
+unary_opcodes! {
+    IntNeg => 0x20, IntBitNot => 0x21, FloatNeg => 0x40, IntToFloat => 0x41,
+    FloatToInt => 0x42, BoolNot => 0x62
+}
+
+const OP_MAKE_INT_CONST: u8 = 0x01;
+const OP_MAKE_FLOAT_CONST: u8 = 0x02;
+const OP_MAKE_CHAR_CONST: u8 = 0x03;
+const OP_MAKE_BOOL_CONST: u8 = 0x04;
+const OP_INCR: u8 = 0x22;
+const OP_JUMP_IF_TRUE: u8 = 0x70;
+const OP_JUMP: u8 = 0x71;
+const OP_FUNC_CALL: u8 = 0x72;
+const OP_FFI_CALL: u8 = 0x73;
+const OP_RETURN_ONE: u8 = 0x74;
+const OP_RETURN_MULTIPLE: u8 = 0x75;
+const OP_RETURN_NOTHING: u8 = 0x76;
+const OP_THROW: u8 = 0x77;
+const OP_UNREACHABLE_INSC: u8 = 0x78;
+const OP_TAIL_CALL: u8 = 0x79;
+
+const TYPE_ANY: u8 = 0;
+const TYPE_INT: u8 = 1;
+const TYPE_FLOAT: u8 = 2;
+const TYPE_CHAR: u8 = 3;
+const TYPE_BOOL: u8 = 4;
+const TYPE_OBJECT: u8 = 5;
+
+/// 导入声明中的类型能否表示为值类型
+fn value_type_of(tyck_info: &TypeCheckInfo) -> Option<ValueType> {
+    match tyck_info {
+        TypeCheckInfo::SimpleType(type_id) => ValueType::from_type_id(*type_id),
+        _ => None
+    }
+}
+
+/// 将导入声明中记录为对象的类型替换为注册的宿主函数在对应位置上的类型，`specs` 是宿主函数的
+/// 参数或返回值签名
+fn resolve_object_types(
+    func_name: &str,
+    tyck_infos: Vec<Option<TypeCheckInfo>>,
+    specs: &[(TypeCheckInfo, FFIAction, Nullable)],
+    is_return: bool
+) -> Result<Vec<TypeCheckInfo>, ModuleError> {
+    tyck_infos.into_iter().enumerate().map(|(index, tyck_info)| match tyck_info {
+        Some(tyck_info) => Ok(tyck_info),
+        None => match specs.get(index) {
+            Some((tyck_info, _, _))
+                if *tyck_info != TypeCheckInfo::Bypass && value_type_of(tyck_info).is_none() =>
+                Ok(tyck_info.clone()),
+            _ => Err(ModuleError::ObjectTypeMismatch {
+                func_name: func_name.to_string(),
+                index,
+                is_return
+            })
+        }
+    }).collect()
+}
+
+/// 计算 CRC-32（IEEE 802.3）校验和
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct Writer {
+    bytes: Vec<u8>
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) -> Result<(), ModuleError> {
+        let value = u32::try_from(value)
+            .map_err(|_| ModuleError::Malformed(format!("value {} is too large", value)))?;
+        self.u32(value);
+        Ok(())
+    }
+
+    fn usizes(&mut self, values: &[usize]) -> Result<(), ModuleError> {
+        self.usize(values.len())?;
+        values.iter().try_for_each(|value| self.usize(*value))
+    }
+
+    fn str(&mut self, value: &str) -> Result<(), ModuleError> {
+        self.usize(value.len())?;
+        self.bytes.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn tyck_infos(&mut self, tyck_infos: &[TypeCheckInfo]) -> Result<(), ModuleError> {
+        self.usize(tyck_infos.len())?;
+        for tyck_info in tyck_infos {
+            self.u8(match (value_type_of(tyck_info), tyck_info) {
+                (Some(ValueType::Int), _) => TYPE_INT,
+                (Some(ValueType::Float), _) => TYPE_FLOAT,
+                (Some(ValueType::Char), _) => TYPE_CHAR,
+                (Some(ValueType::Bool), _) => TYPE_BOOL,
+                (_, TypeCheckInfo::Bypass) => TYPE_ANY,
+                _ => TYPE_OBJECT
+            });
+        }
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ModuleError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(ModuleError::Truncated);
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, ModuleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ModuleError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ModuleError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, ModuleError> {
+        Ok(self.u32()? as usize)
+    }
+
+    /// 读取一个长度。每个元素至少占用一个字节，长度超过剩余字节数的模块一定是损坏的
+    fn len(&mut self) -> Result<usize, ModuleError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() - self.pos {
+            return Err(ModuleError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn usizes(&mut self) -> Result<Vec<usize>, ModuleError> {
+        let len = self.len()?;
+        (0..len).map(|_| self.usize()).collect()
+    }
+
+    fn str(&mut self) -> Result<String, ModuleError> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| ModuleError::Malformed("invalid utf-8 string".to_string()))
+    }
+
+    /// 读取导入声明中的类型列表，对象类型以 `None` 表示，需要通过 `resolve_object_types` 解析
+    fn tyck_infos(&mut self) -> Result<Vec<Option<TypeCheckInfo>>, ModuleError> {
+        let len = self.len()?;
+        (0..len).map(|_| {
+            Ok(Some(match self.u8()? {
+                TYPE_ANY => TypeCheckInfo::Bypass,
+                TYPE_INT => TypeCheckInfo::SimpleType(TypeId::of::<i64>()),
+                TYPE_FLOAT => TypeCheckInfo::SimpleType(TypeId::of::<f64>()),
+                TYPE_CHAR => TypeCheckInfo::SimpleType(TypeId::of::<char>()),
+                TYPE_BOOL => TypeCheckInfo::SimpleType(TypeId::of::<bool>()),
+                TYPE_OBJECT => return Ok(None),
+                tag => return Err(ModuleError::Malformed(format!("unknown type tag {}", tag)))
+            }))
+        }).collect()
+    }
+}
+
+/// 将 `program` 编码为二进制模块，`registry` 用来查找异常类型的名字
+///
+/// 程序必须带有导入声明（即由 `CompiledProgram::link` 或者汇编器创建），否则宿主函数无法以名字
+/// 存储。
+pub fn encode_module(program: &CompiledProgram, registry: &FunctionRegistry)
+    -> Result<Vec<u8>, ModuleError>
+{
+    if program.ffi_imports.len() != program.ffi_funcs.len() {
+        return Err(ModuleError::MissingImports);
+    }
+
+    let mut w = Writer { bytes: Vec::new() };
+
+    w.usize(program.ffi_imports.len())?;
+    for import in program.ffi_imports.iter() {
+        w.str(&import.name)?;
+        w.tyck_infos(&import.param_tyck_info)?;
+        w.tyck_infos(&import.ret_tyck_info)?;
+    }
+
+    w.usize(program.funcs.len())?;
+    for func_info in program.funcs.iter() {
+        w.usize(func_info.start_addr)?;
+        w.usize(func_info.arg_count)?;
+        w.usize(func_info.ret_count)?;
+        w.usize(func_info.stack_size)?;
+    }
+
+    w.usize(program.exception_handlers.len())?;
+    for handlers in program.exception_handlers.iter() {
+        w.usize(handlers.len())?;
+        for handler in handlers {
+            w.usize(handler.try_start)?;
+            w.usize(handler.try_end)?;
+            w.usize(handler.catch_addr)?;
+            w.usize(handler.exception_value)?;
+            match handler.exception {
+                None => w.u8(0),
+                Some(type_id) => {
+                    let name = registry.exception_name(type_id)
+                        .or_else(|| builtin_exception_name(type_id))
+                        .ok_or(ModuleError::UnnamedException)?;
+                    w.u8(1);
+                    w.str(name)?;
+                }
+            }
+        }
+    }
+
+    w.usize(program.inscs.len())?;
+    for insc in program.inscs.iter() {
+        encode_insc(&mut w, insc)?;
+    }
+
+    let payload = w.bytes;
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(&MODULE_MAGIC);
+    bytes.extend_from_slice(&MODULE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+fn encode_insc(w: &mut Writer, insc: &Insc) -> Result<(), ModuleError> {
+    if let Some((opcode, lhs_value, rhs_value, dest_value)) = binary_insc_parts(insc) {
+        w.u8(opcode);
+        w.usize(lhs_value)?;
+        w.usize(rhs_value)?;
+        return w.usize(dest_value);
+    }
+    if let Some((opcode, value, dest_value)) = unary_insc_parts(insc) {
+        w.u8(opcode);
+        w.usize(value)?;
+        return w.usize(dest_value);
+    }
+
+    match insc {
+        Insc::MakeIntConst { c, dest_value } => {
+            w.u8(OP_MAKE_INT_CONST);
+            w.u64(*c as u64);
+            w.usize(*dest_value)?;
+        },
+        Insc::MakeFloatConst { c, dest_value } => {
+            w.u8(OP_MAKE_FLOAT_CONST);
+            w.u64(c.to_bits());
+            w.usize(*dest_value)?;
+        },
+        Insc::MakeCharConst { c, dest_value } => {
+            w.u8(OP_MAKE_CHAR_CONST);
+            w.u32(*c as u32);
+            w.usize(*dest_value)?;
+        },
+        Insc::MakeBoolConst { c, dest_value } => {
+            w.u8(OP_MAKE_BOOL_CONST);
+            w.u8(*c as u8);
+            w.usize(*dest_value)?;
+        },
+        Insc::Incr { value } => {
+            w.u8(OP_INCR);
+            w.usize(*value)?;
+        },
+        Insc::JumpIfTrue { cond_value, jump_dest } => {
+            w.u8(OP_JUMP_IF_TRUE);
+            w.usize(*cond_value)?;
+            w.usize(*jump_dest)?;
+        },
+        Insc::Jump { jump_dest } => {
+            w.u8(OP_JUMP);
+            w.usize(*jump_dest)?;
+        },
+        Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
+            w.u8(OP_FUNC_CALL);
+            w.usize(*func_id)?;
+            w.usizes(arg_values)?;
+            w.usizes(ret_value_locs)?;
+        },
+        Insc::TailCall { func_id, arg_values } => {
+            w.u8(OP_TAIL_CALL);
+            w.usize(*func_id)?;
+            w.usizes(arg_values)?;
+        },
+        Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+            w.u8(OP_FFI_CALL);
+            w.usize(*func_id)?;
+            w.usizes(arg_values)?;
+            w.usizes(ret_value_locs)?;
+        },
+        Insc::ReturnOne { ret_value } => {
+            w.u8(OP_RETURN_ONE);
+            w.usize(*ret_value)?;
+        },
+        Insc::ReturnMultiple { ret_values } => {
+            w.u8(OP_RETURN_MULTIPLE);
+            w.usizes(ret_values)?;
+        },
+        Insc::ReturnNothing => w.u8(OP_RETURN_NOTHING),
+        Insc::Throw { exception_value } => {
+            w.u8(OP_THROW);
+            w.usize(*exception_value)?;
+        },
+        Insc::UnreachableInsc => w.u8(OP_UNREACHABLE_INSC),
+        Insc::TypeChecked { .. } => return Err(ModuleError::Malformed(
+            "TypeChecked insc cannot be encoded".to_string()
+        )),
+        _ => unreachable!()
+    }
+    Ok(())
+}
+
+/// 从二进制模块中加载程序，使用 `registry` 链接宿主函数、解析异常类型，并对加载的程序运行校验器
+pub fn decode_module(bytes: &[u8], registry: &FunctionRegistry)
+    -> Result<CompiledProgram, ModuleError>
+{
+    if bytes.len() < HEADER_SIZE {
+        return Err(ModuleError::Truncated);
+    }
+    if bytes[0..8] != MODULE_MAGIC {
+        return Err(ModuleError::BadMagic);
+    }
+    let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+    if version != MODULE_VERSION {
+        return Err(ModuleError::UnsupportedVersion(version));
+    }
+    if bytes[10..12] != [0, 0] {
+        return Err(ModuleError::Malformed("reserved header bytes are not zero".to_string()));
+    }
+    let payload_len = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(bytes[16..20].try_into().unwrap());
+    let payload = &bytes[HEADER_SIZE..];
+    if payload.len() != payload_len {
+        return Err(ModuleError::Truncated);
+    }
+    let actual_checksum = crc32(payload);
+    if actual_checksum != checksum {
+        return Err(ModuleError::ChecksumMismatch { expected: checksum, got: actual_checksum });
+    }
+
+    let mut r = Reader { bytes: payload, pos: 0 };
+
+    let import_count = r.len()?;
+    let mut ffi_imports = Vec::with_capacity(import_count);
+    for _ in 0..import_count {
+        let name = r.str()?;
+        let params = r.tyck_infos()?;
+        let rets = r.tyck_infos()?;
+        let (params, rets) = if params.iter().chain(&rets).any(Option::is_none) {
+            let func = registry.get_by_name(&name)
+                .ok_or_else(|| ModuleError::Link(LinkError::UnresolvedFunction(name.clone())))?;
+            (resolve_object_types(&name, params, &func.param_specs, false)?,
+             resolve_object_types(&name, rets, &func.return_value_spec, true)?)
+        } else {
+            (params.into_iter().flatten().collect(), rets.into_iter().flatten().collect())
+        };
+        ffi_imports.push(FFIImport::new(name, params, rets));
+    }
+
+    let func_count = r.len()?;
+    let mut funcs = Vec::with_capacity(func_count);
+    for _ in 0..func_count {
+        funcs.push(CompiledFuncInfo::new(r.usize()?, r.usize()?, r.usize()?, r.usize()?));
+    }
+
+    let handler_table_count = r.len()?;
+    let mut exception_handlers = Vec::with_capacity(handler_table_count);
+    for _ in 0..handler_table_count {
+        let handler_count = r.len()?;
+        let mut handlers = Vec::with_capacity(handler_count);
+        for _ in 0..handler_count {
+            let (try_start, try_end, catch_addr, exception_value) =
+                (r.usize()?, r.usize()?, r.usize()?, r.usize()?);
+            let exception = match r.u8()? {
+                0 => None,
+                1 => {
+                    let name = r.str()?;
+                    let type_id = registry.exception_type(&name)
+                        .or_else(|| builtin_exception_type(&name))
+                        .ok_or(ModuleError::UnknownException(name))?;
+                    Some(type_id)
+                },
+                tag => return Err(ModuleError::Malformed(format!("unknown exception tag {}", tag)))
+            };
+            handlers.push(ExceptionHandler::new(
+                try_start, try_end, exception, catch_addr, exception_value
+            ));
+        }
+        exception_handlers.push(handlers);
+    }
+
+    let insc_count = r.len()?;
+    let mut inscs = Vec::with_capacity(insc_count);
+    for _ in 0..insc_count {
+        inscs.push(decode_insc(&mut r)?);
+    }
+
+    if r.pos != payload.len() {
+        return Err(ModuleError::Malformed("trailing data after instructions".to_string()));
+    }
+
+    let mut program = CompiledProgram::link(inscs, funcs, ffi_imports, registry)
+        .map_err(ModuleError::Link)?;
+    program.exception_handlers = exception_handlers;
+    verify(&program).map_err(ModuleError::Verify)?;
+    Ok(program)
+}
+
+fn decode_insc(r: &mut Reader) -> Result<Insc, ModuleError> {
+    let opcode = r.u8()?;
+    if make_binary_insc(opcode, 0, 0, 0).is_some() {
+        let (lhs_value, rhs_value, dest_value) = (r.usize()?, r.usize()?, r.usize()?);
+        return Ok(make_binary_insc(opcode, lhs_value, rhs_value, dest_value).unwrap());
+    }
+    if make_unary_insc(opcode, 0, 0).is_some() {
+        let (value, dest_value) = (r.usize()?, r.usize()?);
+        return Ok(make_unary_insc(opcode, value, dest_value).unwrap());
+    }
+
+    let insc = match opcode {
+        OP_MAKE_INT_CONST => Insc::MakeIntConst { c: r.u64()? as i64, dest_value: r.usize()? },
+        OP_MAKE_FLOAT_CONST =>
+            Insc::MakeFloatConst { c: f64::from_bits(r.u64()?), dest_value: r.usize()? },
+        OP_MAKE_CHAR_CONST => {
+            let c = char::from_u32(r.u32()?)
+                .ok_or_else(|| ModuleError::Malformed("invalid char constant".to_string()))?;
+            Insc::MakeCharConst { c, dest_value: r.usize()? }
+        },
+        OP_MAKE_BOOL_CONST => {
+            let c = match r.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(ModuleError::Malformed("invalid bool constant".to_string()))
+            };
+            Insc::MakeBoolConst { c, dest_value: r.usize()? }
+        },
+        OP_INCR => Insc::Incr { value: r.usize()? },
+        OP_JUMP_IF_TRUE => Insc::JumpIfTrue { cond_value: r.usize()?, jump_dest: r.usize()? },
+        OP_JUMP => Insc::Jump { jump_dest: r.usize()? },
+        OP_FUNC_CALL => Insc::FuncCall {
+            func_id: r.usize()?,
+            arg_values: r.usizes()?,
+            ret_value_locs: r.usizes()?
+        },
+        OP_TAIL_CALL => Insc::TailCall { func_id: r.usize()?, arg_values: r.usizes()? },
+        OP_FFI_CALL => Insc::FFICall {
+            func_id: r.usize()?,
+            arg_values: r.usizes()?,
+            ret_value_locs: r.usizes()?
+        },
+        OP_RETURN_ONE => Insc::ReturnOne { ret_value: r.usize()? },
+        OP_RETURN_MULTIPLE => Insc::ReturnMultiple { ret_values: r.usizes()? },
+        OP_RETURN_NOTHING => Insc::ReturnNothing,
+        OP_THROW => Insc::Throw { exception_value: r.usize()? },
+        OP_UNREACHABLE_INSC => Insc::UnreachableInsc,
+        _ => return Err(ModuleError::Malformed(format!("unknown opcode {:#04x}", opcode)))
+    };
+    Ok(insc)
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+    use std::marker::PhantomData;
+
+    use crate::error::ModuleError;
+    use crate::func::{RustFunction, RustFunction1};
+    use crate::func::registry::{FFIImport, FunctionRegistry};
+    use crate::turbofan::rd93::asm::assemble;
+    use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, Insc};
+    use crate::turbofan::rd93::module::{crc32, decode_module, encode_module, HEADER_SIZE};
+    use crate::tyck::TypeCheckInfo;
+
+    fn add(x: i64, y: i64) -> i64 {
+        x + y
+    }
+
+    fn registry() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry.register("math::add", RustFunction { f: add, _phantom: PhantomData }).unwrap();
+        registry.register_exception::<std::fmt::Error>("fmt::Error");
+        registry
+    }
+
+    fn program(registry: &FunctionRegistry) -> CompiledProgram {
+        assemble("\
+ffi math::add(int, int) -> (int)
+
+func main(2) -> 1, stack 6
+    handler fmt::Error, try, try_end, catch -> %5
+    handler TypeError, try, try_end, catch -> %5
+    make_float_const -1.5 -> %3
+    make_char_const '字' -> %4
+    make_bool_const true -> %4
+try:
+    make_int_const -42 -> %2
+    int_add %0, %2 -> %2
+    float_neg %3 -> %3
+    ffi_call math::add(%0, %1) -> %2
+    func_call helper(%2)
+try_end:
+    return_one %2
+catch:
+    throw %5
+
+func helper(1) -> 0, stack 1
+    return_nothing
+", registry).unwrap()
+    }
+
+    #[test] fn test_round_trip() {
+        let registry = registry();
+        let program = program(&registry);
+        let bytes = encode_module(&program, &registry).unwrap();
+        let decoded = decode_module(&bytes, &registry).unwrap();
+
+        assert_eq!(format!("{:?}", program.inscs), format!("{:?}", decoded.inscs));
+        assert_eq!(format!("{:?}", program.funcs), format!("{:?}", decoded.funcs));
+        assert_eq!(format!("{:?}", program.exception_handlers),
+                   format!("{:?}", decoded.exception_handlers));
+        assert_eq!(format!("{:?}", program.ffi_imports), format!("{:?}", decoded.ffi_imports));
+        assert_eq!(bytes, encode_module(&decoded, &registry).unwrap());
+    }
+
+    struct Counter(i64);
+
+    fn peek(counter: &Counter) -> i64 {
+        counter.0
+    }
+
+    #[test] fn test_object_import() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("host::peek", RustFunction1 { f: peek, _phantom: PhantomData }).unwrap();
+        let imports = vec![FFIImport::new(
+            "host::peek",
+            vec![TypeCheckInfo::SimpleType(TypeId::of::<Counter>())],
+            vec![TypeCheckInfo::SimpleType(TypeId::of::<i64>())]
+        )];
+        let program = CompiledProgram::link(vec![
+            Insc::FFICall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![0] },
+            Insc::ReturnOne { ret_value: 0 }
+        ], vec![CompiledFuncInfo::new(0, 1, 1, 1)], imports, &registry).unwrap();
+
+        // 对象类型在往返之后仍然保留，链接时的类型检查不会被绕过
+        let bytes = encode_module(&program, &registry).unwrap();
+        let decoded = decode_module(&bytes, &registry).unwrap();
+        assert_eq!(format!("{:?}", program.ffi_imports), format!("{:?}", decoded.ffi_imports));
+
+        let mut other_registry = FunctionRegistry::new();
+        other_registry.register("host::peek", RustFunction1 { f: |x: i64| x, _phantom: PhantomData })
+            .unwrap();
+        assert!(matches!(
+            decode_module(&bytes, &other_registry),
+            Err(ModuleError::ObjectTypeMismatch { index: 0, is_return: false, .. })
+        ));
+    }
+
+    #[test] fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test] fn test_corrupted() {
+        let registry = registry();
+        let bytes = encode_module(&program(&registry), &registry).unwrap();
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert!(matches!(decode_module(&corrupted, &registry), Err(ModuleError::BadMagic)));
+
+        let mut corrupted = bytes.clone();
+        corrupted[8] = 99;
+        assert!(matches!(decode_module(&corrupted, &registry),
+                         Err(ModuleError::UnsupportedVersion(99))));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(decode_module(&corrupted, &registry),
+                         Err(ModuleError::ChecksumMismatch { .. })));
+
+        assert!(matches!(decode_module(&bytes[..bytes.len() - 1], &registry),
+                         Err(ModuleError::Truncated)));
+        assert!(matches!(decode_module(&bytes[..10], &registry), Err(ModuleError::Truncated)));
+    }
+
+    /// 重新计算校验和，模拟校验和正确但内容不合法的模块
+    fn fix_checksum(bytes: &mut [u8]) {
+        let checksum = crc32(&bytes[HEADER_SIZE..]);
+        bytes[16..20].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test] fn test_load_time_validation() {
+        let registry = registry();
+        let program = program(&registry);
+        let bytes = encode_module(&program, &registry).unwrap();
+
+        // 最后一条指令是 `helper` 中的 `return_nothing`，将它改为未知的操作码
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() = 0xff;
+        fix_checksum(&mut corrupted);
+        assert!(matches!(decode_module(&corrupted, &registry), Err(ModuleError::Malformed(_))));
+
+        // 将它改为 `unreachable_insc` 仍然可以通过校验，改为 `incr` 则会缺少操作数
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() = 0x78;
+        fix_checksum(&mut corrupted);
+        assert!(decode_module(&corrupted, &registry).is_ok());
+        *corrupted.last_mut().unwrap() = 0x22;
+        fix_checksum(&mut corrupted);
+        assert!(matches!(decode_module(&corrupted, &registry), Err(ModuleError::Truncated)));
+
+        let mut other_registry = FunctionRegistry::new();
+        other_registry.register_exception::<std::fmt::Error>("fmt::Error");
+        assert!(matches!(decode_module(&bytes, &other_registry), Err(ModuleError::Link(_))));
+
+        let mut other_registry = FunctionRegistry::new();
+        other_registry.register("math::add", RustFunction { f: add, _phantom: PhantomData })
+            .unwrap();
+        assert!(matches!(decode_module(&bytes, &other_registry),
+                         Err(ModuleError::UnknownException(_))));
+
+        // 函数的起始地址越界，由校验器拒绝
+        let mut program = program;
+        program.funcs[1].start_addr = 100;
+        let bytes = encode_module(&program, &registry).unwrap();
+        assert!(matches!(decode_module(&bytes, &registry), Err(ModuleError::Verify(_))));
+    }
+}
@@ -0,0 +1,414 @@
+//! `verify` 模块实现了 `rd93` 字节码的校验器
+//!
+//! `RD93::run_func` 在执行时不做任何边界检查，因此只接受通过校验的 `VerifiedProgram`。校验器检查：
+//!
+//! - 每个函数的起始地址、参数个数与栈帧大小是否合法
+//! - 跳转目标与异常处理器的地址是否位于同一个函数内
+//! - 每条指令访问的栈位置是否小于函数的栈帧大小
+//! - `FuncCall` 的参数、返回值个数是否与被调用函数的 `CompiledFuncInfo` 一致
+//! - `TailCall` 的参数个数是否与被调用函数一致，被调用函数的返回值个数是否与当前函数一致
+//! - `FFICall` 的参数、返回值个数是否与宿主函数的 `param_specs()`、`return_value_spec()` 一致
+//! - 返回指令的返回值个数是否与函数的 `ret_count` 一致
+//! - 从函数入口和异常处理代码出发的每条路径都以返回、`Throw` 或者 `UnreachableInsc` 结束
+//! - 不会读取可能未初始化的栈位置
+//!
+//! 校验通过后，校验器还会进行类型推导，为无法静态证明类型正确的指令插入运行时类型检查，参见
+//! `infer` 模块。
+
+use std::ops::Range;
+
+use crate::data::ValueType;
+use crate::error::{ArgLenError, VerifyError};
+use crate::turbofan::rd93::infer::infer_types;
+use crate::turbofan::rd93::insc::{CompiledProgram, Insc};
+
+/// 通过校验的程序
+///
+/// 除了原程序之外，还保存了插入运行时类型检查之后的指令序列，以及推导出的各个函数的参数类型
+pub struct VerifiedProgram<'a> {
+    program: &'a CompiledProgram,
+    inscs: Vec<Insc>,
+    param_types: Vec<Vec<Option<ValueType>>>
+}
+
+impl<'a> VerifiedProgram<'a> {
+    pub fn program(&self) -> &'a CompiledProgram {
+        self.program
+    }
+
+    /// 实际执行的指令序列
+    pub fn inscs(&self) -> &[Insc] {
+        &self.inscs
+    }
+
+    /// 函数 `func_id` 的参数类型，`None` 表示类型未知
+    pub fn param_types(&self, func_id: usize) -> &[Option<ValueType>] {
+        &self.param_types[func_id]
+    }
+}
+
+/// 校验 `program`，成功时返回可以交给 `RD93::run_func` 执行的 `VerifiedProgram`
+pub fn verify(program: &CompiledProgram) -> Result<VerifiedProgram<'_>, VerifyError> {
+    let func_ranges = func_ranges(program)?;
+    if program.exception_handlers.len() > program.funcs.len() {
+        return Err(VerifyError::BadExceptionHandler {
+            func_id: program.exception_handlers.len() - 1,
+            handler_idx: 0
+        });
+    }
+
+    for (func_id, range) in func_ranges.iter().enumerate() {
+        let func_info = program.funcs[func_id];
+        if func_info.arg_count > func_info.stack_size {
+            return Err(VerifyError::BadArgCount { func_id });
+        }
+
+        for insc_ptr in range.clone() {
+            verify_insc(program, range, func_id, insc_ptr)?;
+        }
+
+        let handlers = program.exception_handlers.get(func_id).map_or(&[][..], Vec::as_slice);
+        for (handler_idx, handler) in handlers.iter().enumerate() {
+            if handler.try_start > handler.try_end
+                || handler.try_start < range.start
+                || handler.try_end > range.end
+                || !range.contains(&handler.catch_addr)
+                || handler.exception_value >= func_info.stack_size
+            {
+                return Err(VerifyError::BadExceptionHandler { func_id, handler_idx });
+            }
+        }
+
+        verify_control_flow(program, range, func_id)?;
+    }
+
+    let type_info = infer_types(program, &func_ranges)?;
+    Ok(VerifiedProgram {
+        program,
+        inscs: type_info.inscs,
+        param_types: type_info.param_types
+    })
+}
+
+/// 计算每个函数的指令范围。一个函数的范围从它的起始地址开始，到下一个起始地址更大的函数的起始
+/// 地址（或者程序末尾）为止
+fn func_ranges(program: &CompiledProgram) -> Result<Vec<Range<usize>>, VerifyError> {
+    let mut start_addrs = Vec::with_capacity(program.funcs.len());
+    for (func_id, func_info) in program.funcs.iter().enumerate() {
+        if func_info.start_addr >= program.inscs.len() {
+            return Err(VerifyError::BadFuncStart { func_id });
+        }
+        start_addrs.push(func_info.start_addr);
+    }
+    start_addrs.sort_unstable();
+    start_addrs.dedup();
+
+    Ok(program.funcs.iter().map(|func_info| {
+        let idx = start_addrs.binary_search(&func_info.start_addr).unwrap();
+        let end = start_addrs.get(idx + 1).copied().unwrap_or(program.inscs.len());
+        func_info.start_addr..end
+    }).collect())
+}
+
+fn verify_insc(
+    program: &CompiledProgram,
+    range: &Range<usize>,
+    func_id: usize,
+    insc_ptr: usize
+) -> Result<(), VerifyError> {
+    let func_info = program.funcs[func_id];
+    let check_slot = |index: usize| {
+        if index < func_info.stack_size {
+            Ok(())
+        } else {
+            Err(VerifyError::BadStackIndex { insc_ptr, index, stack_size: func_info.stack_size })
+        }
+    };
+    let check_jump = |jump_dest: usize| {
+        if range.contains(&jump_dest) {
+            Ok(())
+        } else {
+            Err(VerifyError::BadJumpTarget { insc_ptr, jump_dest })
+        }
+    };
+    let check_len = |expected: usize, got: usize, is_ret: bool| {
+        if expected == got {
+            Ok(())
+        } else if is_ret {
+            Err(VerifyError::RetCountMismatch { insc_ptr, error: ArgLenError::new(expected, got) })
+        } else {
+            Err(VerifyError::ArgCountMismatch { insc_ptr, error: ArgLenError::new(expected, got) })
+        }
+    };
+
+    match &program.inscs[insc_ptr] {
+        Insc::MakeIntConst { dest_value, .. }
+        | Insc::MakeFloatConst { dest_value, .. }
+        | Insc::MakeCharConst { dest_value, .. }
+        | Insc::MakeBoolConst { dest_value, .. } => check_slot(*dest_value)?,
+        Insc::IntAdd { lhs_value, rhs_value, dest_value }
+        | Insc::IntSub { lhs_value, rhs_value, dest_value }
+        | Insc::IntMul { lhs_value, rhs_value, dest_value }
+        | Insc::IntDiv { lhs_value, rhs_value, dest_value }
+        | Insc::IntMod { lhs_value, rhs_value, dest_value }
+        | Insc::IntBitAnd { lhs_value, rhs_value, dest_value }
+        | Insc::IntBitOr { lhs_value, rhs_value, dest_value }
+        | Insc::IntBitXor { lhs_value, rhs_value, dest_value }
+        | Insc::IntShl { lhs_value, rhs_value, dest_value }
+        | Insc::IntShr { lhs_value, rhs_value, dest_value }
+        | Insc::IntEq { lhs_value, rhs_value, dest_value }
+        | Insc::IntNe { lhs_value, rhs_value, dest_value }
+        | Insc::IntLt { lhs_value, rhs_value, dest_value }
+        | Insc::IntLe { lhs_value, rhs_value, dest_value }
+        | Insc::IntGt { lhs_value, rhs_value, dest_value }
+        | Insc::IntGe { lhs_value, rhs_value, dest_value }
+        | Insc::FloatAdd { lhs_value, rhs_value, dest_value }
+        | Insc::FloatSub { lhs_value, rhs_value, dest_value }
+        | Insc::FloatMul { lhs_value, rhs_value, dest_value }
+        | Insc::FloatDiv { lhs_value, rhs_value, dest_value }
+        | Insc::FloatEq { lhs_value, rhs_value, dest_value }
+        | Insc::FloatNe { lhs_value, rhs_value, dest_value }
+        | Insc::FloatLt { lhs_value, rhs_value, dest_value }
+        | Insc::FloatLe { lhs_value, rhs_value, dest_value }
+        | Insc::FloatGt { lhs_value, rhs_value, dest_value }
+        | Insc::FloatGe { lhs_value, rhs_value, dest_value }
+        | Insc::CharEq { lhs_value, rhs_value, dest_value }
+        | Insc::CharNe { lhs_value, rhs_value, dest_value }
+        | Insc::CharLt { lhs_value, rhs_value, dest_value }
+        | Insc::CharLe { lhs_value, rhs_value, dest_value }
+        | Insc::CharGt { lhs_value, rhs_value, dest_value }
+        | Insc::CharGe { lhs_value, rhs_value, dest_value }
+        | Insc::BoolAnd { lhs_value, rhs_value, dest_value }
+        | Insc::BoolOr { lhs_value, rhs_value, dest_value } => {
+            check_slot(*lhs_value)?;
+            check_slot(*rhs_value)?;
+            check_slot(*dest_value)?;
+        },
+        Insc::IntNeg { value, dest_value }
+        | Insc::IntBitNot { value, dest_value }
+        | Insc::FloatNeg { value, dest_value }
+        | Insc::IntToFloat { value, dest_value }
+        | Insc::FloatToInt { value, dest_value }
+        | Insc::BoolNot { value, dest_value } => {
+            check_slot(*value)?;
+            check_slot(*dest_value)?;
+        },
+        Insc::Incr { value } => check_slot(*value)?,
+        Insc::JumpIfTrue { cond_value, jump_dest } => {
+            check_slot(*cond_value)?;
+            check_jump(*jump_dest)?;
+        },
+        Insc::Jump { jump_dest } => check_jump(*jump_dest)?,
+        Insc::FuncCall { func_id, arg_values, ret_value_locs } => {
+            let callee = program.funcs.get(*func_id)
+                .ok_or(VerifyError::BadFuncId { insc_ptr, func_id: *func_id })?;
+            check_len(callee.arg_count, arg_values.len(), false)?;
+            check_len(callee.ret_count, ret_value_locs.len(), true)?;
+            arg_values.iter().chain(ret_value_locs).try_for_each(|index| check_slot(*index))?;
+        },
+        Insc::TailCall { func_id, arg_values } => {
+            let callee = program.funcs.get(*func_id)
+                .ok_or(VerifyError::BadFuncId { insc_ptr, func_id: *func_id })?;
+            check_len(callee.arg_count, arg_values.len(), false)?;
+            check_len(func_info.ret_count, callee.ret_count, true)?;
+            arg_values.iter().try_for_each(|index| check_slot(*index))?;
+        },
+        Insc::FFICall { func_id, arg_values, ret_value_locs } => {
+            let callee = program.ffi_funcs.get(*func_id)
+                .ok_or(VerifyError::BadFFIFuncId { insc_ptr, func_id: *func_id })?;
+            check_len(callee.param_specs().len(), arg_values.len(), false)?;
+            check_len(callee.return_value_spec().len(), ret_value_locs.len(), true)?;
+            arg_values.iter().chain(ret_value_locs).try_for_each(|index| check_slot(*index))?;
+        },
+        Insc::ReturnOne { ret_value } => {
+            check_len(func_info.ret_count, 1, true)?;
+            check_slot(*ret_value)?;
+        },
+        Insc::ReturnMultiple { ret_values } => {
+            check_len(func_info.ret_count, ret_values.len(), true)?;
+            ret_values.iter().try_for_each(|index| check_slot(*index))?;
+        },
+        Insc::ReturnNothing => check_len(func_info.ret_count, 0, true)?,
+        Insc::Throw { exception_value } => check_slot(*exception_value)?,
+        Insc::UnreachableInsc => {},
+        Insc::TypeChecked { .. } => return Err(VerifyError::BadInsc { insc_ptr })
+    }
+    Ok(())
+}
+
+/// 检查从函数入口和异常处理代码出发的每条路径都不会越过函数末尾
+fn verify_control_flow(
+    program: &CompiledProgram,
+    range: &Range<usize>,
+    func_id: usize
+) -> Result<(), VerifyError> {
+    let mut visited = vec![false; range.len()];
+    let mut worklist = vec![range.start];
+    if let Some(handlers) = program.exception_handlers.get(func_id) {
+        worklist.extend(handlers.iter().map(|handler| handler.catch_addr));
+    }
+
+    while let Some(insc_ptr) = worklist.pop() {
+        if visited[insc_ptr - range.start] {
+            continue;
+        }
+        visited[insc_ptr - range.start] = true;
+
+        let falls_through = match &program.inscs[insc_ptr] {
+            Insc::Jump { jump_dest } => {
+                worklist.push(*jump_dest);
+                false
+            },
+            Insc::JumpIfTrue { jump_dest, .. } => {
+                worklist.push(*jump_dest);
+                true
+            },
+            Insc::TailCall { .. }
+            | Insc::ReturnOne { .. }
+            | Insc::ReturnMultiple { .. }
+            | Insc::ReturnNothing
+            | Insc::Throw { .. }
+            | Insc::UnreachableInsc => false,
+            _ => true
+        };
+        if falls_through {
+            if insc_ptr + 1 >= range.end {
+                return Err(VerifyError::FallThrough { func_id, insc_ptr });
+            }
+            worklist.push(insc_ptr + 1);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::VerifyError;
+    use crate::turbofan::rd93::insc::{CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc};
+    use crate::turbofan::rd93::verify::verify;
+
+    fn make_program(inscs: Vec<Insc>, funcs: Vec<CompiledFuncInfo>) -> CompiledProgram {
+        CompiledProgram::new(inscs, funcs, vec![])
+    }
+
+    #[test] fn test_verify_ok() {
+        let program = make_program(vec![
+            /*00*/ Insc::MakeIntConst { c: 0, dest_value: 1 },
+            /*01*/ Insc::IntLt { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+            /*02*/ Insc::JumpIfTrue { cond_value: 2, jump_dest: 4 },
+            /*03*/ Insc::Jump { jump_dest: 5 },
+            /*04*/ Insc::IntNeg { value: 0, dest_value: 0 },
+            /*05*/ Insc::FuncCall { func_id: 1, arg_values: vec![0], ret_value_locs: vec![] },
+            /*06*/ Insc::ReturnOne { ret_value: 0 },
+            /*07*/ Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 1, 3),
+            CompiledFuncInfo::new(7, 1, 0, 1)
+        ]);
+        assert!(verify(&program).is_ok());
+    }
+
+    #[test] fn test_verify_bad_jump() {
+        let program = make_program(vec![
+            Insc::Jump { jump_dest: 2 },
+            Insc::ReturnNothing,
+            Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 0, 0, 0),
+            CompiledFuncInfo::new(2, 0, 0, 0)
+        ]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::BadJumpTarget { insc_ptr: 0, jump_dest: 2 })));
+    }
+
+    #[test] fn test_verify_bad_stack_index() {
+        let program = make_program(vec![
+            Insc::IntAdd { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+            Insc::ReturnOne { ret_value: 2 }
+        ], vec![
+            CompiledFuncInfo::new(0, 2, 1, 2)
+        ]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::BadStackIndex { insc_ptr: 0, index: 2, .. })));
+    }
+
+    #[test] fn test_verify_call_mismatch() {
+        let program = make_program(vec![
+            Insc::FuncCall { func_id: 1, arg_values: vec![0, 0], ret_value_locs: vec![0] },
+            Insc::ReturnNothing,
+            Insc::ReturnOne { ret_value: 0 }
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 0, 1),
+            CompiledFuncInfo::new(2, 1, 1, 1)
+        ]);
+        assert!(matches!(verify(&program), Err(VerifyError::ArgCountMismatch { insc_ptr: 0, .. })));
+
+        let program = make_program(vec![
+            Insc::FuncCall { func_id: 2, arg_values: vec![], ret_value_locs: vec![] },
+            Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 0, 0, 0)
+        ]);
+        assert!(matches!(verify(&program), Err(VerifyError::BadFuncId { func_id: 2, .. })));
+    }
+
+    #[test] fn test_verify_tail_call_mismatch() {
+        // 被调用函数的返回值个数必须与当前函数相同
+        let program = make_program(vec![
+            Insc::TailCall { func_id: 1, arg_values: vec![0] },
+            Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 1, 1),
+            CompiledFuncInfo::new(1, 1, 0, 1)
+        ]);
+        assert!(matches!(verify(&program), Err(VerifyError::RetCountMismatch { insc_ptr: 0, .. })));
+
+        // 尾调用之后不会继续执行下一条指令
+        let program = make_program(vec![
+            Insc::TailCall { func_id: 0, arg_values: vec![0] }
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 0, 1)
+        ]);
+        assert!(verify(&program).is_ok());
+    }
+
+    #[test] fn test_verify_ret_mismatch() {
+        let program = make_program(vec![
+            Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 1, 1)
+        ]);
+        assert!(matches!(verify(&program), Err(VerifyError::RetCountMismatch { insc_ptr: 0, .. })));
+    }
+
+    #[test] fn test_verify_fall_through() {
+        let program = make_program(vec![
+            /*00*/ Insc::JumpIfTrue { cond_value: 0, jump_dest: 2 },
+            /*01*/ Insc::ReturnNothing,
+            /*02*/ Insc::BoolNot { value: 0, dest_value: 0 },
+            /*03*/ Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 1, 0, 1),
+            CompiledFuncInfo::new(3, 0, 0, 0)
+        ]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::FallThrough { func_id: 0, insc_ptr: 2 })));
+    }
+
+    #[test] fn test_verify_bad_exception_handler() {
+        let mut program = make_program(vec![
+            Insc::ReturnNothing,
+            Insc::ReturnNothing
+        ], vec![
+            CompiledFuncInfo::new(0, 0, 0, 1)
+        ]);
+        program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(0, 1, 1, 0)]);
+        assert!(verify(&program).is_ok());
+        program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(0, 1, 2, 0)]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::BadExceptionHandler { func_id: 0, handler_idx: 0 })));
+        program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(0, 1, 1, 1)]);
+        assert!(matches!(verify(&program),
+                         Err(VerifyError::BadExceptionHandler { func_id: 0, handler_idx: 0 })));
+    }
+}
@@ -1,6 +1,7 @@
 use std::mem::MaybeUninit;
 
 use crate::data::Value;
+use crate::error::StackOverflow;
 
 #[derive(Copy, Clone)]
 pub struct StackSlice(*mut [MaybeUninit<Value>]);
@@ -39,135 +40,408 @@ impl StackSlice {
     }
 }
 
-#[derive(Debug)]
-pub struct FrameInfo<'a> {
+#[derive(Copy, Clone, Debug)]
+pub struct FrameInfo {
+    /// 栈帧所在的段
+    pub segment: usize,
+    /// 栈帧在段内的起止位置
     pub frame_start: usize,
     pub frame_end: usize,
-    pub ret_value_locs: &'a [usize],
-    pub ret_addr: usize
+    /// 指向程序中调用指令的返回值位置列表。程序在栈帧存在期间必须保持存活
+    pub ret_value_locs: *const [usize],
+    pub ret_addr: usize,
+    /// 栈帧所属的函数，异常展开时用来查找异常处理器
+    pub func_id: usize
 }
 
-impl<'a> FrameInfo<'a> {
+impl FrameInfo {
     pub fn new(
-        frame_start: usize, frame_end: usize, ret_value_locs: &'a [usize], ret_addr: usize
+        segment: usize,
+        frame_start: usize,
+        frame_end: usize,
+        ret_value_locs: *const [usize],
+        ret_addr: usize,
+        func_id: usize
     ) -> Self {
         Self {
+            segment,
             frame_start,
             frame_end,
             ret_value_locs,
-            ret_addr
+            ret_addr,
+            func_id
         }
     }
+
+    pub fn size(&self) -> usize {
+        self.frame_end - self.frame_start
+    }
+}
+
+/// 调用栈的大小限制
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StackConfig {
+    /// 栈帧个数的上限
+    pub max_depth: usize,
+    /// 所有栈帧加起来的槽位个数上限
+    pub max_slots: usize,
+    /// 宿主函数回调 VM 的嵌套层数上限。每一层嵌套调用都会占用宿主一侧的栈空间
+    pub max_nesting: usize
 }
 
-pub struct Stack<'a> {
-    pub values: Vec<MaybeUninit<Value>>,
-    pub frames: Vec<FrameInfo<'a>>
+impl StackConfig {
+    pub fn new(max_depth: usize, max_slots: usize) -> Self {
+        Self { max_depth, max_slots, max_nesting: 64 }
+    }
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        Self::new(65536, 1 << 20)
+    }
+}
+
+/// 第一个段的最小大小，之后的段依次翻倍
+const MIN_SEGMENT_SIZE: usize = 64;
+
+/// VM 的调用栈
+///
+/// 栈上的值分段存放，每个栈帧完整地位于某一个段内。段一经分配就不会移动，因此栈帧存在期间，
+/// 它的 `StackSlice` 始终有效，即使栈在此期间继续增长——例如宿主函数回调 VM 时，嵌套调用的栈帧
+/// 压在宿主函数调用者的栈帧之上。
+///
+/// 同一个 `Stack` 可以在多次 `run_func` 之间复用，已经分配的段不会被释放。
+pub struct Stack {
+    segments: Vec<Box<[MaybeUninit<Value>]>>,
+    pub frames: Vec<FrameInfo>,
+    config: StackConfig,
+    /// 所有栈帧占用的槽位个数
+    slot_count: usize,
+    /// 每一层从宿主发起的调用的最外层栈帧下标，最后一个元素对应正在执行的调用
+    ext_call_bases: Vec<usize>,
+    /// 尾调用时暂存参数，避免参数在复制过程中互相覆盖
+    tail_call_args: Vec<Value>
 }
 
-impl<'a> Stack<'a> {
+impl Stack {
     pub fn new() -> Self {
+        Self::with_config(StackConfig::default())
+    }
+
+    pub fn with_config(config: StackConfig) -> Self {
         Self {
-            values: Vec::with_capacity(64),
-            frames: Vec::with_capacity(4)
+            segments: Vec::new(),
+            frames: Vec::with_capacity(4),
+            config,
+            slot_count: 0,
+            ext_call_bases: Vec::new(),
+            tail_call_args: Vec::with_capacity(8)
         }
     }
 
+    pub fn config(&self) -> StackConfig {
+        self.config
+    }
+
+    /// 当前的栈帧个数，包括所有嵌套调用的栈帧
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// 所有栈帧占用的槽位个数
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+
+    /// 清空栈上的所有栈帧，保留已经分配的空间
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.ext_call_bases.clear();
+        self.slot_count = 0;
+    }
+
+    /// 返回正在执行的调用中各个栈帧的返回地址，不包括最外层的栈帧
+    pub fn ret_addrs(&self) -> Vec<usize> {
+        self.frames.iter().skip(self.base_frame() + 1).map(|frame| frame.ret_addr).collect()
+    }
+
+    #[inline(always)] fn base_frame(&self) -> usize {
+        self.ext_call_bases.last().copied().unwrap_or(0)
+    }
+
+    #[inline(always)] fn frame_slice(&mut self, frame: FrameInfo) -> StackSlice {
+        StackSlice(
+            &mut self.segments[frame.segment][frame.frame_start..frame.frame_end]
+                as *mut [MaybeUninit<Value>]
+        )
+    }
+
+    /// 在 `segment` 段的 `start` 位置之后找到能放下 `frame_size` 个槽位的位置，必要时分配新的段
+    fn place_frame(&mut self, segment: usize, start: usize, frame_size: usize) -> (usize, usize) {
+        if let Some(seg) = self.segments.get(segment) {
+            if start + frame_size <= seg.len() {
+                return (segment, start);
+            }
+        }
+
+        // 当前段放不下，使用下一个段。下一个段及其之后的段上没有存活的栈帧，可以直接替换
+        let next = if self.segments.is_empty() { 0 } else { segment + 1 };
+        if self.segments.get(next).is_none_or(|seg| seg.len() < frame_size) {
+            let prev_size = next.checked_sub(1).map_or(0, |prev| self.segments[prev].len());
+            let size = (prev_size * 2).max(frame_size).max(MIN_SEGMENT_SIZE);
+            let seg = std::iter::repeat_with(MaybeUninit::uninit).take(size).collect();
+            if next < self.segments.len() {
+                self.segments[next] = seg;
+            } else {
+                self.segments.push(seg);
+            }
+        }
+        (next, 0)
+    }
+
+    /// 新栈帧的位置：紧接在最上层栈帧之后
+    #[inline(always)] fn next_frame_pos(&self) -> (usize, usize) {
+        self.frames.last().map_or((0, 0), |frame| (frame.segment, frame.frame_end))
+    }
+
+    #[inline(always)] fn check_limits(&self, frame_size: usize) -> Result<(), StackOverflow> {
+        if self.frames.len() >= self.config.max_depth
+            || self.slot_count + frame_size > self.config.max_slots
+        {
+            Err(stack_overflow(&self.config))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 从宿主调用 VM 中的函数时，在栈顶创建这一次调用的最外层栈帧并复制参数
+    ///
+    /// 栈上已有的栈帧属于正在等待宿主函数返回的外层调用，不受影响。调用结束之后，无论成功与否，
+    /// 都必须调用 `ext_func_call_shrink_stack`
+    ///
+    /// # Safety
+    /// `args` 的长度不能超过 `frame_size`
     pub unsafe fn ext_func_call_grow_stack(
         &mut self,
+        func_id: usize,
         frame_size: usize,
         args: &[Value],
-        dummy_ret_value_locs: &'a [usize]
-    ) -> StackSlice {
-        debug_assert_eq!(self.values.len(), 0);
-        debug_assert_eq!(self.frames.len(), 0);
+        dummy_ret_value_locs: &[usize]
+    ) -> Result<StackSlice, StackOverflow> {
+        self.check_limits(frame_size)?;
+        if self.ext_call_bases.len() >= self.config.max_nesting {
+            return Err(stack_overflow(&self.config));
+        }
+
+        let (segment, start) = self.next_frame_pos();
+        let (segment, start) = self.place_frame(segment, start, frame_size);
+        let frame = FrameInfo::new(segment, start, start + frame_size, dummy_ret_value_locs, 0,
+                                   func_id);
+        self.ext_call_bases.push(self.frames.len());
+        self.frames.push(frame);
+        self.slot_count += frame_size;
 
-        self.values.resize(frame_size, MaybeUninit::uninit());
+        let mut slice = self.frame_slice(frame);
         for (i, arg) in args.iter().enumerate() {
-            self.values.get_unchecked_mut(i).write(*arg);
+            slice.set_value(i, *arg);
         }
-        self.frames.push(FrameInfo::new(0, frame_size, dummy_ret_value_locs, 0));
-        StackSlice(&mut self.values[..] as *mut [MaybeUninit<Value>])
+        Ok(slice)
     }
 
+    /// 结束从宿主发起的调用，弹出这一次调用留在栈上的所有栈帧
+    pub fn ext_func_call_shrink_stack(&mut self) {
+        if let Some(base) = self.ext_call_bases.pop() {
+            for frame in self.frames.drain(base..) {
+                self.slot_count -= frame.size();
+            }
+        }
+    }
+
+    /// 创建被调用函数的栈帧并复制参数，返回新栈帧的 `StackSlice`
+    ///
+    /// 超出栈的大小限制时返回 `StackOverflow`，此时栈保持不变
+    ///
+    /// # Safety
+    /// 栈上至少要有一个栈帧，`arg_locs` 中的位置都位于当前栈帧内并且已经初始化
     pub unsafe fn func_call_grow_stack(
         &mut self,
+        func_id: usize,
         frame_size: usize,
         arg_locs: &[usize],
-        ret_value_locs: &'a [usize],
+        ret_value_locs: &[usize],
         ret_addr: usize
-    ) -> StackSlice {
-        let this_frame = self.frames.last().unwrap_unchecked();
-        let (this_frame_start, this_frame_end) = (this_frame.frame_start, this_frame.frame_end);
-
-        debug_assert_eq!(this_frame_end, self.values.len());
-        let new_frame_end = this_frame_end + frame_size;
-        self.values.resize(new_frame_end, MaybeUninit::uninit());
-        self.frames.push(FrameInfo::new(this_frame_end, new_frame_end, ret_value_locs, ret_addr));
-        let mut old_slice = StackSlice(
-            &mut self.values[this_frame_start..this_frame_end] as *mut [MaybeUninit<Value>]
-        );
-        let mut new_slice = StackSlice(
-            &mut self.values[this_frame_end..new_frame_end] as *mut [MaybeUninit<Value>]
-        );
+    ) -> Result<StackSlice, StackOverflow> {
+        self.check_limits(frame_size)?;
+
+        let this_frame = *self.frames.last().unwrap_unchecked();
+        let (segment, start) =
+            self.place_frame(this_frame.segment, this_frame.frame_end, frame_size);
+        let new_frame =
+            FrameInfo::new(segment, start, start + frame_size, ret_value_locs, ret_addr, func_id);
+        self.frames.push(new_frame);
+        self.slot_count += frame_size;
+
+        let mut old_slice = self.frame_slice(this_frame);
+        let mut new_slice = self.frame_slice(new_frame);
         for (i, arg_loc) in arg_locs.iter().enumerate() {
             new_slice.set_value(i, old_slice.get_value(*arg_loc));
         }
-        new_slice
+        Ok(new_slice)
     }
 
-    pub unsafe fn done_func_call_shrink_stack(
+    /// 尾调用时复用当前栈帧：将参数复制到栈帧开头，并将栈帧大小调整为 `frame_size`。栈帧的
+    /// 返回值位置和返回地址保持不变。返回调整之后的 `StackSlice`
+    ///
+    /// 超出栈的大小限制时返回 `StackOverflow`，此时栈保持不变
+    ///
+    /// # Safety
+    /// 栈上至少要有一个栈帧，`arg_locs` 中的位置都位于当前栈帧内并且已经初始化
+    pub unsafe fn tail_call_reuse_frame(
         &mut self,
-        ret_values: &[usize]
-    ) -> Option<(StackSlice, usize)> {
+        func_id: usize,
+        frame_size: usize,
+        arg_locs: &[usize]
+    ) -> Result<StackSlice, StackOverflow> {
+        let this_frame = *self.frames.last().unwrap_unchecked();
+        let slot_count = self.slot_count - this_frame.size() + frame_size;
+        if slot_count > self.config.max_slots {
+            return Err(stack_overflow(&self.config));
+        }
+
+        let mut old_slice = self.frame_slice(this_frame);
+        for arg_loc in arg_locs {
+            self.tail_call_args.push(old_slice.get_value(*arg_loc));
+        }
+        // 当前段放不下更大的栈帧时，栈帧移动到下一个段
+        let (segment, start) =
+            self.place_frame(this_frame.segment, this_frame.frame_start, frame_size);
+        let new_frame = FrameInfo {
+            segment,
+            frame_start: start,
+            frame_end: start + frame_size,
+            func_id,
+            ..this_frame
+        };
+        *self.frames.last_mut().unwrap_unchecked() = new_frame;
+        self.slot_count = slot_count;
+
+        let mut new_slice = self.frame_slice(new_frame);
+        for (i, arg) in self.tail_call_args.drain(..).enumerate() {
+            new_slice.set_value(i, arg);
+        }
+        Ok(new_slice)
+    }
+
+    /// 弹出最上层的栈帧，返回被弹出的栈帧和上一层栈帧。最上层的栈帧是当前调用的最外层栈帧时
+    /// 返回 `None`
+    #[inline(always)] unsafe fn pop_frame(&mut self) -> Option<(FrameInfo, FrameInfo)> {
         let frame_count = self.frames.len();
-        if frame_count == 1 {
+        if frame_count == self.base_frame() + 1 {
             return None;
         }
 
-        let this_frame: &FrameInfo = self.frames.get_unchecked(frame_count - 1);
-        let prev_frame: &FrameInfo = self.frames.get_unchecked(frame_count - 2);
-        debug_assert_eq!(prev_frame.frame_end, this_frame.frame_start);
-        let mut this_slice =
-            StackSlice(&mut self.values[this_frame.frame_start..this_frame.frame_end]);
-        let mut prev_slice =
-            StackSlice(&mut self.values[prev_frame.frame_start..prev_frame.frame_end]);
+        let this_frame = self.frames.pop().unwrap_unchecked();
+        self.slot_count -= this_frame.size();
+        Some((this_frame, *self.frames.last().unwrap_unchecked()))
+    }
+
+    /// 函数返回时将 `ret_values` 复制到调用者的栈帧中并弹出当前栈帧。返回调用者栈帧的
+    /// `StackSlice` 及返回地址；当前栈帧是当前调用的最外层栈帧时返回 `None`
+    ///
+    /// # Safety
+    /// 栈上至少要有一个栈帧，`ret_values` 与调用指令的返回值位置一一对应
+    pub unsafe fn done_func_call_shrink_stack(
+        &mut self,
+        ret_values: &[usize]
+    ) -> Option<(StackSlice, usize)> {
+        let (this_frame, prev_frame) = self.pop_frame()?;
+        let mut this_slice = self.frame_slice(this_frame);
+        let mut prev_slice = self.frame_slice(prev_frame);
 
         debug_assert_eq!(ret_values.len(), this_frame.ret_value_locs.len());
         for (ret_value, ret_value_loc) in
-            ret_values.iter().zip(this_frame.ret_value_locs)
+            ret_values.iter().zip(&*this_frame.ret_value_locs)
         {
             prev_slice.set_value(*ret_value_loc, this_slice.get_value(*ret_value))
         }
-        let ret_addr = this_frame.ret_addr;
-        self.values.truncate(prev_frame.frame_end);
-        self.frames.pop().unwrap_unchecked();
-        Some((prev_slice, ret_addr))
+        Some((prev_slice, this_frame.ret_addr))
     }
 
+    /// 只有一个返回值的 `done_func_call_shrink_stack`
+    ///
+    /// # Safety
+    /// 栈上至少要有一个栈帧，调用指令只有一个返回值位置
     pub unsafe fn done_func_call_shrink_stack1(
         &mut self,
         ret_value: usize
     ) -> Option<(StackSlice, usize)> {
-        let frame_count = self.frames.len();
-        if frame_count == 1 {
-            return None;
-        }
-
-        let this_frame: &FrameInfo = self.frames.get_unchecked(frame_count - 1);
-        let prev_frame: &FrameInfo = self.frames.get_unchecked(frame_count - 2);
-        debug_assert_eq!(prev_frame.frame_end, this_frame.frame_start);
-        let mut this_slice =
-            StackSlice(&mut self.values[this_frame.frame_start..this_frame.frame_end]);
-        let mut prev_slice =
-            StackSlice(&mut self.values[prev_frame.frame_start..prev_frame.frame_end]);
+        let (this_frame, prev_frame) = self.pop_frame()?;
+        let mut this_slice = self.frame_slice(this_frame);
+        let mut prev_slice = self.frame_slice(prev_frame);
 
         debug_assert_eq!(this_frame.ret_value_locs.len(), 1);
-        prev_slice.set_value(*this_frame.ret_value_locs.get_unchecked(0),
+        prev_slice.set_value(*(&*this_frame.ret_value_locs).get_unchecked(0),
                              this_slice.get_value(ret_value));
-        let ret_addr = this_frame.ret_addr;
-        self.values.truncate(prev_frame.frame_end);
-        self.frames.pop().unwrap_unchecked();
-        Some((prev_slice, ret_addr))
+        Some((prev_slice, this_frame.ret_addr))
+    }
+
+    /// 异常展开时弹出最上层的栈帧，不传递任何返回值。返回上一层栈帧的 `StackSlice` 及返回地址；
+    /// 最上层的栈帧是当前调用的最外层栈帧时返回 `None`
+    ///
+    /// # Safety
+    /// 栈上至少要有一个栈帧
+    pub unsafe fn unwind_frame(&mut self) -> Option<(StackSlice, usize)> {
+        let (this_frame, prev_frame) = self.pop_frame()?;
+        Some((self.frame_slice(prev_frame), this_frame.ret_addr))
+    }
+}
+
+#[cold] fn stack_overflow(config: &StackConfig) -> StackOverflow {
+    StackOverflow::new(config.max_depth, config.max_slots, config.max_nesting)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::Value;
+    use crate::turbofan::stack::{Stack, StackConfig};
+
+    #[test] fn test_slices_stay_valid() {
+        let mut stack = Stack::new();
+        let dummy_ret_locs = [];
+        let ret_value_locs = [1];
+        unsafe {
+            let mut outer = stack.ext_func_call_grow_stack(0, 60, &[Value::from(1i64)],
+                                                           &dummy_ret_locs).unwrap();
+            // 放不下的栈帧被分配到新的段中，外层栈帧的 `StackSlice` 不受影响
+            let mut nested = stack.ext_func_call_grow_stack(1, 100, &[Value::from(2i64)],
+                                                            &dummy_ret_locs).unwrap();
+            let mut callee = stack.func_call_grow_stack(2, 1000, &[0], &ret_value_locs, 7)
+                .unwrap();
+            assert_eq!(stack.depth(), 3);
+            assert_eq!(stack.slot_count(), 1160);
+            assert_eq!(callee.get_value(0).as_int(), Some(2));
+
+            callee.set_value(0, Value::from(3i64));
+            let (mut slice, ret_addr) = stack.done_func_call_shrink_stack1(0).unwrap();
+            assert_eq!(ret_addr, 7);
+            assert_eq!(slice.get_value(1).as_int(), Some(3));
+            assert_eq!(nested.get_value(1).as_int(), Some(3));
+            assert!(stack.done_func_call_shrink_stack1(0).is_none());
+
+            stack.ext_func_call_shrink_stack();
+            assert_eq!(stack.depth(), 1);
+            assert_eq!(outer.get_value(0).as_int(), Some(1));
+            stack.ext_func_call_shrink_stack();
+            assert_eq!(stack.slot_count(), 0);
+        }
+    }
+
+    #[test] fn test_nesting_limit() {
+        let mut stack = Stack::with_config(StackConfig { max_nesting: 2, ..StackConfig::default() });
+        unsafe {
+            assert!(stack.ext_func_call_grow_stack(0, 1, &[], &[]).is_ok());
+            assert!(stack.ext_func_call_grow_stack(0, 1, &[], &[]).is_ok());
+            assert!(stack.ext_func_call_grow_stack(0, 1, &[], &[]).is_err());
+        }
     }
 }
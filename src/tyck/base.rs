@@ -3,6 +3,7 @@
 use std::any::TypeId;
 
 use crate::tyck::{TypeCheckInfo, FFIAction};
+use crate::tyck::names;
 use crate::void::Void;
 
 /// `StaticBase` 用于进行各类“编译”期检查，返回一些有用的信息
@@ -20,6 +21,10 @@ pub trait StaticBase<T> {
 
     /// 返回类型的 `FFIAction`
     fn ffi_action() -> FFIAction;
+
+    /// 在 `names` 注册表中登记类型名，使 `TypeError` 能够打印这个类型。只在注册宿主函数时调用，
+    /// 不在类型检查的路径上
+    fn register_type_name();
 }
 
 trait StaticBaseImpl<T> {
@@ -27,15 +32,15 @@ trait StaticBaseImpl<T> {
 }
 
 impl<T: 'static> StaticBase<T> for Void {
-    #[inline] fn base_type_id() -> TypeId {
+    #[inline] default fn base_type_id() -> TypeId {
         TypeId::of::<T>()
     }
 
-    #[inline] fn tyck_info() -> TypeCheckInfo {
+    #[inline] default fn tyck_info() -> TypeCheckInfo {
         TypeCheckInfo::SimpleType(TypeId::of::<T>())
     }
 
-    #[inline] fn tyck(tyck_info: &TypeCheckInfo) -> bool {
+    #[inline] default fn tyck(tyck_info: &TypeCheckInfo) -> bool {
         if let TypeCheckInfo::SimpleType(tid) = tyck_info {
             *tid == TypeId::of::<T>()
         } else {
@@ -43,9 +48,13 @@ impl<T: 'static> StaticBase<T> for Void {
         }
     }
 
-    #[inline] fn ffi_action() -> FFIAction {
+    #[inline] default fn ffi_action() -> FFIAction {
         <Void as StaticBaseImpl<T>>::ffi_action_impl()
     }
+
+    #[inline] default fn register_type_name() {
+        names::register::<T>();
+    }
 }
 
 impl<T> StaticBaseImpl<T> for Void {
@@ -26,6 +26,17 @@ pub trait FusionRV<T> {
 
     fn nullable_rv() -> Nullable;
     fn exception() -> ExceptionSpec;
+    /// 登记返回值类型的类型名，参见 `StaticBase::register_type_name`
+    fn register_names_rv();
+}
+
+/// 在 `FusionRV` 之上特殊处理元组 `(A, B, ...)` 和 `Result<(A, B, ...), E>`
+///
+/// 元组的每个元素对应一个返回值，`()` 不对应任何返回值，其他类型都视为单个返回值。
+pub trait FusionRVMulti<T> {
+    fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)>;
+    fn exception_multi() -> ExceptionSpec;
+    fn register_names_multi();
 }
 
 pub trait FusionRV2<T> {
@@ -34,6 +45,7 @@ pub trait FusionRV2<T> {
     fn ffi_action_rv2() -> FFIAction;
 
     fn nullable_rv2() -> Nullable;
+    fn register_names_rv2();
 }
 
 pub trait Fusion<T> {
@@ -43,14 +55,105 @@ pub trait Fusion<T> {
     fn fusion_ffi_action() -> FFIAction;
 
     fn nullable() -> Nullable;
+    /// 登记参数类型的类型名，参见 `StaticBase::register_type_name`
+    fn fusion_register_names();
 }
 
 pub trait Fusion2<T> {
     fn fusion_tyck_info2() -> TypeCheckInfo;
     fn fusion_tyck2(tyck_info: &TypeCheckInfo) -> bool;
     fn fusion_ffi_action2() -> FFIAction;
+    fn fusion_register_names2();
+}
+
+impl<T> FusionRVMulti<T> for Void where Void: FusionRV<T> {
+    #[inline] default fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+        vec![(<Void as FusionRV<T>>::tyck_info_rv(),
+              <Void as FusionRV<T>>::ffi_action_rv(),
+              <Void as FusionRV<T>>::nullable_rv())]
+    }
+
+    #[inline] default fn exception_multi() -> ExceptionSpec {
+        <Void as FusionRV<T>>::exception()
+    }
+
+    #[inline] default fn register_names_multi() {
+        <Void as FusionRV<T>>::register_names_rv()
+    }
+}
+
+impl FusionRVMulti<()> for Void {
+    #[inline] fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+        vec![]
+    }
+
+    #[inline] fn exception_multi() -> ExceptionSpec {
+        None
+    }
+
+    #[inline] fn register_names_multi() {}
 }
 
+impl<E> FusionRVMulti<Result<(), E>> for Void where E: 'static + Error {
+    #[inline] fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+        vec![]
+    }
+
+    #[inline] fn exception_multi() -> ExceptionSpec {
+        Some(TypeId::of::<E>())
+    }
+
+    #[inline] fn register_names_multi() {}
+}
+
+/// 为 `(A, B, ...)` 和 `Result<(A, B, ...), E>` 生成 `FusionRVMulti` 实现
+macro_rules! impl_fusion_rv_multi_tuple {
+    ($($elem_ty:ident),*) => {
+        impl<$($elem_ty: 'static,)*> FusionRVMulti<($($elem_ty,)*)> for Void
+            where $(Void: FusionRV2<$elem_ty>,)*
+        {
+            #[inline] fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                vec![
+                    $((<Void as FusionRV2<$elem_ty>>::tyck_info_rv2(),
+                       <Void as FusionRV2<$elem_ty>>::ffi_action_rv2(),
+                       <Void as FusionRV2<$elem_ty>>::nullable_rv2()),)*
+                ]
+            }
+
+            #[inline] fn exception_multi() -> ExceptionSpec {
+                None
+            }
+
+            #[inline] fn register_names_multi() {
+                $(<Void as FusionRV2<$elem_ty>>::register_names_rv2();)*
+            }
+        }
+
+        impl<$($elem_ty: 'static,)* EXC> FusionRVMulti<Result<($($elem_ty,)*), EXC>> for Void
+            where $(Void: FusionRV2<$elem_ty>,)*
+                  EXC: 'static + Error
+        {
+            #[inline] fn rv_specs() -> Vec<(TypeCheckInfo, FFIAction, Nullable)> {
+                <Void as FusionRVMulti<($($elem_ty,)*)>>::rv_specs()
+            }
+
+            #[inline] fn exception_multi() -> ExceptionSpec {
+                Some(TypeId::of::<EXC>())
+            }
+
+            #[inline] fn register_names_multi() {
+                <Void as FusionRVMulti<($($elem_ty,)*)>>::register_names_multi()
+            }
+        }
+    };
+}
+
+impl_fusion_rv_multi_tuple!(A, B);
+impl_fusion_rv_multi_tuple!(A, B, C);
+impl_fusion_rv_multi_tuple!(A, B, C, D);
+impl_fusion_rv_multi_tuple!(A, B, C, D, E);
+impl_fusion_rv_multi_tuple!(A, B, C, D, E, F);
+
 impl<T> FusionRV<T> for Void where Void: FusionRV2<T> {
     #[inline] default fn tyck_info_rv() -> TypeCheckInfo {
         <Void as FusionRV2<T>>::tyck_info_rv2()
@@ -75,6 +178,10 @@ impl<T> FusionRV<T> for Void where Void: FusionRV2<T> {
     #[inline] default fn exception() -> ExceptionSpec {
         None
     }
+
+    #[inline] default fn register_names_rv() {
+        <Void as FusionRV2<T>>::register_names_rv2()
+    }
 }
 
 impl<T, E> FusionRV<Result<T, E>> for Void where Void: FusionRV2<T>, E: 'static + Error {
@@ -101,6 +208,10 @@ impl<T, E> FusionRV<Result<T, E>> for Void where Void: FusionRV2<T>, E: 'static
     #[inline] fn exception() -> ExceptionSpec {
         Some(TypeId::of::<E>())
     }
+
+    #[inline] fn register_names_rv() {
+        <Void as FusionRV2<T>>::register_names_rv2()
+    }
 }
 
 impl<T> FusionRV2<T> for Void where Void: Fusion<T> {
@@ -119,6 +230,10 @@ impl<T> FusionRV2<T> for Void where Void: Fusion<T> {
     #[inline] default fn nullable_rv2() -> bool {
         <Void as Fusion<T>>::nullable()
     }
+
+    #[inline] default fn register_names_rv2() {
+        <Void as Fusion<T>>::fusion_register_names()
+    }
 }
 
 impl<T: 'static> FusionRV2<&Option<T>> for Void where Void: StaticBase<T> {
@@ -137,6 +252,10 @@ impl<T: 'static> FusionRV2<&Option<T>> for Void where Void: StaticBase<T> {
     #[inline] fn nullable_rv2() -> bool {
         true
     }
+
+    #[inline] fn register_names_rv2() {
+        <Void as StaticBase<T>>::register_type_name()
+    }
 }
 
 impl<T: 'static> FusionRV2<&mut Option<T>> for Void where Void: StaticBase<T> {
@@ -155,6 +274,10 @@ impl<T: 'static> FusionRV2<&mut Option<T>> for Void where Void: StaticBase<T> {
     #[inline] fn nullable_rv2() -> bool {
         true
     }
+
+    #[inline] fn register_names_rv2() {
+        <Void as StaticBase<T>>::register_type_name()
+    }
 }
 
 impl<T> Fusion<T> for Void where Void: Fusion2<T> {
@@ -177,6 +300,10 @@ impl<T> Fusion<T> for Void where Void: Fusion2<T> {
     #[inline] default fn nullable() -> bool {
         false
     }
+
+    #[inline] default fn fusion_register_names() {
+        <Void as Fusion2<T>>::fusion_register_names2()
+    }
 }
 
 impl<T> Fusion<Option<T>> for Void where Void: Fusion2<T> {
@@ -199,6 +326,10 @@ impl<T> Fusion<Option<T>> for Void where Void: Fusion2<T> {
     #[inline] fn nullable() -> bool {
         true
     }
+
+    #[inline] fn fusion_register_names() {
+        <Void as Fusion2<T>>::fusion_register_names2()
+    }
 }
 
 impl Fusion<Value> for Void {
@@ -221,6 +352,8 @@ impl Fusion<Value> for Void {
     #[inline] fn nullable() -> bool {
         true
     }
+
+    #[inline] fn fusion_register_names() {}
 }
 
 impl<T> Fusion2<T> for Void where Void: StaticBase<T> {
@@ -235,6 +368,10 @@ impl<T> Fusion2<T> for Void where Void: StaticBase<T> {
     #[inline] default fn fusion_ffi_action2() -> FFIAction {
         <Void as StaticBase<T>>::ffi_action()
     }
+
+    #[inline] default fn fusion_register_names2() {
+        <Void as StaticBase<T>>::register_type_name()
+    }
 }
 
 impl<'a, T> Fusion2<&'a T> for Void where Void: StaticBase<T> {
@@ -249,6 +386,10 @@ impl<'a, T> Fusion2<&'a T> for Void where Void: StaticBase<T> {
     #[inline] fn fusion_ffi_action2() -> FFIAction {
         FFIAction::Share
     }
+
+    #[inline] fn fusion_register_names2() {
+        <Void as StaticBase<T>>::register_type_name()
+    }
 }
 
 impl<'a, T> Fusion2<&'a mut T> for Void where Void: StaticBase<T> {
@@ -263,6 +404,10 @@ impl<'a, T> Fusion2<&'a mut T> for Void where Void: StaticBase<T> {
     #[inline] fn fusion_ffi_action2() -> FFIAction {
         FFIAction::MutShare
     }
+
+    #[inline] fn fusion_register_names2() {
+        <Void as StaticBase<T>>::register_type_name()
+    }
 }
 
 #[cfg(test)]
@@ -273,7 +418,7 @@ mod test {
 
     use crate::tyck::FFIAction;
     use crate::tyck::TypeCheckInfo;
-    use crate::tyck::fusion::{ExceptionSpec, FusionRV};
+    use crate::tyck::fusion::{ExceptionSpec, FusionRV, FusionRVMulti};
     use crate::void::Void;
 
     fn type_check_info_assert(tyck_info: &TypeCheckInfo, type_ids: &[TypeId]) {
@@ -421,4 +566,24 @@ mod test {
             &[TypeId::of::<i64>()], FFIAction::Copy, false, Some(TypeId::of::<TestError2>())
         );
     }
+
+    #[test]
+    fn test_fusion_rv_multi() {
+        let specs = <Void as FusionRVMulti<(i64, String, Option<i64>)>>::rv_specs();
+        assert_eq!(specs.len(), 3);
+        type_check_info_assert(&specs[0].0, &[TypeId::of::<i64>()]);
+        assert_eq!(specs[0].1, FFIAction::Copy);
+        type_check_info_assert(&specs[1].0, &[TypeId::of::<String>()]);
+        assert_eq!(specs[1].1, FFIAction::Move);
+        assert!(specs[2].2);
+        assert_eq!(<Void as FusionRVMulti<(i64, String, Option<i64>)>>::exception_multi(), None);
+
+        assert_eq!(<Void as FusionRVMulti<Result<(i64, i64), TestError1>>>::rv_specs().len(), 2);
+        assert_eq!(<Void as FusionRVMulti<Result<(i64, i64), TestError1>>>::exception_multi(),
+                   Some(TypeId::of::<TestError1>()));
+        assert_eq!(<Void as FusionRVMulti<()>>::rv_specs().len(), 0);
+        assert_eq!(<Void as FusionRVMulti<Result<(), TestError2>>>::exception_multi(),
+                   Some(TypeId::of::<TestError2>()));
+        assert_eq!(<Void as FusionRVMulti<i64>>::rv_specs().len(), 1);
+    }
 }
@@ -2,12 +2,13 @@
 
 pub mod base;
 pub mod fusion;
+pub mod names;
 
 /// 类型检查信息
 ///
 /// 类型检查信息在“编译”时生成，运行时用来确定两个类型之间的兼容性。目前的实现采用朴素的分配方式，
 /// 之后可能改用一个 arena 来管理所有的 `TypeCheckInfo`。
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TypeCheckInfo {
     /// 不进行类型检查
     Bypass,
@@ -17,6 +18,21 @@ pub enum TypeCheckInfo {
     Container(std::any::TypeId, Vec<TypeCheckInfo>),
 }
 
+impl TypeCheckInfo {
+    /// 判断需要 `self` 类型的位置能否接受 `other` 类型。任何一方为 `Bypass` 时都视为兼容
+    pub fn accepts(&self, other: &TypeCheckInfo) -> bool {
+        match (self, other) {
+            (TypeCheckInfo::Bypass, _) | (_, TypeCheckInfo::Bypass) => true,
+            (TypeCheckInfo::SimpleType(lhs), TypeCheckInfo::SimpleType(rhs)) => lhs == rhs,
+            (TypeCheckInfo::Container(lhs, lhs_elems), TypeCheckInfo::Container(rhs, rhs_elems)) =>
+                lhs == rhs
+                && lhs_elems.len() == rhs_elems.len()
+                && lhs_elems.iter().zip(rhs_elems).all(|(l, r)| l.accepts(r)),
+            _ => false
+        }
+    }
+}
+
 /// 生存期检查信息
 ///
 /// 生存期检查信息在“编译”时生成，运行时用来确定对象如何在 Rust 和 `T10` 之间传递
@@ -0,0 +1,68 @@
+//! 运行时的类型名注册表
+//!
+//! `TypeId` 本身不携带可读的类型名。值类型和 `TError` 的类型名预先登记在这个全局注册表中。
+//! 宿主函数的参数和返回值类型由 `StaticBase::register_type_name` 登记，`FunctionRegistry::register`
+//! 注册函数时对每个类型调用一次；没有经过注册表的类型在构造类型错误的路径上（例如
+//! `DynBase::dyn_type_name`）补充登记。构造 `TypeError` 时再从这里查找类型名，类型检查的热路径
+//! 不会访问这个注册表。
+
+use std::any::{TypeId, type_name};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::error::TError;
+
+fn registry() -> &'static RwLock<HashMap<TypeId, String>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<TypeId, String>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut names = HashMap::new();
+        names.insert(TypeId::of::<i64>(), type_name::<i64>().to_string());
+        names.insert(TypeId::of::<f64>(), type_name::<f64>().to_string());
+        names.insert(TypeId::of::<char>(), type_name::<char>().to_string());
+        names.insert(TypeId::of::<bool>(), type_name::<bool>().to_string());
+        names.insert(TypeId::of::<TError>(), type_name::<TError>().to_string());
+        RwLock::new(names)
+    })
+}
+
+/// 登记类型 `T` 的类型名
+#[inline] pub fn register<T: ?Sized + 'static>() {
+    register_name(TypeId::of::<T>(), type_name::<T>());
+}
+
+/// 登记 `type_id` 对应的类型名，已经登记过的类型名不会被覆盖
+pub fn register_name(type_id: TypeId, name: &str) {
+    if registry().read().unwrap().contains_key(&type_id) {
+        return;
+    }
+    registry().write().unwrap().entry(type_id).or_insert_with(|| name.to_string());
+}
+
+/// 查找 `type_id` 对应的类型名
+pub fn lookup(type_id: TypeId) -> Option<String> {
+    registry().read().unwrap().get(&type_id).cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use std::any::TypeId;
+
+    use crate::tyck::names::{lookup, register, register_name};
+
+    #[test] fn test_register() {
+        struct Unnamed();
+        struct Named();
+        struct FirstWriter();
+
+        assert_eq!(lookup(TypeId::of::<i64>()).as_deref(), Some("i64"));
+        assert_eq!(lookup(TypeId::of::<Unnamed>()), None);
+
+        register::<Named>();
+        assert!(lookup(TypeId::of::<Named>()).unwrap().ends_with("Named"));
+
+        register_name(TypeId::of::<FirstWriter>(), "first");
+        register_name(TypeId::of::<FirstWriter>(), "second");
+        register::<FirstWriter>();
+        assert_eq!(lookup(TypeId::of::<FirstWriter>()).as_deref(), Some("first"));
+    }
+}
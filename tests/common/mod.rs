@@ -0,0 +1,67 @@
+//! `test_rd93` 和 `test_f110` 共用的测试工具
+
+use std::cell::Cell;
+use std::mem::MaybeUninit;
+
+use t10::data::Value;
+use t10::error::{TError, VmError};
+use t10::intake::oxtank::OxTank;
+
+thread_local! {
+    static DROPS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// 被释放时计数的宿主对象
+pub struct Tracked(pub i64);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPS.with(|drops| drops.set(drops.get() + 1));
+    }
+}
+
+pub fn track(x: i64) -> Tracked {
+    Tracked(x)
+}
+
+/// 当前线程中已经释放的 `Tracked` 对象个数
+pub fn drops() -> usize {
+    DROPS.with(Cell::get)
+}
+
+/// 分配 `ret_count` 个返回值位置并执行 `run`，成功时取出所有返回值
+pub fn run_collect(
+    ret_count: usize,
+    run: impl FnOnce(&mut [MaybeUninit<Value>]) -> Result<(), VmError>
+) -> Result<Vec<Value>, VmError> {
+    let mut ret_values = vec![MaybeUninit::uninit(); ret_count];
+    run(&mut ret_values)?;
+    Ok(ret_values.into_iter().map(|v| unsafe { v.assume_init() }).collect())
+}
+
+/// 检查宿主函数返回的对象和被捕获的异常对象都交给了 `intake`
+///
+/// `run` 以参数 5 执行一个调用 `host_calls` 次 `track` 之后抛出异常的函数，异常处理器返回最后
+/// 一个 `Tracked` 对象
+pub fn check_ffi_return_adopted(
+    run: impl Fn(&mut OxTank) -> Result<Value, VmError>,
+    host_calls: usize
+) {
+    let drops_before = drops();
+
+    // 所有对象都由 `intake` 管理，随 `intake` 一起释放
+    let mut intake = OxTank::new_unlimited();
+    let ret = run(&mut intake).unwrap();
+    assert_eq!(unsafe { ret.as_ref::<Tracked>() }.0, 5);
+    assert_eq!(intake.object_count(), host_calls + 1);
+    assert_eq!(drops(), drops_before);
+    drop(intake);
+    assert_eq!(drops(), drops_before + host_calls);
+
+    // 接管失败时对象被立即释放，接管异常对象同样失败，错误返回给宿主
+    let mut intake = OxTank::new_limited(0);
+    let err = run(&mut intake).err().unwrap();
+    assert!(matches!(*err.error, TError::OutOfMemory(_)));
+    assert_eq!(intake.object_count(), 0);
+    assert_eq!(drops(), drops_before + host_calls + 1);
+}
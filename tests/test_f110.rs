@@ -0,0 +1,247 @@
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+
+use t10::data::{DynBase, GcInfo, StaticWrapper, Value};
+use t10::error::{TError, VmError};
+use t10::func::{RustFunction, RustFunction1, RustVmFunction1, VmContext};
+use t10::intake::oxtank::OxTank;
+use t10::turbofan::f110::{CompiledProgram, Insc, OpData, Operand, F110};
+use t10::turbofan::rd93::{CompiledFuncInfo, ExceptionHandler};
+use t10::turbofan::stack::{Stack, StackConfig};
+
+mod common;
+
+use common::{check_ffi_return_adopted, run_collect, track};
+
+fn sv(idx: usize) -> Operand<i64> {
+    Operand::stack_value(idx)
+}
+
+fn run_multiple(
+    program: &CompiledProgram,
+    func_id: usize,
+    args: &[Value],
+    ret_count: usize
+) -> Result<Vec<Value>, VmError> {
+    // 返回值中的对象在 `intake` 被丢弃时释放，调用者只检查值类型的返回值
+    let mut intake = OxTank::new_unlimited();
+    run_collect(ret_count, |rets| unsafe {
+        F110::run_func(program, func_id, args, rets, &mut intake)
+    })
+}
+
+#[test]
+fn test_fibonacci() {
+    let program = CompiledProgram::new(vec![
+        // fibonacci(n int @%0) -> int
+        /*00*/ Insc::IntLe { lhs: sv(0), rhs: Operand::constant(1), dest_value: 1 },
+        /*01*/ Insc::JumpIfTrue { cond: Operand::stack_value(1), jump_dest: 7 },
+        /*02*/ Insc::IntSub { lhs: sv(0), rhs: Operand::constant(1), dest_value: 1 },
+        /*03*/ Insc::IntSub { lhs: sv(0), rhs: Operand::constant(2), dest_value: 2 },
+        /*04*/ Insc::FuncCall { func_id: 0, arg_values: vec![1], ret_value_locs: vec![1] },
+        /*05*/ Insc::FuncCall { func_id: 0, arg_values: vec![2], ret_value_locs: vec![2] },
+        /*06*/ Insc::IntAdd { lhs: sv(1), rhs: sv(2), dest_value: 0 },
+        /*07*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 3)
+    ], vec![]);
+
+    let rets = run_multiple(&program, 0, &[Value::from(10i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(55));
+}
+
+fn mul(x: i64, y: i64) -> i64 {
+    x * y
+}
+
+#[test]
+fn test_ffi_call_and_multiple_returns() {
+    let program = CompiledProgram::new(vec![
+        // square_and_cube(x int @%0) -> (int, int, float, bool)
+        /*00*/ Insc::FFICall { func_id: 0, arg_values: vec![0, 0], ret_value_locs: vec![1] },
+        /*01*/ Insc::FFICall { func_id: 0, arg_values: vec![0, 1], ret_value_locs: vec![2] },
+        /*02*/ Insc::IntToFloat { value: sv(2), dest_value: 3 },
+        /*03*/ Insc::FloatDiv {
+            lhs: Operand::stack_value(3), rhs: Operand::constant(2.0), dest_value: 3
+        },
+        /*04*/ Insc::CharLt { lhs: Operand::constant('a'), rhs: Operand::constant('b'),
+                              dest_value: 4 },
+        /*05*/ Insc::ReturnMultiple { ret_values: vec![1, 2, 3, 4] }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 4, 5)
+    ], vec![
+        Box::new(RustFunction { f: mul, _phantom: PhantomData })
+    ]);
+
+    let rets = run_multiple(&program, 0, &[Value::from(3i64)], 4).unwrap();
+    assert_eq!(rets[0].as_int(), Some(9));
+    assert_eq!(rets[1].as_int(), Some(27));
+    assert_eq!(rets[2].as_float(), Some(13.5));
+    assert_eq!(rets[3].as_bool(), Some(true));
+}
+
+#[test]
+fn test_tyck() {
+    let program = CompiledProgram::new(vec![
+        // add(a @%0, b @%1) -> int
+        /*00*/ Insc::IntAdd {
+            lhs: Operand::new(OpData::StackValue(0), true, false),
+            rhs: Operand::new(OpData::Stack(1), true, true),
+            dest_value: 0
+        },
+        /*01*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 1, 2)
+    ], vec![]);
+
+    let rets = run_multiple(&program, 0, &[Value::from(1i64), Value::from(2i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(3));
+
+    let err = run_multiple(&program, 0, &[Value::from(true), Value::from(2i64)], 1)
+        .err().unwrap();
+    assert!(matches!(*err.error, TError::TypeError(_)));
+    assert_eq!(err.insc_ptr, 0);
+
+    let err = run_multiple(&program, 0, &[Value::from(1i64), Value::null()], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::NullError(_)));
+
+    // `OpData::Stack` 可以读取存放在堆上的整数
+    let boxed = Box::leak(Box::new(StaticWrapper::owned(40i64))) as *mut dyn DynBase;
+    let rets = run_multiple(&program, 0, &[Value::from(2i64), Value::from(boxed)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(42));
+}
+
+#[test]
+fn test_rtlc() {
+    let program = CompiledProgram::new(vec![
+        // neg(x @%0) -> int
+        /*00*/ Insc::IntNeg { value: Operand::new(OpData::Stack(0), true, true), dest_value: 0 },
+        /*01*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 1)
+    ], vec![]);
+
+    let boxed = Box::leak(Box::new(StaticWrapper::owned(5i64))) as *mut dyn DynBase;
+    let value = Value::from(boxed);
+    let rets = run_multiple(&program, 0, &[value], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(-5));
+
+    // 已经移动给宿主的对象不能再读取
+    unsafe { value.set_gc_info(GcInfo::MovedToHost); }
+    let err = run_multiple(&program, 0, &[value], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::LifetimeError(_)));
+}
+
+#[test]
+fn test_exception_handler() {
+    let mut program = CompiledProgram::new(vec![
+        // safe_div(a int @%0, b int @%1) -> int
+        /*00*/ Insc::FuncCall { func_id: 1, arg_values: vec![0, 1], ret_value_locs: vec![0] },
+        /*01*/ Insc::ReturnOne { ret_value: 0 },
+        /*02*/ Insc::MakeIntConst { c: -1, dest_value: 0 },
+        /*03*/ Insc::ReturnOne { ret_value: 0 },
+
+        // div(a int @%0, b int @%1) -> int
+        /*04*/ Insc::IntDiv { lhs: sv(0), rhs: sv(1), dest_value: 0 },
+        /*05*/ Insc::ReturnOne { ret_value: 0 },
+
+        // rethrow(a int @%0) -> int
+        /*06*/ Insc::IntMod { lhs: sv(0), rhs: Operand::constant(0), dest_value: 0 },
+        /*07*/ Insc::ReturnOne { ret_value: 0 },
+        /*08*/ Insc::Throw { exception_value: 1 }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 1, 3),
+        CompiledFuncInfo::new(4, 2, 1, 2),
+        CompiledFuncInfo::new(6, 1, 1, 2)
+    ], vec![]);
+    program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(0, 1, 2, 2)]);
+    program.set_exception_handlers(2, vec![ExceptionHandler::catch_all(6, 7, 8, 1)]);
+
+    let rets = run_multiple(&program, 0, &[Value::from(7i64), Value::from(2i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(3));
+    let rets = run_multiple(&program, 0, &[Value::from(7i64), Value::from(0i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(-1));
+
+    let err = run_multiple(&program, 2, &[Value::from(7i64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::UncheckedException(_)));
+    assert_eq!(err.insc_ptr, 8);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let program = CompiledProgram::new(vec![
+        // countdown(n int @%0) -> int
+        /*00*/ Insc::IntEq { lhs: sv(0), rhs: Operand::constant(0), dest_value: 1 },
+        /*01*/ Insc::JumpIfTrue { cond: Operand::stack_value(1), jump_dest: 5 },
+        /*02*/ Insc::IntSub { lhs: sv(0), rhs: Operand::constant(1), dest_value: 0 },
+        /*03*/ Insc::FuncCall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*04*/ Insc::ReturnOne { ret_value: 0 },
+        /*05*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 2)
+    ], vec![]);
+
+    let mut stack = Stack::with_config(StackConfig::new(16, 1024));
+    let mut run = |n: i64| {
+        let mut intake = OxTank::new_unlimited();
+        let mut ret_values = vec![MaybeUninit::uninit()];
+        unsafe {
+            F110::run_func_with_stack(&mut stack, &program, 0, &[Value::from(n)], &mut ret_values,
+                                      &mut intake)
+                .map(|_| ret_values[0].assume_init().as_int())
+        }
+    };
+
+    assert_eq!(run(15).unwrap(), Some(0));
+    let err = run(16).err().unwrap();
+    assert!(matches!(*err.error, TError::StackOverflow(_)));
+    assert_eq!(err.insc_ptr, 3);
+    assert_eq!(run(15).unwrap(), Some(0));
+}
+
+fn call_back(vm: &mut dyn VmContext, x: i64) -> Result<i64, TError> {
+    let rets = vm.call_func(1, &[Value::from(x)])?;
+    Ok(rets[0].as_int().unwrap() + 1)
+}
+
+#[test]
+fn test_reentrant_call() {
+    let program = CompiledProgram::new(vec![
+        // entry(x int @%0) -> int
+        /*00*/ Insc::FFICall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![1] },
+        /*01*/ Insc::IntAdd { lhs: sv(0), rhs: sv(1), dest_value: 0 },
+        /*02*/ Insc::ReturnOne { ret_value: 0 },
+
+        // halve(x int @%0) -> int
+        /*03*/ Insc::IntDiv { lhs: sv(0), rhs: Operand::constant(2), dest_value: 0 },
+        /*04*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 2),
+        CompiledFuncInfo::new(3, 1, 1, 1)
+    ], vec![
+        Box::new(RustVmFunction1 { f: call_back, _phantom: PhantomData })
+    ]);
+
+    let rets = run_multiple(&program, 0, &[Value::from(10i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(16));
+}
+
+#[test]
+fn test_ffi_return_adopted() {
+    let mut program = CompiledProgram::new(vec![
+        // entry(x int @%0) -> any，除以零之后由异常处理器返回 track 的结果
+        /*00*/ Insc::FFICall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![1] },
+        /*01*/ Insc::IntDiv { lhs: sv(0), rhs: Operand::constant(0), dest_value: 0 },
+        /*02*/ Insc::ReturnOne { ret_value: 0 },
+        /*03*/ Insc::ReturnOne { ret_value: 1 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 3)
+    ], vec![
+        Box::new(RustFunction1 { f: track, _phantom: PhantomData })
+    ]);
+    program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(1, 2, 3, 2)]);
+
+    check_ffi_return_adopted(|intake| run_collect(1, |rets| unsafe {
+        F110::run_func(&program, 0, &[Value::from(5i64)], rets, intake)
+    }).map(|rets| rets[0]), 1);
+}
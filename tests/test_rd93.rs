@@ -1,8 +1,21 @@
 use std::any::TypeId;
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 
-use t10::data::Value;
-use t10::turbofan::rd93::{CompiledFuncInfo, CompiledProgram, Insc, RD93};
+use t10::data::{GcInfo, Value};
+use t10::error::{LinkError, StackOverflow, TError, VmError};
+use t10::func::{RustFunction, RustFunction0, RustFunction1, RustVmFunction2, VmContext};
+use t10::func::registry::{FFIImport, FunctionRegistry};
+use t10::intake::{Adopt, OxProvider};
+use t10::intake::oxtank::OxTank;
+use t10::intake::ramped::{IntakeOptions, RampedIntake};
+use t10::tyck::TypeCheckInfo;
+use t10::turbofan::stack::{Stack, StackConfig};
+use t10::turbofan::rd93::{assemble, decode_module, disassemble, encode_module, verify, FlatProgram, VerifiedProgram, CompiledFuncInfo, CompiledProgram, ExceptionHandler, Insc, RD93};
+
+mod common;
+
+use common::{check_ffi_return_adopted, drops, run_collect, track, Tracked};
 
 #[test]
 fn test_add_func() {
@@ -14,9 +27,10 @@ fn test_add_func() {
         CompiledFuncInfo::new(0, 2, 1, 2)
     ], vec![]);
 
+    let mut intake = OxTank::new_unlimited();
     let mut ret_values = vec![MaybeUninit::uninit()];
     unsafe {
-        RD93::run_func(&program, 0, &[Value::from(13i64), Value::from(42i64)], &mut ret_values)
+        RD93::run_func(&verify(&program).unwrap(), 0, &[Value::from(13i64), Value::from(42i64)], &mut ret_values, &mut intake).unwrap()
     };
     unsafe {
         let ret_value = ret_values[0].assume_init();
@@ -44,9 +58,10 @@ fn test_func_call() {
         CompiledFuncInfo::new(6, 2, 1, 2), // add
     ], vec![]);
 
+    let mut intake = OxTank::new_unlimited();
     let mut ret_values = vec![MaybeUninit::uninit()];
     unsafe {
-        RD93::run_func(&program, 0, &[Value::from(13i64), Value::from(42i64)], &mut ret_values)
+        RD93::run_func(&verify(&program).unwrap(), 0, &[Value::from(13i64), Value::from(42i64)], &mut ret_values, &mut intake).unwrap()
     };
     unsafe {
         let ret_value = ret_values[0].assume_init();
@@ -77,9 +92,10 @@ fn test_fibonacci() {
         CompiledFuncInfo::new(0, 1, 1, 4),
     ], vec![]);
 
+    let mut intake = OxTank::new_unlimited();
     let mut ret_values = vec![MaybeUninit::uninit()];
     unsafe {
-        RD93::run_func(&program, 0, &[Value::from(10i64)], &mut ret_values)
+        RD93::run_func(&verify(&program).unwrap(), 0, &[Value::from(10i64)], &mut ret_values, &mut intake).unwrap()
     };
     unsafe {
         let ret_value = ret_values[0].assume_init();
@@ -87,3 +103,905 @@ fn test_fibonacci() {
         assert_eq!(ret_value.value_typed_data.inner.int, 55);
     }
 }
+
+fn mul(x: i64, y: i64) -> i64 {
+    x * y
+}
+
+#[test]
+fn test_linked_ffi_call() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("math::mul", RustFunction { f: mul, _phantom: PhantomData }).unwrap();
+
+    let int = || TypeCheckInfo::SimpleType(TypeId::of::<i64>());
+    let inscs = || vec![
+        // square(a int @%0) -> int
+        Insc::FFICall { func_id: 0, arg_values: vec![0, 0], ret_value_locs: vec![0] },
+        Insc::ReturnOne { ret_value: 0 }
+    ];
+    let program = CompiledProgram::link(inscs(), vec![
+        CompiledFuncInfo::new(0, 1, 1, 1)
+    ], vec![
+        FFIImport::new("math::mul", vec![int(), int()], vec![int()])
+    ], &registry).unwrap();
+
+    let mut intake = OxTank::new_unlimited();
+    let mut ret_values = vec![MaybeUninit::uninit()];
+    unsafe {
+        RD93::run_func(&verify(&program).unwrap(), 0, &[Value::from(12i64)], &mut ret_values, &mut intake).unwrap()
+    };
+    unsafe {
+        let ret_value = ret_values[0].assume_init();
+        assert_eq!(ret_value.value_typed_data.inner.int, 144);
+    }
+
+    let result = CompiledProgram::link(inscs(), vec![
+        CompiledFuncInfo::new(0, 1, 1, 1)
+    ], vec![
+        FFIImport::new("math::mul", vec![int()], vec![int()])
+    ], &registry);
+    assert!(matches!(result, Err(LinkError::ArgLenMismatch { .. })));
+}
+
+fn run_multiple(
+    program: &CompiledProgram,
+    args: &[Value],
+    ret_count: usize
+) -> Result<Vec<Value>, VmError> {
+    let verified = verify(program).unwrap();
+    // 返回值中的对象在 `intake` 被丢弃时释放，调用者只检查值类型的返回值
+    let mut intake = OxTank::new_unlimited();
+    let result = run_collect(ret_count, |rets| unsafe {
+        RD93::run_func(&verified, 0, args, rets, &mut intake)
+    });
+
+    // 定长指令后端必须给出完全相同的结果
+    let flat = FlatProgram::new(&verified).unwrap();
+    let flat_result = run_collect(ret_count, |rets| unsafe {
+        RD93::run_flat_func(&flat, 0, args, rets, &mut intake)
+    });
+    match (&result, &flat_result) {
+        (Ok(rets), Ok(flat_rets)) => {
+            let summary = |v: &Value| (v.as_int(), v.as_float().map(f64::to_bits), v.as_char(),
+                                       v.as_bool(), v.is_null());
+            assert_eq!(rets.iter().map(summary).collect::<Vec<_>>(),
+                       flat_rets.iter().map(summary).collect::<Vec<_>>());
+        },
+        (Err(e), Err(flat_e)) => assert_eq!(e.to_string(), flat_e.to_string()),
+        _ => panic!("backends disagree on whether the call succeeds")
+    }
+    result
+}
+
+#[test]
+fn test_int_ops() {
+    let program = CompiledProgram::new(vec![
+        // int_ops(x int @%0, y int @%1) -> (int, int, int, int, int, int, int, int, int, int, bool)
+        Insc::IntMul { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+        Insc::IntDiv { lhs_value: 0, rhs_value: 1, dest_value: 3 },
+        Insc::IntMod { lhs_value: 0, rhs_value: 1, dest_value: 4 },
+        Insc::IntNeg { value: 0, dest_value: 5 },
+        Insc::IntBitAnd { lhs_value: 0, rhs_value: 1, dest_value: 6 },
+        Insc::IntBitOr { lhs_value: 0, rhs_value: 1, dest_value: 7 },
+        Insc::IntBitXor { lhs_value: 0, rhs_value: 1, dest_value: 8 },
+        Insc::IntBitNot { value: 0, dest_value: 9 },
+        Insc::IntShl { lhs_value: 0, rhs_value: 1, dest_value: 10 },
+        Insc::MakeIntConst { c: 65, dest_value: 12 },
+        Insc::IntShr { lhs_value: 0, rhs_value: 12, dest_value: 11 },
+        Insc::IntLe { lhs_value: 0, rhs_value: 1, dest_value: 12 },
+        Insc::ReturnMultiple { ret_values: vec![2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12] }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 11, 13)
+    ], vec![]);
+
+    let rets = run_multiple(&program, &[Value::from(17i64), Value::from(5i64)], 11).unwrap();
+    let ints = rets[..10].iter().map(|v| v.as_int().unwrap()).collect::<Vec<_>>();
+    assert_eq!(ints, vec![85, 3, 2, -17, 17 & 5, 17 | 5, 17 ^ 5, !17, 17 << 5, 17 >> 1]);
+    assert_eq!(rets[10].as_bool(), Some(false));
+
+    let rets = run_multiple(&program, &[Value::from(i64::MIN), Value::from(-1i64)], 11).unwrap();
+    assert_eq!(rets[0].as_int(), Some(i64::MIN));
+    assert_eq!(rets[1].as_int(), Some(i64::MIN));
+    assert_eq!(rets[2].as_int(), Some(0));
+    assert_eq!(rets[3].as_int(), Some(i64::MIN));
+}
+
+#[test]
+fn test_int_div_by_zero() {
+    let program = CompiledProgram::new(vec![
+        Insc::IntDiv { lhs_value: 0, rhs_value: 1, dest_value: 0 },
+        Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 1, 2)
+    ], vec![]);
+    let err = run_multiple(&program, &[Value::from(1i64), Value::from(0i64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::UncheckedException(_)));
+    assert_eq!(err.insc_ptr, 0);
+}
+
+fn checked_sqrt(x: f64) -> Result<f64, std::fmt::Error> {
+    if x < 0.0 {
+        Err(std::fmt::Error)
+    } else {
+        Ok(x.sqrt())
+    }
+}
+
+#[test]
+fn test_ffi_error() {
+    let program = CompiledProgram::new(vec![
+        // entry(x float @%0) -> float
+        /*00*/ Insc::FuncCall { func_id: 1, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*01*/ Insc::ReturnOne { ret_value: 0 },
+
+        // sqrt(x float @%0) -> float
+        /*02*/ Insc::FFICall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*03*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 1),
+        CompiledFuncInfo::new(2, 1, 1, 1)
+    ], vec![
+        Box::new(RustFunction1 { f: checked_sqrt, _phantom: PhantomData })
+    ]);
+
+    let rets = run_multiple(&program, &[Value::from(16.0f64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(4.0));
+
+    let err = run_multiple(&program, &[Value::from(-1.0f64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::UserException(..)));
+    assert_eq!(err.insc_ptr, 2);
+    assert_eq!(err.ret_addrs, vec![1]);
+}
+
+#[test]
+fn test_unreachable_insc() {
+    let program = CompiledProgram::new(vec![
+        Insc::UnreachableInsc
+    ], vec![
+        CompiledFuncInfo::new(0, 0, 0, 0)
+    ], vec![]);
+    let err = run_multiple(&program, &[], 0).err().unwrap();
+    assert!(matches!(*err.error, TError::UncheckedException(_)));
+}
+
+#[test]
+fn test_float_ops() {
+    let program = CompiledProgram::new(vec![
+        // float_ops(a float @%0, b float @%1) -> (float, float, float, float, float, bool, bool, float, int, int)
+        Insc::FloatAdd { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+        Insc::FloatSub { lhs_value: 0, rhs_value: 1, dest_value: 3 },
+        Insc::FloatMul { lhs_value: 0, rhs_value: 1, dest_value: 4 },
+        Insc::FloatDiv { lhs_value: 0, rhs_value: 1, dest_value: 5 },
+        Insc::FloatNeg { value: 0, dest_value: 6 },
+        Insc::FloatLt { lhs_value: 0, rhs_value: 1, dest_value: 7 },
+        Insc::FloatGe { lhs_value: 0, rhs_value: 1, dest_value: 8 },
+        Insc::MakeIntConst { c: 3, dest_value: 9 },
+        Insc::IntToFloat { value: 9, dest_value: 9 },
+        Insc::MakeFloatConst { c: -2.7, dest_value: 10 },
+        Insc::FloatToInt { value: 10, dest_value: 10 },
+        Insc::MakeFloatConst { c: 0.0, dest_value: 11 },
+        Insc::FloatDiv { lhs_value: 11, rhs_value: 11, dest_value: 11 },
+        Insc::FloatToInt { value: 11, dest_value: 11 },
+        Insc::ReturnMultiple { ret_values: vec![2, 3, 4, 5, 6, 7, 8, 9, 10, 11] }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 10, 12)
+    ], vec![]);
+
+    let rets = run_multiple(&program, &[Value::from(1.5f64), Value::from(0.5f64)], 10).unwrap();
+    let floats = rets[..5].iter().map(|v| v.as_float().unwrap()).collect::<Vec<_>>();
+    assert_eq!(floats, vec![2.0, 1.0, 0.75, 3.0, -1.5]);
+    assert_eq!(rets[5].as_bool(), Some(false));
+    assert_eq!(rets[6].as_bool(), Some(true));
+    assert_eq!(rets[7].as_float(), Some(3.0));
+    assert_eq!(rets[8].as_int(), Some(-2));
+    assert_eq!(rets[9].as_int(), Some(0));
+}
+
+#[test]
+fn test_char_bool_ops() {
+    let program = CompiledProgram::new(vec![
+        // char_bool_ops(c1 char @%0, c2 char @%1) -> (bool, bool, bool, bool, bool)
+        Insc::CharLt { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+        Insc::CharEq { lhs_value: 0, rhs_value: 1, dest_value: 3 },
+        Insc::MakeCharConst { c: 'a', dest_value: 4 },
+        Insc::CharGe { lhs_value: 0, rhs_value: 4, dest_value: 4 },
+        Insc::BoolAnd { lhs_value: 2, rhs_value: 3, dest_value: 5 },
+        Insc::BoolOr { lhs_value: 2, rhs_value: 3, dest_value: 6 },
+        Insc::MakeBoolConst { c: false, dest_value: 7 },
+        Insc::BoolNot { value: 7, dest_value: 7 },
+        Insc::ReturnMultiple { ret_values: vec![2, 3, 4, 5, 6, 7] }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 6, 8)
+    ], vec![]);
+
+    let rets = run_multiple(&program, &[Value::from('a'), Value::from('b')], 6).unwrap();
+    let bools = rets.iter().map(|v| v.as_bool().unwrap()).collect::<Vec<_>>();
+    assert_eq!(bools, vec![true, false, true, false, true, true]);
+}
+
+fn exception_code(e: &TError) -> f64 {
+    match e {
+        TError::UserException(..) => -1.0,
+        TError::UncheckedException(_) => -2.0,
+        _ => -3.0
+    }
+}
+
+fn sqrt_program(exception: Option<TypeId>) -> CompiledProgram {
+    let mut program = CompiledProgram::new(vec![
+        // entry(x float @%0) -> float
+        /*00*/ Insc::FuncCall { func_id: 1, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*01*/ Insc::ReturnOne { ret_value: 0 },
+        /*02*/ Insc::FFICall { func_id: 1, arg_values: vec![1], ret_value_locs: vec![0] },
+        /*03*/ Insc::ReturnOne { ret_value: 0 },
+
+        // sqrt(x float @%0) -> float
+        /*04*/ Insc::FFICall { func_id: 0, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*05*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 2),
+        CompiledFuncInfo::new(4, 1, 1, 1)
+    ], vec![
+        Box::new(RustFunction1 { f: checked_sqrt, _phantom: PhantomData }),
+        Box::new(RustFunction1 { f: exception_code, _phantom: PhantomData })
+    ]);
+    program.set_exception_handlers(0, vec![ExceptionHandler::new(0, 1, exception, 2, 1)]);
+    program
+}
+
+#[test]
+fn test_catch_exception() {
+    let program = sqrt_program(Some(TypeId::of::<std::fmt::Error>()));
+    let rets = run_multiple(&program, &[Value::from(16.0f64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(4.0));
+    let rets = run_multiple(&program, &[Value::from(-1.0f64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(-1.0));
+
+    let program = sqrt_program(None);
+    let rets = run_multiple(&program, &[Value::from(-1.0f64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(-1.0));
+
+    let program = sqrt_program(Some(TypeId::of::<std::io::Error>()));
+    let err = run_multiple(&program, &[Value::from(-1.0f64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::UserException(..)));
+    assert_eq!(err.insc_ptr, 4);
+    assert_eq!(err.ret_addrs, vec![1]);
+}
+
+#[test]
+fn test_rethrow_exception() {
+    let mut program = CompiledProgram::new(vec![
+        // entry(a int @%0, b int @%1) -> float
+        /*00*/ Insc::FuncCall { func_id: 1, arg_values: vec![0, 1], ret_value_locs: vec![0] },
+        /*01*/ Insc::IntToFloat { value: 0, dest_value: 0 },
+        /*02*/ Insc::ReturnOne { ret_value: 0 },
+        /*03*/ Insc::FFICall { func_id: 0, arg_values: vec![2], ret_value_locs: vec![0] },
+        /*04*/ Insc::ReturnOne { ret_value: 0 },
+
+        // div(a int @%0, b int @%1) -> int
+        /*05*/ Insc::IntDiv { lhs_value: 0, rhs_value: 1, dest_value: 0 },
+        /*06*/ Insc::ReturnOne { ret_value: 0 },
+        /*07*/ Insc::Throw { exception_value: 2 }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 1, 3),
+        CompiledFuncInfo::new(5, 2, 1, 3)
+    ], vec![
+        Box::new(RustFunction1 { f: exception_code, _phantom: PhantomData })
+    ]);
+    program.set_exception_handlers(0, vec![ExceptionHandler::catch_all(0, 1, 3, 2)]);
+    program.set_exception_handlers(1, vec![ExceptionHandler::catch_all(5, 6, 7, 2)]);
+
+    let rets = run_multiple(&program, &[Value::from(7i64), Value::from(2i64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(3.0));
+    let rets = run_multiple(&program, &[Value::from(7i64), Value::from(0i64)], 1).unwrap();
+    assert_eq!(rets[0].as_float(), Some(-2.0));
+}
+
+#[test]
+fn test_throw_non_exception() {
+    let program = CompiledProgram::new(vec![
+        Insc::Throw { exception_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 0, 1)
+    ], vec![]);
+    let err = run_multiple(&program, &[Value::from(1i64)], 0).err().unwrap();
+    assert!(matches!(*err.error, TError::TypeError(_)));
+}
+
+#[test]
+fn test_runtime_type_check() {
+    let program = CompiledProgram::new(vec![
+        // add(a @%0, b @%1) -> int
+        /*00*/ Insc::IntAdd { lhs_value: 0, rhs_value: 1, dest_value: 0 },
+        /*01*/ Insc::ReturnOne { ret_value: 0 },
+
+        // negate(x int @%0) -> int
+        /*02*/ Insc::IntNeg { value: 0, dest_value: 0 },
+        /*03*/ Insc::ReturnOne { ret_value: 0 },
+
+        // call_negate(x @%0) -> int
+        /*04*/ Insc::MakeIntConst { c: 1, dest_value: 1 },
+        /*05*/ Insc::FuncCall { func_id: 1, arg_values: vec![1], ret_value_locs: vec![1] },
+        /*06*/ Insc::IntAdd { lhs_value: 0, rhs_value: 1, dest_value: 0 },
+        /*07*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 2, 1, 2),
+        CompiledFuncInfo::new(2, 1, 1, 1),
+        CompiledFuncInfo::new(4, 1, 1, 2)
+    ], vec![]);
+    let program = verify(&program).unwrap();
+    assert!(matches!(program.inscs()[0], Insc::TypeChecked { .. }));
+    assert!(matches!(program.inscs()[2], Insc::IntNeg { .. }));
+    assert!(matches!(program.inscs()[6], Insc::TypeChecked { .. }));
+
+    let mut intake = OxTank::new_unlimited();
+    let mut ret_values = [MaybeUninit::uninit()];
+    unsafe {
+        RD93::run_func(&program, 0, &[Value::from(1i64), Value::from(2i64)], &mut ret_values,
+                       &mut intake)
+            .unwrap();
+        assert_eq!(ret_values[0].assume_init().as_int(), Some(3));
+
+        let err = RD93::run_func(&program, 0, &[Value::from(1i64), Value::from(2.0f64)],
+                                 &mut ret_values, &mut intake).err().unwrap();
+        assert!(matches!(*err.error, TError::TypeError(_)));
+        assert_eq!(err.insc_ptr, 0);
+        assert!(err.to_string().starts_with("TypeError: expected type i64("), "{}", err);
+        assert!(err.to_string().contains("got f64("), "{}", err);
+
+        let err = RD93::run_func(&program, 1, &[Value::from(true)], &mut ret_values, &mut intake)
+            .err().unwrap();
+        assert!(matches!(*err.error, TError::TypeError(_)));
+        assert_eq!(err.insc_ptr, 2);
+        assert!(!err.to_string().contains("<unknown>"), "{}", err);
+
+        let err = RD93::run_func(&program, 2, &[Value::from('a')], &mut ret_values, &mut intake)
+            .err().unwrap();
+        assert!(matches!(*err.error, TError::TypeError(_)));
+        assert_eq!(err.insc_ptr, 6);
+
+        let flat = FlatProgram::new(&program).unwrap();
+        let err = RD93::run_flat_func(&flat, 0, &[Value::from(1i64), Value::from(2.0f64)],
+                                      &mut ret_values, &mut intake).err().unwrap();
+        assert!(matches!(*err.error, TError::TypeError(_)));
+        assert_eq!(err.insc_ptr, 0);
+
+        let err = RD93::run_flat_func(&flat, 2, &[Value::from('a')], &mut ret_values, &mut intake)
+            .err().unwrap();
+        assert!(matches!(*err.error, TError::TypeError(_)));
+        assert_eq!(err.insc_ptr, 6);
+    }
+}
+
+#[test]
+fn test_assembly() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("math::mul", RustFunction { f: mul, _phantom: PhantomData }).unwrap();
+
+    let program = assemble("\
+ffi math::mul(int, int) -> (int)
+
+; factorial(n int @%0) -> int
+func factorial(1) -> 1, stack 3
+    make_int_const 1 -> %1
+    int_le %0, %1 -> %2
+    jump_if_true %2, base
+    int_sub %0, %1 -> %1
+    func_call factorial(%1) -> %1
+    ffi_call math::mul(%0, %1) -> %0
+    return_one %0
+base:
+    return_one %1
+", &registry).unwrap();
+
+    let rets = run_multiple(&program, &[Value::from(10i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(3628800));
+
+    let text = disassemble(&program, &registry).unwrap();
+    assert!(text.contains("func_call f0(%1) -> %1"));
+    let reassembled = assemble(&text, &registry).unwrap();
+    let rets = run_multiple(&reassembled, &[Value::from(5i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(120));
+}
+
+#[test]
+fn test_binary_module() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("math::mul", RustFunction { f: mul, _phantom: PhantomData }).unwrap();
+
+    let program = assemble("\
+ffi math::mul(int, int) -> (int)
+
+; square(x int @%0) -> int
+func square(1) -> 1, stack 1
+    ffi_call math::mul(%0, %0) -> %0
+    return_one %0
+", &registry).unwrap();
+    let bytes = encode_module(&program, &registry).unwrap();
+
+    // 在另一个注册表中加载模块，宿主函数按名字重新链接
+    let mut other_registry = FunctionRegistry::new();
+    other_registry.register("math::mul", RustFunction { f: mul, _phantom: PhantomData }).unwrap();
+    let loaded = decode_module(&bytes, &other_registry).unwrap();
+    let rets = run_multiple(&loaded, &[Value::from(12i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(144));
+
+    assert!(decode_module(&bytes, &FunctionRegistry::new()).is_err());
+}
+
+fn countdown_program(entry_catches: bool) -> CompiledProgram {
+    let mut program = CompiledProgram::new(vec![
+        // entry(n int @%0) -> int
+        /*00*/ Insc::FuncCall { func_id: 1, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*01*/ Insc::ReturnOne { ret_value: 0 },
+        /*02*/ Insc::MakeIntConst { c: -1, dest_value: 0 },
+        /*03*/ Insc::ReturnOne { ret_value: 0 },
+
+        // countdown(n int @%0) -> int
+        /*04*/ Insc::MakeIntConst { c: 0, dest_value: 1 },
+        /*05*/ Insc::IntEq { lhs_value: 0, rhs_value: 1, dest_value: 2 },
+        /*06*/ Insc::JumpIfTrue { cond_value: 2, jump_dest: 11 },
+        /*07*/ Insc::MakeIntConst { c: 1, dest_value: 1 },
+        /*08*/ Insc::IntSub { lhs_value: 0, rhs_value: 1, dest_value: 0 },
+        /*09*/ Insc::FuncCall { func_id: 1, arg_values: vec![0], ret_value_locs: vec![0] },
+        /*10*/ Insc::ReturnOne { ret_value: 0 },
+        /*11*/ Insc::ReturnOne { ret_value: 0 }
+    ], vec![
+        CompiledFuncInfo::new(0, 1, 1, 2),
+        CompiledFuncInfo::new(4, 1, 1, 3)
+    ], vec![]);
+    if entry_catches {
+        program.set_exception_handlers(0, vec![
+            ExceptionHandler::new(0, 1, Some(TypeId::of::<StackOverflow>()), 2, 1)
+        ]);
+    }
+    program
+}
+
+#[test]
+fn test_stack_overflow() {
+    let program = countdown_program(false);
+    let rets = run_multiple(&program, &[Value::from(1000i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(0));
+
+    let err = run_multiple(&program, &[Value::from(1_000_000i64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::StackOverflow(_)));
+    assert_eq!(err.insc_ptr, 9);
+    assert_eq!(err.ret_addrs.len() + 1, StackConfig::default().max_depth);
+
+    let program = countdown_program(true);
+    let rets = run_multiple(&program, &[Value::from(1_000_000i64)], 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(-1));
+}
+
+#[test]
+fn test_reuse_stack() {
+    let program = countdown_program(false);
+    let verified = verify(&program).unwrap();
+    let flat = FlatProgram::new(&verified).unwrap();
+    let mut stack = Stack::with_config(StackConfig::new(10, 1024));
+
+    let mut run = |n: i64, flat_backend: bool| {
+        let mut intake = OxTank::new_unlimited();
+        let mut ret_values = vec![MaybeUninit::uninit()];
+        unsafe {
+            if flat_backend {
+                RD93::run_flat_func_with_stack(&mut stack, &flat, 0, &[Value::from(n)],
+                                               &mut ret_values, &mut intake)
+            } else {
+                RD93::run_func_with_stack(&mut stack, &verified, 0, &[Value::from(n)],
+                                          &mut ret_values, &mut intake)
+            }.map(|_| ret_values[0].assume_init().as_int())
+        }
+    };
+
+    for &flat_backend in &[false, true] {
+        // entry 和 countdown(8) .. countdown(0) 一共 10 个栈帧
+        assert_eq!(run(8, flat_backend).unwrap(), Some(0));
+        let err = run(9, flat_backend).err().unwrap();
+        assert!(matches!(*err.error, TError::StackOverflow(_)));
+        // 溢出之后栈上残留的栈帧不影响下一次调用
+        assert_eq!(run(3, flat_backend).unwrap(), Some(0));
+    }
+
+    // 槽位个数同样受到限制：每个 countdown 栈帧占用 3 个槽位
+    let mut stack = Stack::with_config(StackConfig::new(1024, 2 + 3 * 4));
+    let mut intake = OxTank::new_unlimited();
+    let mut ret_values = vec![MaybeUninit::uninit()];
+    unsafe {
+        RD93::run_func_with_stack(&mut stack, &verified, 0, &[Value::from(3i64)], &mut ret_values,
+                                  &mut intake)
+            .unwrap();
+        let err = RD93::run_func_with_stack(&mut stack, &verified, 0, &[Value::from(4i64)],
+                                            &mut ret_values, &mut intake).err().unwrap();
+        assert!(matches!(*err.error, TError::StackOverflow(_)));
+    }
+}
+
+#[test]
+fn test_tail_call() {
+    let registry = FunctionRegistry::new();
+    let program = assemble("\
+; entry(a int @%0, b int @%1, k int @%2) -> int
+func entry(3) -> 1, stack 3
+    func_call swap(%0, %1, %2) -> %2
+    int_add %2, %2 -> %0
+    return_one %0
+
+; swap(a int @%0, b int @%1, k int @%2) -> int，交换 k 次参数之后返回第一个参数
+func swap(3) -> 1, stack 4
+    make_int_const 0 -> %3
+    int_eq %2, %3 -> %3
+    jump_if_true %3, done
+    make_int_const 1 -> %3
+    int_sub %2, %3 -> %2
+    tail_call padded(%1, %0, %2)
+done:
+    return_one %0
+
+; padded(a int @%0, b int @%1, k int @%2) -> int，栈帧比 swap 更大
+func padded(3) -> 1, stack 8
+    tail_call swap(%0, %1, %2)
+", &registry).unwrap();
+
+    let args = |k: i64| [Value::from(1i64), Value::from(2i64), Value::from(k)];
+    let rets = run_multiple(&program, &args(3), 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(4));
+    let rets = run_multiple(&program, &args(4), 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(2));
+
+    // 尾递归只占用固定的栈空间，远超过默认的栈深度限制也不会溢出
+    let rets = run_multiple(&program, &args(1_000_001), 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(4));
+    let verified = verify(&program).unwrap();
+    let mut stack = Stack::with_config(StackConfig::new(2, 3 + 8));
+    let mut intake = OxTank::new_unlimited();
+    let mut ret_values = vec![MaybeUninit::uninit()];
+    unsafe {
+        RD93::run_func_with_stack(&mut stack, &verified, 0, &args(1001), &mut ret_values,
+                                  &mut intake).unwrap();
+        assert_eq!(ret_values[0].assume_init().as_int(), Some(4));
+    }
+
+    let text = disassemble(&program, &registry).unwrap();
+    assert!(text.contains("tail_call f2(%1, %0, %2)"));
+    let bytes = encode_module(&assemble(&text, &registry).unwrap(), &registry).unwrap();
+    let loaded = decode_module(&bytes, &registry).unwrap();
+    let rets = run_multiple(&loaded, &args(5), 1).unwrap();
+    assert_eq!(rets[0].as_int(), Some(4));
+}
+
+fn apply(vm: &mut dyn VmContext, func_id: i64, x: i64) -> Result<i64, TError> {
+    let rets = vm.call_func(func_id as usize, &[Value::from(x)])?;
+    Ok(rets[0].as_int().unwrap())
+}
+
+fn try_apply(vm: &mut dyn VmContext, func_id: i64, x: i64) -> i64 {
+    match vm.call_func(func_id as usize, &[Value::from(x)]) {
+        Ok(rets) => rets[0].as_int().unwrap(),
+        Err(_) => -1
+    }
+}
+
+#[test]
+fn test_reentrant_call() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("host::apply", RustVmFunction2 { f: apply, _phantom: PhantomData }).unwrap();
+    registry.register("host::try_apply", RustVmFunction2 { f: try_apply, _phantom: PhantomData })
+        .unwrap();
+
+    let program = assemble("\
+ffi host::apply(int, int) -> (int)
+ffi host::try_apply(int, int) -> (int)
+
+; entry(x int @%0) -> int，返回 twice(x) + x
+func entry(1) -> 1, stack 3
+    make_int_const 1 -> %1
+    ffi_call host::apply(%1, %0) -> %2
+    int_add %2, %0 -> %0
+    return_one %0
+
+; twice(x int @%0) -> int，返回 square(x) * 2
+func twice(1) -> 1, stack 2
+    make_int_const 2 -> %1
+    ffi_call host::apply(%1, %0) -> %0
+    int_add %0, %0 -> %0
+    return_one %0
+
+func square(1) -> 1, stack 1
+    int_mul %0, %0 -> %0
+    return_one %0
+
+; div_by(x int @%0) -> int，x 为 0 时抛出异常
+func div_by(1) -> 1, stack 2
+    make_int_const 100 -> %1
+    int_div %1, %0 -> %0
+    return_one %0
+
+; safe(x int @%0) -> int，宿主函数自己处理回调中的异常
+func safe(1) -> 1, stack 2
+    make_int_const 3 -> %1
+    ffi_call host::try_apply(%1, %0) -> %0
+    return_one %0
+
+; caught(x int @%0) -> int，回调中的异常经过宿主函数回到 VM，由这里的异常处理器捕获
+func caught(1) -> 1, stack 2
+    handler *, try, try_end, catch -> %1
+try:
+    make_int_const 3 -> %1
+    ffi_call host::apply(%1, %0) -> %0
+try_end:
+    return_one %0
+catch:
+    make_int_const -2 -> %0
+    return_one %0
+
+; relay(x int @%0) -> int，不处理回调中的异常
+func relay(1) -> 1, stack 2
+    make_int_const 3 -> %1
+    ffi_call host::apply(%1, %0) -> %0
+    return_one %0
+
+; nest(n int @%0) -> int，经过宿主函数递归 n 层
+func nest(1) -> 1, stack 3
+    make_int_const 0 -> %1
+    int_eq %0, %1 -> %2
+    jump_if_true %2, done
+    make_int_const 1 -> %1
+    int_sub %0, %1 -> %0
+    make_int_const 7 -> %1
+    ffi_call host::apply(%1, %0) -> %0
+done:
+    return_one %0
+", &registry).unwrap();
+
+    let verified = verify(&program).unwrap();
+    let flat = FlatProgram::new(&verified).unwrap();
+    let mut stack = Stack::with_config(StackConfig { max_nesting: 4, ..StackConfig::default() });
+    let mut run = |func_id: usize, x: i64| {
+        let mut intake = OxTank::new_unlimited();
+        let mut ret_values = vec![MaybeUninit::uninit()];
+        let result = unsafe {
+            RD93::run_func_with_stack(&mut stack, &verified, func_id, &[Value::from(x)],
+                                      &mut ret_values, &mut intake)
+        }.map(|_| unsafe { ret_values[0].assume_init().as_int() });
+        // 无论成功与否，嵌套调用的栈帧都已经全部弹出
+        assert_eq!(stack.depth(), 0);
+
+        let mut flat_ret_values = vec![MaybeUninit::uninit()];
+        let flat_result = unsafe {
+            RD93::run_flat_func_with_stack(&mut stack, &flat, func_id, &[Value::from(x)],
+                                           &mut flat_ret_values, &mut intake)
+        }.map(|_| unsafe { flat_ret_values[0].assume_init().as_int() });
+        assert_eq!(stack.depth(), 0);
+
+        match (&result, &flat_result) {
+            (Ok(rets), Ok(flat_rets)) => assert_eq!(rets, flat_rets),
+            (Err(e), Err(flat_e)) => assert_eq!(e.to_string(), flat_e.to_string()),
+            _ => panic!("backends disagree on whether the call succeeds")
+        }
+        result
+    };
+
+    // 外层栈帧中的值在嵌套调用之后保持不变
+    assert_eq!(run(0, 3).unwrap(), Some(21));
+
+    assert_eq!(run(4, 4).unwrap(), Some(25));
+    assert_eq!(run(4, 0).unwrap(), Some(-1));
+    assert_eq!(run(5, 5).unwrap(), Some(20));
+    assert_eq!(run(5, 0).unwrap(), Some(-2));
+
+    // 宿主函数原样返回的异常不会被再次包装
+    let err = run(6, 0).err().unwrap();
+    assert!(matches!(*err.error, TError::UncheckedException(_)));
+
+    assert_eq!(run(7, 3).unwrap(), Some(0));
+    let err = run(7, 4).err().unwrap();
+    assert!(matches!(*err.error, TError::StackOverflow(_)));
+}
+
+struct Counter(i64);
+
+fn bump(counter: &mut Counter) -> i64 {
+    counter.0 += 1;
+    counter.0
+}
+
+fn peek(counter: &Counter) -> i64 {
+    counter.0
+}
+
+#[test]
+fn test_with_borrowed() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("host::bump", RustFunction1 { f: bump, _phantom: PhantomData }).unwrap();
+    registry.register("host::peek", RustFunction1 { f: peek, _phantom: PhantomData }).unwrap();
+
+    let program = |name: &str| assemble(&format!("\
+ffi host::{}(any) -> (int)
+
+func entry(1) -> 1, stack 1
+    ffi_call host::{}(%0) -> %0
+    return_one %0
+", name, name), &registry).unwrap();
+    let bump_program = program("bump");
+    let peek_program = program("peek");
+    let run = |program: &CompiledProgram, value: Value| -> Result<Option<i64>, VmError> {
+        let mut intake = OxTank::new_unlimited();
+        let mut ret_values = vec![MaybeUninit::uninit()];
+        unsafe {
+            RD93::run_func(&verify(program).unwrap(), 0, &[value], &mut ret_values, &mut intake)?;
+            Ok(ret_values[0].assume_init().as_int())
+        }
+    };
+
+    let mut counter = Counter(0);
+    let mut intake = RampedIntake::default();
+    let stash = intake.with_borrowed_mut(&mut counter, |value| {
+        assert_eq!(run(&bump_program, value).unwrap(), Some(1));
+        assert_eq!(run(&bump_program, value).unwrap(), Some(2));
+        assert_eq!(value.gc_info(), GcInfo::MutSharedFromHost);
+        value
+    }).unwrap();
+    assert_eq!(counter.0, 2);
+    assert_eq!(stash.gc_info(), GcInfo::Dropped);
+    let err = run(&bump_program, stash).err().unwrap();
+    assert!(matches!(*err.error, TError::LifetimeError(_)));
+
+    let stash = intake.with_borrowed(&counter, |value| {
+        assert_eq!(run(&peek_program, value).unwrap(), Some(2));
+        value
+    }).unwrap();
+    let err = run(&peek_program, stash).err().unwrap();
+    assert!(matches!(*err.error, TError::LifetimeError(_)));
+
+    intake.force_collect();
+    assert_eq!(intake.object_count(), 0);
+    drop(intake);
+    assert_eq!(counter.0, 2);
+}
+
+#[test]
+fn test_ffi_object_type_check() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("host::peek", RustFunction1 { f: peek, _phantom: PhantomData }).unwrap();
+    let program = assemble("\
+ffi host::peek(any) -> (int)
+
+func entry(1) -> 1, stack 1
+    ffi_call host::peek(%0) -> %0
+    return_one %0
+", &registry).unwrap();
+
+    // 推导无法证明对象参数的类型，两个后端都必须在调用之前检查，debug 和 release 构建均是如此
+    let counter = Counter(20);
+    let mut intake = RampedIntake::default();
+    intake.with_borrowed(&counter, |value| {
+        let rets = run_multiple(&program, &[value], 1).unwrap();
+        assert_eq!(rets[0].as_int(), Some(20));
+    }).unwrap();
+    intake.with_borrowed(&String::from("not a counter"), |value| {
+        let err = run_multiple(&program, &[value], 1).err().unwrap();
+        if let TError::TypeError(e) = *err.error {
+            assert!(e.required_name.unwrap().ends_with("Counter"));
+            assert!(e.actual_name.unwrap().ends_with("String"));
+        } else {
+            panic!("expected a TypeError");
+        }
+    }).unwrap();
+    let err = run_multiple(&program, &[Value::from(20i64)], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::TypeError(_)));
+    let err = run_multiple(&program, &[Value::null()], 1).err().unwrap();
+    assert!(matches!(*err.error, TError::NullError(_)));
+}
+
+/// 两次调用 `host::track`，除以零之后由异常处理器返回第二个 `Tracked` 对象
+fn track_program() -> CompiledProgram {
+    let mut registry = FunctionRegistry::new();
+    registry.register("host::track", RustFunction1 { f: track, _phantom: PhantomData }).unwrap();
+    assemble("\
+ffi host::track(int) -> (any)
+
+; entry(x int @%0) -> any
+func entry(1) -> 1, stack 3
+    handler *, try, try_end, catch -> %2
+    make_int_const 0 -> %1
+try:
+    ffi_call host::track(%0) -> %1
+    ffi_call host::track(%0) -> %1
+    make_int_const 0 -> %2
+    int_div %0, %2 -> %0
+try_end:
+    return_one %0
+catch:
+    return_one %1
+", &registry).unwrap()
+}
+
+fn run_track(
+    verified: &VerifiedProgram,
+    flat_backend: bool,
+    intake: &mut dyn Adopt
+) -> Result<Value, VmError> {
+    run_collect(1, |rets| unsafe {
+        if flat_backend {
+            let flat = FlatProgram::new(verified).unwrap();
+            RD93::run_flat_func(&flat, 0, &[Value::from(5i64)], rets, intake)
+        } else {
+            RD93::run_func(verified, 0, &[Value::from(5i64)], rets, intake)
+        }
+    }).map(|rets| rets[0])
+}
+
+#[test]
+fn test_ffi_return_adopted() {
+    let program = track_program();
+    let verified = verify(&program).unwrap();
+
+    for &flat_backend in &[false, true] {
+        check_ffi_return_adopted(|intake| run_track(&verified, flat_backend, intake), 2);
+    }
+}
+
+#[test]
+fn test_ffi_return_adopted_with_collect() {
+    let program = track_program();
+    let verified = verify(&program).unwrap();
+
+    for &flat_backend in &[false, true] {
+        let drops_before = drops();
+
+        // 每接管一个对象都会超出债务上限，但执行期间不会回收 VM 栈上仍在使用的对象
+        let mut intake = RampedIntake::new(IntakeOptions::new(1, 1));
+        intake.enable_collect();
+        let ret = run_track(&verified, flat_backend, &mut intake).unwrap();
+        assert_eq!(unsafe { ret.as_ref::<Tracked>() }.0, 5);
+        assert_eq!(intake.object_count(), 3);
+        assert_eq!(drops(), drops_before);
+
+        // 返回值不在根集合中，下一次分配之前的回收会释放所有接管的对象
+        intake.allocate(0i64).unwrap();
+        assert_eq!(intake.object_count(), 1);
+        assert_eq!(drops(), drops_before + 2);
+    }
+}
+
+fn big() -> String {
+    "x".repeat(8192)
+}
+
+#[test]
+fn test_ffi_adopt_failure_in_try() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("host::big", RustFunction0 { f: big, _phantom: PhantomData }).unwrap();
+    let program = assemble("\
+ffi host::big() -> (any)
+
+; entry() -> int，返回值接管失败时，异常处理器看到的 %0 是空值而不是整数
+func entry(0) -> 1, stack 2
+    handler *, try, try_end, catch -> %1
+    make_int_const 5 -> %0
+try:
+    ffi_call host::big() -> %0
+try_end:
+    return_one %0
+catch:
+    int_add %0, %0 -> %0
+    return_one %0
+", &registry).unwrap();
+    let verified = verify(&program).unwrap();
+    let flat = FlatProgram::new(&verified).unwrap();
+
+    for &flat_backend in &[false, true] {
+        let mut intake = OxTank::new_limited(4096);
+        let mut ret_values = vec![MaybeUninit::uninit()];
+        let err = unsafe {
+            if flat_backend {
+                RD93::run_flat_func(&flat, 0, &[], &mut ret_values, &mut intake)
+            } else {
+                RD93::run_func(&verified, 0, &[], &mut ret_values, &mut intake)
+            }
+        }.err().unwrap();
+        assert!(matches!(*err.error, TError::NullError(_)), "{}", err);
+    }
+}